@@ -8,8 +8,13 @@
 //! transform the state each frame.
 //!
 //! This has some limitations because everything before the frame function (e.g. initialization)
-//! can not be hot-reloaded. If a struct is changed, it will crash the game because the struct
-//! that was initialized in `main()` doesn't match the struct that the frame function expects. Also,
+//! can not be hot-reloaded. Changing a struct used to crash the game because the struct that was
+//! initialized in `main()` didn't match the struct that the frame function expected, so now the
+//! old library saves the main scene in a versioned snapshot before unloading, and the new
+//! library loads it (see `logic::hot_reload`). If the snapshot can not be loaded, a new game is
+//! started and the reason is printed. The introduction scene is not saved, and the helpers in
+//! this file that touch the scene directly (reloading textures, changing the UI backend) use the
+//! struct layout compiled into this executable, so avoid them after changing a struct. Also,
 //! we do dependency injection for the graphics backend, which means we instantiate the concrete
 //! implementation in `main()`, so we can not hot-reload the code for those concrete implementations
 //! of the graphics (and input) interfaces. (We can not create the Macroquad backend in the dynamic
@@ -28,13 +33,17 @@
 //! it takes ~0.5 seconds from starting the build of the library until I see the changes live, for
 //! simple changes.
 //!
+//! The tileset is reloaded as well when `assets/image/tileset.png` changes on disk (or when
+//! pressing T in the main scene). If the new image can not be used, the error is printed and the
+//! previous textures are kept.
+//!
 //! Most of these ideas about hot-reloading came from <https://fasterthanli.me/articles/so-you-want-to-live-reload-rust>
 //!
 //! See <https://jmmut.github.io/2023/03/17/Hot-reloading-Rust-and-Macroquad.html> for the specifics
 //! of hot-reloading Macroquad.
 
 use bioengineer::common::cli::{CliArgs, UiBackend};
-use bioengineer::external::assets_macroquad::load_tileset;
+use bioengineer::external::assets_macroquad::try_load_tileset;
 use clap::Parser;
 use juquad::fps::sleep_until_next_frame;
 use macroquad::input::is_key_pressed;
@@ -43,12 +52,13 @@ use macroquad::window::next_frame;
 use macroquad::window::Conf;
 use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
 use std::ffi::{c_char, c_int, c_void, CString};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Receiver;
 
 use bioengineer::external::backends::{
     create_introduction_scene, create_main_scene, drawer_factory, TILESET_PATH,
 };
+use logic::hot_reload::{HotReloadState, RestoredScene};
 use logic::scene::GameLoopState;
 use logic::screen::gui::set_skin;
 use logic::world::map::chunk::chunks::cache::print_cache_stats;
@@ -66,6 +76,9 @@ pub const RTLD_LAZY: c_int = 0x00001;
 type AnyError = Box<dyn std::error::Error>;
 
 pub type DrawFrameFunction = extern "C" fn(scene_wrapper: &mut Box<SceneState>) -> GameLoopState;
+pub type SaveStateFunction =
+    extern "C" fn(scene_wrapper: Box<SceneState>) -> Box<Result<HotReloadState, Box<SceneState>>>;
+pub type RestoreStateFunction = extern "C" fn(state: Box<HotReloadState>) -> Box<RestoredScene>;
 
 #[link(name = "dl")]
 extern "C" {
@@ -84,9 +97,13 @@ async fn main() -> Result<(), AnyError> {
     let textures = {
         let mut scene = create_introduction_scene(&args).await;
         while draw_frame(&mut scene).should_continue() {
-            if should_reload(&rx) {
+            let changes = pending_changes(&rx);
+            if changes.library {
                 info!("reloading lib");
-                (draw_frame, lib_handle) = reload(lib_handle)?;
+                (draw_frame, lib_handle, scene) = reload_keeping_state(scene, lib_handle)?;
+            }
+            if changes.tileset {
+                reload_tileset(scene.as_mut()).await;
             }
             sleep_until_next_frame(&mut previous_time).await
         }
@@ -97,14 +114,13 @@ async fn main() -> Result<(), AnyError> {
     {
         let mut scene = create_main_scene(&args, textures).await;
         while draw_frame(&mut scene).should_continue() {
-            if should_reload(&rx) {
+            let changes = pending_changes(&rx);
+            if changes.library {
                 info!("reloading lib");
-                (draw_frame, lib_handle) = reload(lib_handle)?;
+                (draw_frame, lib_handle, scene) = reload_keeping_state(scene, lib_handle)?;
             }
-            if is_key_pressed(KeyCode::T) {
-                scene
-                    .as_mut()
-                    .set_textures(load_tileset(TILESET_PATH).await);
+            if changes.tileset || is_key_pressed(KeyCode::T) {
+                reload_tileset(scene.as_mut()).await;
             }
             if is_key_pressed(KeyCode::LeftControl) && is_key_pressed(KeyCode::Q) {
                 swap_ui_backend(scene.as_mut(), &mut args, UiBackend::Macroquad);
@@ -147,6 +163,13 @@ fn load() -> Result<(DrawFrameFunction, *const c_void), AnyError> {
         .into());
     }
 
+    let function = load_function(lib, function_name)?;
+    use std::mem::transmute;
+    let transmuted_function: DrawFrameFunction = unsafe { transmute(function) };
+    Ok((transmuted_function, lib))
+}
+
+fn load_function(lib: *const c_void, function_name: &str) -> Result<*const c_void, AnyError> {
     let function_name = CString::new(function_name).unwrap();
     let function = unsafe { dlsym(lib, function_name.as_ptr()) };
     if function.is_null() {
@@ -156,13 +179,31 @@ fn load() -> Result<(DrawFrameFunction, *const c_void), AnyError> {
         )
         .into());
     }
-    use std::mem::transmute;
-    let transmuted_function: DrawFrameFunction = unsafe { transmute(function) };
-    Ok((transmuted_function, lib))
+    Ok(function)
 }
 
-fn should_reload(rx: &Receiver<()>) -> bool {
-    rx.try_recv().is_ok()
+/// A file that the watcher detected as changed on disk.
+enum WatchedFile {
+    Library,
+    Tileset,
+}
+
+#[derive(Default)]
+struct Changes {
+    library: bool,
+    tileset: bool,
+}
+
+/// Saving a file usually triggers several events, so all the pending ones are merged.
+fn pending_changes(rx: &Receiver<WatchedFile>) -> Changes {
+    let mut changes = Changes::default();
+    for file in rx.try_iter() {
+        match file {
+            WatchedFile::Library => changes.library = true,
+            WatchedFile::Tileset => changes.tileset = true,
+        }
+    }
+    changes
 
     // use macroquad::prelude::draw_text;
     // use macroquad::color::BLACK;
@@ -178,6 +219,47 @@ fn reload(lib: *const c_void) -> Result<(DrawFrameFunction, *const c_void), AnyE
     load()
 }
 
+/// The old library saves the scene before being unloaded, and the new one loads it, so that the
+/// structs of the logic crate can change between reloads.
+fn reload_keeping_state(
+    scene: Box<SceneState>,
+    lib: *const c_void,
+) -> Result<(DrawFrameFunction, *const c_void, Box<SceneState>), AnyError> {
+    use std::mem::transmute;
+    let save_state: SaveStateFunction =
+        unsafe { transmute(load_function(lib, "hot_reload_save_state")?) };
+    let saved = *save_state(scene);
+    let (draw_frame, lib) = reload(lib)?;
+    let scene = match saved {
+        Ok(state) => {
+            let restore_state: RestoreStateFunction =
+                unsafe { transmute(load_function(lib, "hot_reload_restore_state")?) };
+            let restored = *restore_state(Box::new(state));
+            if let Some(error) = restored.error {
+                eprintln!(
+                    "Could not migrate the game state to the reloaded library, started a new game instead: {}",
+                    error
+                );
+            }
+            restored.scene
+        }
+        // not saved, so it will only work if its structs didn't change
+        Err(scene) => scene,
+    };
+    Ok((draw_frame, lib, scene))
+}
+
+async fn reload_tileset(scene: &mut SceneState) {
+    info!("reloading tileset");
+    match try_load_tileset(TILESET_PATH).await {
+        Ok(textures) => scene.set_textures(textures),
+        Err(error) => eprintln!(
+            "Could not reload the tileset, keeping the previous one: {}",
+            error
+        ),
+    }
+}
+
 fn unload(lib: *const c_void) {
     if !lib.is_null() {
         unsafe {
@@ -186,24 +268,32 @@ fn unload(lib: *const c_void) {
     }
 }
 
-fn watch() -> Result<(RecommendedWatcher, Receiver<()>), AnyError> {
+fn watch() -> Result<(RecommendedWatcher, Receiver<WatchedFile>), AnyError> {
     let base = PathBuf::from(".").canonicalize().unwrap();
     let libname = "liblogic.so";
     let relative_path = PathBuf::from("target").join("debug").join(libname);
     // let absolute_path = base.join(&relative_path);
+    let tileset_path = PathBuf::from(TILESET_PATH);
 
     // here's our watcher to communicate between the watcher thread
     // (using `tx`, the "transmitter") and the main thread (using
     // `rx`, the "receiver").
-    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    let (tx, rx) = std::sync::mpsc::channel::<WatchedFile>();
 
     let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
         match res {
             Ok(event) => {
+                let changed = |path: &Path| event.paths.iter().any(|x| x.ends_with(path));
                 if let notify::EventKind::Create(_) = event.kind {
-                    if event.paths.iter().any(|x| x.ends_with(&relative_path)) {
+                    if changed(&relative_path) {
                         // signal that we need to reload
-                        tx.send(()).unwrap();
+                        tx.send(WatchedFile::Library).unwrap();
+                    }
+                }
+                // image editors may overwrite the file instead of creating a new one
+                if let notify::EventKind::Create(_) | notify::EventKind::Modify(_) = event.kind {
+                    if changed(&tileset_path) {
+                        tx.send(WatchedFile::Tileset).unwrap();
                     }
                 }
             }
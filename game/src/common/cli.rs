@@ -1,8 +1,9 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use git_version::git_version;
 use std::str::FromStr;
 
 pub const GIT_VERSION: &str = git_version!(args = ["--tags"]);
+pub const DEFAULT_BALANCE_PATH: &str = "assets/balance.txt";
 
 #[derive(Parser, Debug)]
 #[clap(version = GIT_VERSION)]
@@ -22,6 +23,107 @@ pub struct CliArgs {
         default_value = "macroquad"
     )]
     pub ui: UiBackend,
+
+    #[clap(
+        long,
+        help = "File with the keybindings. Changing keys in game will save them here.",
+        default_value = "assets/keybindings.txt"
+    )]
+    pub keybindings: String,
+
+    #[clap(
+        long,
+        help = "File with the economy and goal values. Changes are applied while playing. [default: assets/balance.txt]"
+    )]
+    pub balance: Option<String>,
+
+    #[clap(
+        long,
+        help = "Check the consistency of the world every tick and print any problem. Slow."
+    )]
+    pub validate: bool,
+
+    #[clap(
+        long,
+        help = "File with the starting map, resources, robots and buildable tiles, instead of a random map."
+    )]
+    pub scenario: Option<String>,
+
+    #[clap(
+        long,
+        help = "File with the objectives of the campaign, like assets/objectives.txt. Not used with --load, which continues the campaign of the snapshot."
+    )]
+    pub objectives: Option<String>,
+
+    #[clap(
+        long,
+        help = "File with developer console commands, one per line, to run when the game starts."
+    )]
+    pub script: Option<String>,
+
+    #[clap(
+        long,
+        help = "Listen on this localhost port for line-delimited JSON commands from external scripts."
+    )]
+    pub control_port: Option<u16>,
+
+    #[clap(
+        long,
+        help = "Snapshot file of a game to continue, like the ones made by 'import-vox'."
+    )]
+    pub load: Option<String>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl CliArgs {
+    /// The default balance file can be missing and then the default values are used, but a file
+    /// given with `--balance` has to exist.
+    pub fn balance_path(&self) -> Result<&str, String> {
+        match &self.balance {
+            None => Ok(DEFAULT_BALANCE_PATH),
+            Some(path) if std::path::Path::new(path).is_file() => Ok(path),
+            Some(path) => Err(format!("the balance file {} does not exist", path)),
+        }
+    }
+}
+
+/// Tools that run without opening the game window.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[clap(about = "Write a map as a MagicaVoxel .vox file, from a snapshot or a generated seed.")]
+    ExportVox {
+        #[clap(help = "The .vox file to write.")]
+        output: String,
+
+        #[clap(
+            long,
+            help = "Snapshot file to take the map from, instead of generating one."
+        )]
+        save: Option<String>,
+
+        #[clap(
+            long,
+            help = "Map generator: island, simplex, caves, archipelago or mountains, optionally with parameters like 'caves hollowness=0.5'.",
+            default_value = "simplex"
+        )]
+        map: String,
+
+        #[clap(long, help = "Seed of the generated map. Random if missing.")]
+        seed: Option<i64>,
+    },
+
+    #[clap(
+        about = "Build a map from a MagicaVoxel .vox file and write it as a snapshot to play with --load."
+    )]
+    ImportVox {
+        #[clap(help = "The .vox file to read.")]
+        input: String,
+
+        #[clap(help = "The snapshot file to write.")]
+        output: String,
+    },
 }
 
 #[derive(Debug, Copy, Clone)]
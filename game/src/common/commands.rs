@@ -0,0 +1,48 @@
+use crate::common::cli::Command;
+use logic::common::snapshot::{Snapshot, SnapshotReader, SnapshotWriter};
+use logic::world::map::{Map, MapType};
+use logic::world::World;
+
+pub fn run_command(command: &Command) -> Result<(), String> {
+    match command {
+        Command::ExportVox {
+            output,
+            save,
+            map,
+            seed,
+        } => {
+            let map = match save {
+                Some(path) => Map::load(&read_snapshot(path)?)?,
+                None => {
+                    let map_type = MapType::parse(map).map_err(|e| {
+                        format!(
+                            "{}, expected island, simplex, caves, archipelago or mountains, optionally with parameters like 'caves hollowness=0.5'",
+                            e
+                        )
+                    })?;
+                    Map::new_generated_with_seed(map_type, *seed)
+                }
+            };
+            write(output, &map.to_vox()?)
+        }
+        Command::ImportVox { input, output } => {
+            let bytes =
+                std::fs::read(input).map_err(|e| format!("could not read {}: {}", input, e))?;
+            let map = Map::from_vox(&bytes).map_err(|e| format!("in {}: {}", input, e))?;
+            let world = World::new_with_built_map(false, false, map);
+            let mut snapshot = SnapshotWriter::new();
+            world.save(&mut snapshot);
+            write(output, snapshot.finish().as_bytes())
+        }
+    }
+}
+
+pub fn read_snapshot(path: &str) -> Result<SnapshotReader, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    SnapshotReader::parse(&text).map_err(|e| format!("in {}: {}", path, e))
+}
+
+fn write(path: &str, bytes: &[u8]) -> Result<(), String> {
+    std::fs::write(path, bytes).map_err(|e| format!("could not write {}: {}", path, e))
+}
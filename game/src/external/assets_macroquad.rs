@@ -1,8 +1,9 @@
 use logic::screen::assets::{
-    crop, extract_images, zoom, PIXELS_PER_TILE_HEIGHT, PIXELS_PER_TILE_WIDTH,
+    check_tileset, crop, extract_images, zoom, PIXELS_PER_TILE_HEIGHT, PIXELS_PER_TILE_WIDTH,
 };
 use logic::world::map::cell::ExtraTextures;
 use logic::world::map::cell::TextureIndexTrait;
+use macroquad::file::load_file;
 use mq_basics::{load_image, FilterMode, Image, Texture2D};
 
 pub async fn load_tileset(path: &str) -> Vec<Texture2D> {
@@ -11,6 +12,19 @@ pub async fn load_tileset(path: &str) -> Vec<Texture2D> {
     split_tileset(&image)
 }
 
+/// Like `load_tileset`, but returns an error instead of panicking if the image is missing or
+/// malformed.
+pub async fn try_load_tileset(path: &str) -> Result<Vec<Texture2D>, String> {
+    let bytes = load_file(path)
+        .await
+        .map_err(|e| format!("could not load {}: {}", path, e))?;
+    // macroquad panics if the bytes are not a valid image
+    let image = std::panic::catch_unwind(|| Image::from_file_with_format(&bytes, None))
+        .map_err(|_| format!("could not decode {} as an image", path))?;
+    check_tileset(&image).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(split_tileset(&image))
+}
+
 // Note that Texture2D::from_rgba8 uses the macroquad context, so this can't be used directly in
 // the logic crate
 pub fn split_tileset(image: &Image) -> Vec<Texture2D> {
@@ -1,15 +1,22 @@
 use crate::common::cli::{CliArgs, UiBackend, GIT_VERSION};
+use crate::common::commands::read_snapshot;
 use crate::external::assets_macroquad;
 use crate::external::drawer_egui_macroquad::DrawerEguiMacroquad;
 use crate::external::drawer_macroquad::DrawerMacroquad;
 use juquad::input::input_macroquad::InputMacroquad;
 use juquad::texture_loader::TextureLoader;
+use logic::common::snapshot::Snapshot;
 use logic::scene::introduction_scene::{IntroductionScene, JuquadFunctions};
 use logic::scene::main_scene::MainScene;
+use logic::screen::control_server::ControlServer;
 use logic::screen::drawer_trait::DrawerTrait;
 use logic::screen::main_scene_input_source::MainSceneInputSource;
 use logic::screen::Screen;
+use logic::world::balance::refresh_balance;
+use logic::world::console::ConsoleCommand;
 use logic::world::map::MapType;
+use logic::world::objectives::{Campaign, Objectives};
+use logic::world::scenario::Scenario;
 use logic::world::World;
 use logic::SceneState;
 use macroquad::texture::Texture2D;
@@ -19,16 +26,72 @@ pub const TILESET_PATH: &'static str = "assets/image/tileset.png";
 pub async fn create_main_scene(args: &CliArgs, textures: Vec<Texture2D>) -> Box<SceneState> {
     println!("Running Bioengineer version {}", GIT_VERSION);
     let drawer = drawer_factory(args.ui, textures);
-    let input_source = MainSceneInputSource::new(Box::new(InputMacroquad));
-    let world = World::new_with_options(args.profile, args.fluids, MapType::Simplex);
-    Box::new(SceneState::Main(MainScene {
-        screen: Screen::new(
-            drawer,
-            input_source,
-            world.map.get_ship_position().unwrap_or_default(),
-        ),
-        world,
-    }))
+    let input_source = MainSceneInputSource::new_with_keybindings_file(
+        Box::new(InputMacroquad),
+        &args.keybindings,
+    );
+    let balance_path = args.balance_path().unwrap_or_else(|error| {
+        eprintln!("Error: {}", error);
+        std::process::exit(1);
+    });
+    // before creating the world, as the initial storage of the ship depends on it
+    refresh_balance(balance_path);
+    let mut world = create_world(args);
+    world.game_state.balance_path = Some(balance_path.to_string());
+    world.game_state.validate = args.validate;
+    let mut screen = Screen::new(
+        drawer,
+        input_source,
+        world.map.get_ship_position().unwrap_or_default(),
+    );
+    if let Some(path) = &args.script {
+        match ConsoleCommand::load_script(path) {
+            Ok(commands) => screen.queue_console_commands(commands),
+            Err(error) => eprintln!("Error loading the script, not running it: {}", error),
+        }
+    }
+    if let Some(port) = args.control_port {
+        match ControlServer::bind(port) {
+            Ok(control_server) => {
+                println!("Listening for control commands on localhost:{}", port);
+                screen.set_control_server(control_server);
+            }
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+    Box::new(SceneState::Main(MainScene { screen, world }))
+}
+
+fn create_world(args: &CliArgs) -> World {
+    if let Some(path) = &args.load {
+        match read_snapshot(path).and_then(|snapshot| World::load(&snapshot)) {
+            Ok(world) => return world,
+            Err(error) => eprintln!("Error loading the snapshot, starting a new game: {}", error),
+        }
+    }
+    let mut world = create_new_world(args);
+    if let Some(path) = &args.objectives {
+        match Campaign::load(path) {
+            Ok(campaign) => world.objectives = Objectives::new(campaign),
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+    world
+}
+
+fn create_new_world(args: &CliArgs) -> World {
+    if let Some(path) = &args.scenario {
+        match Scenario::load(path)
+            .and_then(|scenario| World::new_with_scenario(args.profile, args.fluids, &scenario))
+        {
+            Ok(world) => return world,
+            Err(error) => eprintln!("Error loading the scenario, using a random map: {}", error),
+        }
+    }
+    World::new_with_options(args.profile, args.fluids, MapType::Simplex)
 }
 
 pub async fn create_introduction_scene(args: &CliArgs) -> Box<SceneState> {
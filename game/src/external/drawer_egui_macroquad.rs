@@ -155,6 +155,18 @@ impl<'a> DrawerTrait for DrawerEguiMacroquad<'a> {
         self.inner.as_mut().unwrap().measure_text(text, font_size)
     }
 
+    fn draw_cached_layer(&mut self, layer: i32, key: u64) -> bool {
+        self.inner.as_mut().unwrap().draw_cached_layer(layer, key)
+    }
+
+    fn begin_cached_layer(&mut self, layer: i32, key: u64) {
+        self.inner.as_mut().unwrap().begin_cached_layer(layer, key)
+    }
+
+    fn end_cached_layer(&mut self) {
+        self.inner.as_mut().unwrap().end_cached_layer()
+    }
+
     fn ui_run(&mut self, f: &mut dyn FnMut(&mut dyn DrawerTrait) -> ()) {
         let gl = unsafe { get_internal_gl() };
         macroquad::input::utils::repeat_all_miniquad_input(self, self.input_processor_id);
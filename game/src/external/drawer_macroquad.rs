@@ -14,6 +14,7 @@ use macroquad::ui::{root_ui, widgets, Skin};
 use macroquad::window::{clear_background, screen_height, screen_width};
 use std::ops::Range;
 
+use crate::external::layer_cache_macroquad::LayerCacheMacroquad;
 use logic::screen::assets;
 use logic::screen::assets::{PIXELS_PER_TILE_HEIGHT, PIXELS_PER_TILE_WIDTH};
 use logic::screen::drawer_trait::{DrawerTrait, Interaction};
@@ -26,6 +27,7 @@ pub struct DrawerMacroquad {
     pub drawing: DrawingState,
     pub textures: Vec<Texture2D>,
     pub same_line: bool,
+    layer_cache: LayerCacheMacroquad,
 }
 
 impl DrawerTrait for DrawerMacroquad {
@@ -41,6 +43,7 @@ impl DrawerTrait for DrawerMacroquad {
             drawing: DrawingState::new(),
             textures,
             same_line: false,
+            layer_cache: LayerCacheMacroquad::new(),
         };
         // d._debug_draw_all_textures();
         d
@@ -48,6 +51,7 @@ impl DrawerTrait for DrawerMacroquad {
 
     fn set_textures(&mut self, textures: Vec<Texture2D>) {
         self.textures = textures;
+        self.layer_cache.clear();
     }
 
     fn take_textures(self: Box<Self>) -> Vec<Texture2D> {
@@ -137,6 +141,16 @@ impl DrawerTrait for DrawerMacroquad {
         self.ui_measure_text(text, font_size)
     }
 
+    fn draw_cached_layer(&mut self, layer: i32, key: u64) -> bool {
+        self.layer_cache.draw(layer, key)
+    }
+    fn begin_cached_layer(&mut self, layer: i32, key: u64) {
+        self.layer_cache.begin(layer, key)
+    }
+    fn end_cached_layer(&mut self) {
+        self.layer_cache.end()
+    }
+
     fn ui_run(&mut self, f: &mut dyn FnMut(&mut dyn DrawerTrait) -> ()) {
         f(self);
     }
@@ -0,0 +1,209 @@
+use macroquad::camera::{set_camera, set_default_camera, Camera2D};
+use macroquad::color::{Color, WHITE};
+use macroquad::material::{
+    gl_use_default_material, gl_use_material, load_material, Material, MaterialParams,
+};
+use macroquad::math::{vec2, Rect};
+use macroquad::miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams};
+use macroquad::texture::{draw_texture_ex, render_target, DrawTextureParams, RenderTarget};
+use macroquad::window::{clear_background, screen_height, screen_width};
+use std::collections::HashMap;
+
+/// Offscreen textures with the result of drawing some layers, so that they don't need to be
+/// drawn cell by cell in the next frames if nothing changed.
+///
+/// The textures store premultiplied alpha: a layer is drawn over a transparent background, and
+/// blending semi-transparent cells over it with the default blending would multiply the alpha
+/// twice.
+pub struct LayerCacheMacroquad {
+    layers: HashMap<i32, CachedLayer>,
+    recording: Option<i32>,
+    record_material: Option<Material>,
+    compose_material: Option<Material>,
+}
+
+struct CachedLayer {
+    key: u64,
+    target: RenderTarget,
+    width: u32,
+    height: u32,
+}
+
+impl LayerCacheMacroquad {
+    pub fn new() -> Self {
+        Self {
+            layers: HashMap::new(),
+            recording: None,
+            record_material: None,
+            compose_material: None,
+        }
+    }
+
+    pub fn draw(&mut self, layer: i32, key: u64) -> bool {
+        let (width, height) = screen_size();
+        if let Some(cached) = self.layers.get(&layer) {
+            if cached.key == key && cached.width == width && cached.height == height {
+                let target = cached.target.texture;
+                self.compose(target);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Forgets every cached layer, so that they are drawn again cell by cell.
+    pub fn clear(&mut self) {
+        for (_, cached) in self.layers.drain() {
+            cached.target.delete();
+        }
+    }
+
+    pub fn begin(&mut self, layer: i32, key: u64) {
+        let (width, height) = screen_size();
+        let reuse_target = match self.layers.get(&layer) {
+            Some(cached) => cached.width == width && cached.height == height,
+            None => false,
+        };
+        if !reuse_target {
+            if let Some(cached) = self.layers.remove(&layer) {
+                cached.target.delete();
+            }
+            self.layers.insert(
+                layer,
+                CachedLayer {
+                    key,
+                    target: render_target(width, height),
+                    width,
+                    height,
+                },
+            );
+        }
+        let cached = self.layers.get_mut(&layer).unwrap();
+        cached.key = key;
+        set_camera(&Camera2D {
+            render_target: Some(cached.target),
+            ..Camera2D::from_display_rect(Rect::new(0.0, 0.0, width as f32, height as f32))
+        });
+        clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
+        if let Some(material) = self.get_record_material() {
+            gl_use_material(material);
+        }
+        self.recording = Some(layer);
+    }
+
+    pub fn end(&mut self) {
+        gl_use_default_material();
+        set_default_camera();
+        if let Some(layer) = self.recording.take() {
+            let target = self.layers[&layer].target.texture;
+            self.compose(target);
+        }
+    }
+
+    fn compose(&mut self, texture: macroquad::texture::Texture2D) {
+        if let Some(material) = self.get_compose_material() {
+            gl_use_material(material);
+        }
+        draw_texture_ex(
+            texture,
+            0.0,
+            0.0,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                flip_y: true,
+                ..Default::default()
+            },
+        );
+        gl_use_default_material();
+    }
+
+    /// blends the colors as usual, but accumulates the alpha so that the texture ends up with
+    /// premultiplied alpha
+    fn get_record_material(&mut self) -> Option<Material> {
+        if self.record_material.is_none() {
+            self.record_material = load_blending_material(
+                BlendFactor::Value(BlendValue::SourceAlpha),
+                Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::One,
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+            );
+        }
+        self.record_material
+    }
+
+    /// draws a texture with premultiplied alpha
+    fn get_compose_material(&mut self) -> Option<Material> {
+        if self.compose_material.is_none() {
+            self.compose_material = load_blending_material(BlendFactor::One, None);
+        }
+        self.compose_material
+    }
+}
+
+fn screen_size() -> (u32, u32) {
+    (screen_width() as u32, screen_height() as u32)
+}
+
+fn load_blending_material(
+    source_color_factor: BlendFactor,
+    alpha_blend: Option<BlendState>,
+) -> Option<Material> {
+    let material = load_material(
+        VERTEX_SHADER,
+        FRAGMENT_SHADER,
+        MaterialParams {
+            pipeline_params: PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    source_color_factor,
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                alpha_blend,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+    match material {
+        Ok(material) => Some(material),
+        Err(error) => {
+            eprintln!(
+                "Could not load the layer cache material, using the default one: {:?}",
+                error
+            );
+            None
+        }
+    }
+}
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}
+"#;
@@ -1,5 +1,6 @@
 pub mod common {
     pub mod cli;
+    pub mod commands;
 }
 
 pub mod external {
@@ -7,4 +8,5 @@ pub mod external {
     pub mod backends;
     pub mod drawer_egui_macroquad;
     pub mod drawer_macroquad;
+    pub mod layer_cache_macroquad;
 }
@@ -1,10 +1,13 @@
 use clap::Parser;
 use macroquad::window::next_frame;
 use macroquad::window::Conf;
+use macroquad::Window;
 
 use bioengineer::common::cli::CliArgs;
+use bioengineer::common::commands::run_command;
 use bioengineer::external::backends::{create_introduction_scene, create_main_scene};
 use juquad::fps::sleep_until_next_frame;
+use logic::common::profiling::{write_chrome_trace, PROFILE_TRACE_PATH};
 use logic::world::map::chunk::chunks::cache::print_cache_stats;
 use logic::{frame, SceneState};
 use mq_basics::now;
@@ -13,9 +16,19 @@ const DEFAULT_WINDOW_WIDTH: i32 = 1200;
 const DEFAULT_WINDOW_HEIGHT: i32 = 675;
 const DEFAULT_WINDOW_TITLE: &str = "Bioengineer";
 
-#[macroquad::main(window_conf)]
-async fn main() {
+fn main() {
     let args = CliArgs::parse();
+    if let Some(command) = &args.command {
+        if let Err(error) = run_command(command) {
+            eprintln!("Error: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    Window::from_config(window_conf(), run_game(args));
+}
+
+async fn run_game(args: CliArgs) {
     let mut scene = create_introduction_scene(&args).await;
     let mut previous_time = now();
     while frame(&mut scene).should_continue() {
@@ -28,7 +41,13 @@ async fn main() {
         sleep_until_next_frame(&mut previous_time).await
     }
     if let SceneState::Main(main_scene) = scene.as_ref() {
-        print_cache_stats(main_scene.world.game_state.profile);
+        let profile = main_scene.world.game_state.profile;
+        print_cache_stats(profile);
+        if profile {
+            if let Err(error) = write_chrome_trace(PROFILE_TRACE_PATH) {
+                eprintln!("{}", error);
+            }
+        }
     }
 }
 
@@ -0,0 +1,375 @@
+//! A small JSON value with a parser and a compact writer, enough for the messages of the control
+//! server (see `crate::screen::control_server`). Objects keep the order of their keys.
+
+use std::fmt::{Display, Formatter, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            index: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.index < parser.chars.len() {
+            return Err(parser.error("unexpected text after the value"));
+        }
+        Ok(value)
+    }
+
+    pub fn object(entries: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string(text: impl Into<String>) -> Json {
+        Json::String(text.into())
+    }
+
+    /// the value of a key of an object, or None if it's missing or this is not an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// only for numbers without decimals
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_f64()
+            .filter(|number| number.fract() == 0.0)
+            .map(|number| number as i64)
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Json::Number(value)
+    }
+}
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Json::Number(value as f64)
+    }
+}
+impl From<i32> for Json {
+    fn from(value: i32) -> Self {
+        Json::Number(value as f64)
+    }
+}
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map(|value| value.into()).unwrap_or(Json::Null)
+    }
+}
+
+/// Writes the value in a single line, so it can be used in line-delimited JSON.
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) => {
+                if number.is_finite() {
+                    write!(f, "{}", number)
+                } else {
+                    write!(f, "null")
+                }
+            }
+            Json::String(text) => write_string(f, text),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(entries) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, text: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("{} at character {}", message, self.index)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.index < self.chars.len() && self.chars[self.index].is_whitespace() {
+            self.index += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).cloned()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() == Some(expected) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Json::String(self.parse_string()?)),
+            Some('t') => self.parse_literal("true", Json::Bool(true)),
+            Some('f') => self.parse_literal("false", Json::Bool(false)),
+            Some('n') => self.parse_literal("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+        for expected in literal.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.index;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || "+-.eE".contains(c) {
+                self.index += 1;
+            } else {
+                break;
+            }
+        }
+        let text = self.chars[start..self.index].iter().collect::<String>();
+        text.parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid number '{}' at character {}", text, start))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.index += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.index += 1;
+                    match escaped {
+                        '"' | '\\' | '/' => text.push(escaped),
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'u' => {
+                            let end = (self.index + 4).min(self.chars.len());
+                            let hex = self.chars[self.index..end].iter().collect::<String>();
+                            let code = u32::from_str_radix(&hex, 16)
+                                .map_err(|_| self.error("invalid unicode escape"))?;
+                            self.index = end;
+                            text.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.index += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some(']') => {
+                    self.index += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.index += 1;
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some('}') => {
+                    self.index += 1;
+                    return Ok(Json::Object(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let text =
+            r#"{"cmd":"transform","cells":[[1,-2,3.5]],"ok":true,"none":null,"text":"a\"b\\c\n"}"#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(
+            json.get("cmd").and_then(|cmd| cmd.as_str()),
+            Some("transform")
+        );
+        let cells = json
+            .get("cells")
+            .and_then(|cells| cells.as_array())
+            .unwrap();
+        assert_eq!(cells[0].as_array().unwrap()[1].as_i64(), Some(-2));
+        assert_eq!(cells[0].as_array().unwrap()[2].as_i64(), None);
+        assert_eq!(json.to_string(), text);
+    }
+
+    #[test]
+    fn test_whitespace_and_escapes() {
+        let json = Json::parse(" { \"a\" : [ 1 , 2e3 ] , \"b\" : \"\\u0041\" } ").unwrap();
+        assert_eq!(
+            json,
+            Json::object(vec![
+                (
+                    "a",
+                    Json::Array(vec![Json::Number(1.0), Json::Number(2000.0)])
+                ),
+                ("b", Json::string("A")),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Json::parse("{\"a\" 1}"),
+            Err("expected ':' at character 5".to_string())
+        );
+        assert_eq!(
+            Json::parse("[1, 2"),
+            Err("expected ',' or ']' at character 5".to_string())
+        );
+        assert_eq!(
+            Json::parse("true false"),
+            Err("unexpected text after the value at character 5".to_string())
+        );
+        assert!(Json::parse("\"open").is_err());
+    }
+}
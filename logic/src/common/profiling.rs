@@ -1,4 +1,20 @@
+//! Scope timings and counters, gathered in a global registry.
+//!
+//! Enabled `ScopedProfiler`s record how long their scope took, and the timings can be summarized
+//! with `scope_stats` or exported with `write_chrome_trace` to be opened in `chrome://tracing` or
+//! in Perfetto. The counters are always recorded, as they are cheap enough for hot loops.
+
 use mq_basics::now;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Mutex;
+
+/// Older durations are dropped to keep the memory bounded.
+pub const MAX_SAMPLES_PER_SCOPE: usize = 1000;
+/// Older trace events are dropped to keep the memory bounded.
+pub const MAX_TRACE_EVENTS: usize = 100_000;
+pub const PROFILE_TRACE_PATH: &str = "profile_trace.json";
+const UNNAMED_SCOPE: &str = "unnamed scope";
 
 /// needs a function `now` that returns a timestamp in seconds.
 /// `now` should be extracted as a generic parameter if taken out of this project.
@@ -6,6 +22,7 @@ pub struct ScopedProfiler {
     pub enabled: bool,
     pub start_ts: f64,
     pub name: Option<String>,
+    depth: usize,
 }
 
 impl ScopedProfiler {
@@ -17,11 +34,16 @@ impl ScopedProfiler {
         Self::new_with_maybe_name(enabled, Option::Some(String::from(name)))
     }
     fn new_with_maybe_name(enabled: bool, name: Option<String>) -> Self {
-        let start_ts = if enabled { now() } else { 0.0 };
+        let (start_ts, depth) = if enabled {
+            (now(), with_metrics(|metrics| metrics.enter_scope()))
+        } else {
+            (0.0, 0)
+        };
         Self {
             enabled,
             start_ts,
             name,
+            depth,
         }
     }
 }
@@ -29,14 +51,318 @@ impl ScopedProfiler {
 impl Drop for ScopedProfiler {
     fn drop(&mut self) {
         if self.enabled {
-            let diff = now() - self.start_ts;
-            let formatted_name = match &self.name {
-                None => String::new(),
-                Some(name) => {
-                    format!(" on: {}", name)
-                }
+            let record = ScopeRecord {
+                name: self.name.take().unwrap_or(UNNAMED_SCOPE.to_string()),
+                start_ts: self.start_ts,
+                duration: now() - self.start_ts,
+                depth: self.depth,
             };
-            println!("Spent: {:.3} ms{}", diff * 1000.0, formatted_name);
+            with_metrics(|metrics| metrics.exit_scope(record));
+        }
+    }
+}
+
+/// A finished scope. Timestamps and durations are in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeRecord {
+    pub name: String,
+    pub start_ts: f64,
+    pub duration: f64,
+    /// how many scopes were open when this one started
+    pub depth: usize,
+}
+
+/// Aggregated durations of all the scopes with the same name, in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeStats {
+    pub name: String,
+    pub depth: usize,
+    pub count: usize,
+    pub min: f64,
+    pub mean: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl ScopeStats {
+    /// returns None if there are no durations
+    pub fn from_durations<'a>(
+        name: &str,
+        depth: usize,
+        durations: impl Iterator<Item = &'a f64>,
+    ) -> Option<Self> {
+        let mut sorted = durations.cloned().collect::<Vec<_>>();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let count = sorted.len();
+        let p99_index = ((count as f64 * 0.99).ceil() as usize).max(1) - 1;
+        Some(Self {
+            name: name.to_string(),
+            depth,
+            count,
+            min: sorted[0],
+            mean: sorted.iter().sum::<f64>() / count as f64,
+            p99: sorted[p99_index],
+            max: sorted[count - 1],
+        })
+    }
+}
+
+/// Declare them as statics: `static MISSES: Counter = Counter::new("misses");`. They are added
+/// to the registry the first time they are incremented.
+pub struct Counter {
+    pub name: &'static str,
+    value: AtomicI64,
+    registered: AtomicBool,
+}
+
+impl Counter {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: AtomicI64::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    pub fn increment(&'static self) {
+        if !self.registered.load(Ordering::Relaxed)
+            && !self.registered.swap(true, Ordering::Relaxed)
+        {
+            COUNTERS.lock().unwrap().push(self);
+        }
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+static COUNTERS: Mutex<Vec<&'static Counter>> = Mutex::new(Vec::new());
+
+/// the values of the counters incremented so far, sorted by name
+pub fn counters() -> Vec<(&'static str, i64)> {
+    let mut counters = COUNTERS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|counter| (counter.name, counter.get()))
+        .collect::<Vec<_>>();
+    counters.sort();
+    counters
+}
+
+struct ScopeSamples {
+    /// the smallest depth seen, to indent the summary
+    depth: usize,
+    durations: VecDeque<f64>,
+}
+
+pub struct Metrics {
+    open_scopes: usize,
+    samples: BTreeMap<String, ScopeSamples>,
+    trace: VecDeque<ScopeRecord>,
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            open_scopes: 0,
+            samples: BTreeMap::new(),
+            trace: VecDeque::new(),
+        }
+    }
+
+    /// returns the depth of the new scope
+    pub fn enter_scope(&mut self) -> usize {
+        self.open_scopes += 1;
+        self.open_scopes - 1
+    }
+
+    pub fn exit_scope(&mut self, record: ScopeRecord) {
+        self.open_scopes = self.open_scopes.saturating_sub(1);
+        let samples = self
+            .samples
+            .entry(record.name.clone())
+            .or_insert(ScopeSamples {
+                depth: record.depth,
+                durations: VecDeque::new(),
+            });
+        samples.depth = samples.depth.min(record.depth);
+        if samples.durations.len() >= MAX_SAMPLES_PER_SCOPE {
+            samples.durations.pop_front();
+        }
+        samples.durations.push_back(record.duration);
+        if self.trace.len() >= MAX_TRACE_EVENTS {
+            self.trace.pop_front();
+        }
+        self.trace.push_back(record);
+    }
+
+    /// sorted by name
+    pub fn scope_stats(&self) -> Vec<ScopeStats> {
+        self.samples
+            .iter()
+            .filter_map(|(name, samples)| {
+                ScopeStats::from_durations(name, samples.depth, samples.durations.iter())
+            })
+            .collect()
+    }
+
+    /// The Trace Event Format of chrome://tracing, with a complete event ("X") per scope and a
+    /// counter event ("C") with the counters at the end. Timestamps are in microseconds.
+    pub fn to_chrome_trace(&self, counters: &[(&str, i64)]) -> String {
+        let mut events = self
+            .trace
+            .iter()
+            .map(|record| {
+                format!(
+                    r#"{{"name":"{}","cat":"scope","ph":"X","ts":{:.3},"dur":{:.3},"pid":0,"tid":0,"args":{{"depth":{}}}}}"#,
+                    escape_json(&record.name),
+                    record.start_ts * 1_000_000.0,
+                    record.duration * 1_000_000.0,
+                    record.depth
+                )
+            })
+            .collect::<Vec<_>>();
+        if !counters.is_empty() {
+            let last_ts = self
+                .trace
+                .back()
+                .map(|record| record.start_ts + record.duration)
+                .unwrap_or(0.0);
+            let args = counters
+                .iter()
+                .map(|(name, value)| format!(r#""{}":{}"#, escape_json(name), value))
+                .collect::<Vec<_>>()
+                .join(",");
+            events.push(format!(
+                r#"{{"name":"counters","ph":"C","ts":{:.3},"pid":0,"args":{{{}}}}}"#,
+                last_ts * 1_000_000.0,
+                args
+            ));
+        }
+        format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.trace.clear();
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A static instead of a thread_local because thread locals with destructors keep the library
+/// loaded until the thread exits, which would break the hot reload.
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+pub fn with_metrics<R, F: FnOnce(&mut Metrics) -> R>(f: F) -> R {
+    f(&mut METRICS.lock().unwrap())
+}
+
+pub fn scope_stats() -> Vec<ScopeStats> {
+    with_metrics(|metrics| metrics.scope_stats())
+}
+
+pub fn write_chrome_trace(path: &str) -> Result<(), String> {
+    let counters = counters();
+    let trace = with_metrics(|metrics| metrics.to_chrome_trace(&counters));
+    std::fs::write(path, trace)
+        .map_err(|e| format!("could not write profiling trace to {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, start_ts: f64, duration: f64, depth: usize) -> ScopeRecord {
+        ScopeRecord {
+            name: name.to_string(),
+            start_ts,
+            duration,
+            depth,
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let durations = (1..=100).map(|i| i as f64).collect::<Vec<_>>();
+        let stats = ScopeStats::from_durations("frame", 0, durations.iter().rev()).unwrap();
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.mean, 50.5);
+        assert_eq!(stats.p99, 99.0);
+        assert_eq!(stats.max, 100.0);
+        assert_eq!(ScopeStats::from_durations("frame", 0, [].iter()), None);
+    }
+
+    #[test]
+    fn test_nested_scopes() {
+        let mut metrics = Metrics::new();
+        assert_eq!(metrics.enter_scope(), 0);
+        assert_eq!(metrics.enter_scope(), 1);
+        metrics.exit_scope(record("inner", 0.5, 0.25, 1));
+        metrics.exit_scope(record("outer", 0.0, 1.0, 0));
+        let stats = metrics.scope_stats();
+        assert_eq!(
+            stats
+                .iter()
+                .map(|s| (s.name.as_str(), s.depth))
+                .collect::<Vec<_>>(),
+            vec![("inner", 1), ("outer", 0)]
+        );
+        assert_eq!(metrics.enter_scope(), 0);
+    }
+
+    #[test]
+    fn test_chrome_trace() {
+        let mut metrics = Metrics::new();
+        metrics.exit_scope(record("stage \"1\"", 1.0, 0.5, 0));
+        assert_eq!(
+            metrics.to_chrome_trace(&[("cache_misses", 2)]),
+            r#"{"traceEvents":[
+{"name":"stage \"1\"","cat":"scope","ph":"X","ts":1000000.000,"dur":500000.000,"pid":0,"tid":0,"args":{"depth":0}},
+{"name":"counters","ph":"C","ts":1500000.000,"pid":0,"args":{"cache_misses":2}}
+]}
+"#
+        );
+    }
+
+    #[test]
+    fn test_counters_register_when_used() {
+        static UNUSED: Counter = Counter::new("test unused");
+        static USED: Counter = Counter::new("test used");
+        USED.increment();
+        USED.increment();
+        let counters = counters();
+        assert!(counters.contains(&("test used", 2)));
+        assert!(!counters.iter().any(|(name, _)| *name == UNUSED.name));
+    }
+
+    #[test]
+    fn test_samples_are_bounded() {
+        let mut metrics = Metrics::new();
+        for i in 0..MAX_SAMPLES_PER_SCOPE + 10 {
+            metrics.exit_scope(record("frame", i as f64, i as f64, 0));
         }
+        let stats = metrics.scope_stats();
+        assert_eq!(stats[0].count, MAX_SAMPLES_PER_SCOPE);
+        assert_eq!(stats[0].min, 10.0);
     }
 }
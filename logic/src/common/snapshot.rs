@@ -0,0 +1,226 @@
+//! A versioned text format to carry the game state across builds of this library, so that the
+//! hot reload keeps working after changing the fields of a struct. See
+//! `game/src/bin/hot_reload_bioengineer.rs`.
+//!
+//! The first line is `bioengineer_snapshot <version>`, and every other line is a key followed by
+//! values separated by spaces, like `world.age_in_minutes 42` or
+//! `map.cell 3 -1 0 Air 0 0 0 0 false`. Keys can repeat, and the order of the lines with different
+//! keys doesn't matter.
+//!
+//! Adding a field only needs a default when loading it (see `get_or`), and unknown keys are
+//! ignored. Changing the meaning of an existing key needs a bump of `SNAPSHOT_VERSION` and a
+//! migration in `SnapshotReader::parse`.
+
+use crate::world::map::{CellIndex, TileType};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+const HEADER: &str = "bioengineer_snapshot";
+
+pub trait Snapshot: Sized {
+    fn save(&self, snapshot: &mut SnapshotWriter);
+    fn load(snapshot: &SnapshotReader) -> Result<Self, String>;
+}
+
+pub struct SnapshotWriter {
+    text: String,
+}
+
+impl SnapshotWriter {
+    pub fn new() -> Self {
+        Self {
+            text: format!("{} {}\n", HEADER, SNAPSHOT_VERSION),
+        }
+    }
+
+    /// `values` can be several values already separated by spaces
+    pub fn line<T: Display>(&mut self, key: &str, values: T) {
+        self.text += &format!("{} {}\n", key, values);
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+pub struct SnapshotReader {
+    version: u32,
+    /// values of each line, grouped by key in the order they appear
+    lines: HashMap<String, Vec<Vec<String>>>,
+}
+
+impl SnapshotReader {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut text_lines = text.lines();
+        let header = text_lines.next().unwrap_or_default();
+        let version = match header.split_once(' ') {
+            Some((HEADER, version)) => version
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("line 1: invalid snapshot version '{}'", version))?,
+            _ => return Err(format!("line 1: expected '{} <version>'", HEADER)),
+        };
+        if version > SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot version {} is newer than the supported version {}",
+                version, SNAPSHOT_VERSION
+            ));
+        }
+        if version < SNAPSHOT_VERSION {
+            // no migrations yet, as this is the first version
+            return Err(format!(
+                "snapshot version {} is too old, there is no migration to version {}",
+                version, SNAPSHOT_VERSION
+            ));
+        }
+        let mut lines = HashMap::<String, Vec<Vec<String>>>::new();
+        for line in text_lines {
+            let mut words = line.split_whitespace();
+            if let Some(key) = words.next() {
+                let values = words.map(|word| word.to_string()).collect();
+                lines.entry(key.to_string()).or_default().push(values);
+            }
+        }
+        Ok(Self { version, lines })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// the values of every line with this key
+    pub fn get_all(&self, key: &str) -> &[Vec<String>] {
+        self.lines
+            .get(key)
+            .map(|lines| lines.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// the values of a key that appears once
+    pub fn get_single(&self, key: &str) -> Result<&[String], String> {
+        match self.get_all(key) {
+            [values] => Ok(values),
+            [] => Err(format!("missing '{}'", key)),
+            _ => Err(format!("'{}' appears more than once", key)),
+        }
+    }
+
+    /// the value of a key that appears once and has a single value
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        parse_value(self.get_single(key)?, 0, key)
+    }
+
+    pub fn get_cell_index(&self, key: &str) -> Result<CellIndex, String> {
+        parse_cell_index(self.get_single(key)?, 0, key)
+    }
+
+    /// like `get`, but for fields that older snapshots don't have
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        if self.get_all(key).is_empty() {
+            Ok(default)
+        } else {
+            self.get(key)
+        }
+    }
+}
+
+pub fn parse_value<T: FromStr>(values: &[String], index: usize, key: &str) -> Result<T, String> {
+    let value = values
+        .get(index)
+        .ok_or_else(|| format!("'{}' needs at least {} values", key, index + 1))?;
+    value
+        .parse::<T>()
+        .map_err(|_| format!("'{}' has an invalid value '{}'", key, value))
+}
+
+pub fn cell_index_str(cell_index: CellIndex) -> String {
+    format!("{} {} {}", cell_index.x, cell_index.y, cell_index.z)
+}
+
+/// reads the 3 values starting at `index`
+pub fn parse_cell_index(values: &[String], index: usize, key: &str) -> Result<CellIndex, String> {
+    Ok(CellIndex::new(
+        parse_value(values, index, key)?,
+        parse_value(values, index + 1, key)?,
+        parse_value(values, index + 2, key)?,
+    ))
+}
+
+pub fn parse_tile(values: &[String], index: usize, key: &str) -> Result<TileType, String> {
+    let name = parse_value::<String>(values, index, key)?;
+    TileType::from_name(&name).ok_or_else(|| format!("'{}' has an unknown tile '{}'", key, name))
+}
+
+/// For enums with a `next()` that cycles through all the values, saved with their Debug name.
+pub fn parse_cycling<T: Copy + PartialEq + std::fmt::Debug>(
+    name: &str,
+    first: T,
+    next: fn(&T) -> T,
+    key: &str,
+) -> Result<T, String> {
+    let mut candidate = first;
+    loop {
+        if format!("{:?}", candidate) == name {
+            return Ok(candidate);
+        }
+        candidate = next(&candidate);
+        if candidate == first {
+            return Err(format!("'{}' has an unknown value '{}'", key, name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_values() {
+        let mut writer = SnapshotWriter::new();
+        writer.line("age", 42);
+        writer.line("zoom", 0.1 + 0.2);
+        writer.line("robot", cell_index_str(CellIndex::new(1, -2, 3)));
+        writer.line("robot", cell_index_str(CellIndex::new(4, 5, 6)));
+        writer.line("tile", "WallRock");
+        let reader = SnapshotReader::parse(&writer.finish()).unwrap();
+
+        assert_eq!(reader.version(), SNAPSHOT_VERSION);
+        assert_eq!(reader.get::<i64>("age"), Ok(42));
+        assert_eq!(reader.get::<f64>("zoom"), Ok(0.1 + 0.2));
+        assert_eq!(reader.get_or::<i64>("missing", 7), Ok(7));
+        assert_eq!(
+            reader.get::<i64>("missing"),
+            Err("missing 'missing'".to_string())
+        );
+        let robots = reader
+            .get_all("robot")
+            .iter()
+            .map(|values| parse_cell_index(values, 0, "robot"))
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(
+            robots,
+            Ok(vec![CellIndex::new(1, -2, 3), CellIndex::new(4, 5, 6)])
+        );
+        assert_eq!(
+            parse_tile(&reader.get_all("tile")[0], 0, "tile"),
+            Ok(TileType::WallRock)
+        );
+    }
+
+    #[test]
+    fn test_versions() {
+        assert!(SnapshotReader::parse("some other file\n").is_err());
+        let newer = format!("{} {}\n", HEADER, SNAPSHOT_VERSION + 1);
+        assert!(SnapshotReader::parse(&newer).unwrap_err().contains("newer"));
+    }
+
+    #[test]
+    fn test_parse_cycling() {
+        use crate::screen::overlay::Overlay;
+        let parsed = parse_cycling("Power", Overlay::None, Overlay::next, "overlay");
+        assert_eq!(parsed, Ok(Overlay::Power));
+        assert!(parse_cycling("Rainbow", Overlay::None, Overlay::next, "overlay").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Moves the main scene from one build of this library to the next one, for
+//! `game/src/bin/hot_reload_bioengineer.rs`.
+//!
+//! The old build saves the World and the DrawingState in a snapshot (see
+//! [`crate::common::snapshot`]) and the new build loads them, so the structs can change between
+//! builds. The drawer and the input source are created by the executable, so they are passed
+//! as they are.
+
+use crate::common::snapshot::{Snapshot, SnapshotReader, SnapshotWriter};
+use crate::scene::main_scene::MainScene;
+use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::drawing_state::DrawingState;
+use crate::screen::keybindings::Keybindings;
+use crate::screen::main_scene_input_source::MainSceneInputSource;
+use crate::screen::Screen;
+use crate::world::game_state::{DEFAULT_ADVANCING_FLUIDS, DEFAULT_PROFILE_ENABLED};
+use crate::world::map::MapType;
+use crate::world::World;
+use crate::SceneState;
+use juquad::input::input_trait::InputTrait;
+
+/// Both builds use this struct, so don't change it, or the reload will crash like before the
+/// snapshots existed.
+#[repr(C)]
+pub struct HotReloadState {
+    pub snapshot: String,
+    /// in the format of the keybindings file
+    pub keybindings: String,
+    pub keybindings_path: Option<String>,
+    pub drawer: Box<dyn DrawerTrait>,
+    pub input: Box<dyn InputTrait>,
+}
+
+/// Don't change this struct either.
+#[repr(C)]
+pub struct RestoredScene {
+    pub scene: Box<SceneState>,
+    /// if the snapshot could not be loaded, a new game was started and this says why
+    pub error: Option<String>,
+}
+
+/// Only the main scene can be saved. Other scenes are given back as they are.
+pub fn save_state(scene: Box<SceneState>) -> Result<HotReloadState, Box<SceneState>> {
+    match *scene {
+        SceneState::Main(main_scene) => {
+            let (drawer, input_source, drawing_state) = main_scene.screen.into_parts();
+            let (input, keybindings, keybindings_path) = input_source.into_parts();
+            Ok(HotReloadState {
+                snapshot: save_snapshot(&main_scene.world, &drawing_state),
+                keybindings: keybindings.to_config_string(),
+                keybindings_path,
+                drawer,
+                input,
+            })
+        }
+        scene => Err(Box::new(scene)),
+    }
+}
+
+pub fn restore_state(state: HotReloadState) -> RestoredScene {
+    let mut errors = Vec::new();
+    let keybindings = Keybindings::parse(&state.keybindings).unwrap_or_else(|error| {
+        errors.push(format!("keybindings: {}", error));
+        Keybindings::new()
+    });
+    let input_source = MainSceneInputSource::new_with_keybindings(
+        state.input,
+        keybindings,
+        state.keybindings_path,
+    );
+    let (world, screen) = match load_snapshot(&state.snapshot) {
+        Ok((world, drawing_state)) => (
+            world,
+            Screen::new_with_drawing_state(state.drawer, input_source, drawing_state),
+        ),
+        Err(error) => {
+            errors.push(format!("snapshot: {}", error));
+            let world = World::new_with_options(
+                DEFAULT_PROFILE_ENABLED,
+                DEFAULT_ADVANCING_FLUIDS,
+                MapType::Simplex,
+            );
+            let center = world.map.get_ship_position().unwrap_or_default();
+            (world, Screen::new(state.drawer, input_source, center))
+        }
+    };
+    RestoredScene {
+        scene: Box::new(SceneState::Main(MainScene { world, screen })),
+        error: if errors.is_empty() {
+            None
+        } else {
+            Some(errors.join(". "))
+        },
+    }
+}
+
+pub fn save_snapshot(world: &World, drawing_state: &DrawingState) -> String {
+    let mut snapshot = SnapshotWriter::new();
+    world.save(&mut snapshot);
+    drawing_state.save(&mut snapshot);
+    snapshot.finish()
+}
+
+pub fn load_snapshot(text: &str) -> Result<(World, DrawingState), String> {
+    let snapshot = SnapshotReader::parse(text)?;
+    Ok((World::load(&snapshot)?, DrawingState::load(&snapshot)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::drawing_state::{SelectionMode, ViewMode};
+    use crate::screen::overlay::Overlay;
+
+    #[test]
+    fn test_drawing_state_roundtrip() {
+        let world = World::new();
+        let mut drawing_state = DrawingState::new();
+        drawing_state.re_center(world.map.get_ship_position().unwrap());
+        drawing_state.zoom = 1.75;
+        drawing_state.view_mode = ViewMode::CrossSectionZ;
+        drawing_state.overlay = Overlay::TreeHealth;
+        drawing_state.selection_mode = SelectionMode::FloodFill;
+
+        let (_, loaded) = load_snapshot(&save_snapshot(&world, &drawing_state)).unwrap();
+        assert_eq!(loaded.min_cell, drawing_state.min_cell);
+        assert_eq!(loaded.max_cell, drawing_state.max_cell);
+        assert_eq!(loaded.subcell_diff, drawing_state.subcell_diff);
+        assert_eq!(loaded.zoom, 1.75);
+        assert_eq!(loaded.view_mode, ViewMode::CrossSectionZ);
+        assert_eq!(loaded.overlay, Overlay::TreeHealth);
+        assert_eq!(loaded.selection_mode, SelectionMode::FloodFill);
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let error = load_snapshot("bioengineer_snapshot 1\nmap.type Donut\n").err();
+        assert!(error.is_some());
+    }
+}
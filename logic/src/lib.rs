@@ -7,15 +7,19 @@
 //! external effects, so it's possible to provide mocked implementations of them for integration
 //! tests. See [crate::world::gameplay_tests].
 
+use crate::hot_reload::{restore_state, save_state, HotReloadState, RestoredScene};
 use crate::scene::introduction_scene::IntroductionScene;
 use crate::scene::main_scene::MainScene;
 use crate::scene::{GameLoopState, Scene};
 use mq_basics::Texture2D;
 
 pub mod common {
+    pub mod json;
     pub mod profiling;
+    pub mod snapshot;
     pub mod trunc;
 }
+pub mod hot_reload;
 pub mod scene;
 pub mod screen;
 pub mod world;
@@ -36,6 +40,21 @@ pub extern "C" fn hot_reload_draw_frame(scene_wrapper: &mut Box<SceneState>) ->
     frame(scene_wrapper)
 }
 
+/// Called with the library that is about to be unloaded. Returns the scene back if it can not be
+/// saved. See [crate::hot_reload].
+#[no_mangle]
+pub extern "C" fn hot_reload_save_state(
+    scene_wrapper: Box<SceneState>,
+) -> Box<Result<HotReloadState, Box<SceneState>>> {
+    Box::new(save_state(scene_wrapper))
+}
+
+/// Called with the library that was just loaded, with the state saved by the previous one.
+#[no_mangle]
+pub extern "C" fn hot_reload_restore_state(state: Box<HotReloadState>) -> Box<RestoredScene> {
+    Box::new(restore_state(*state))
+}
+
 impl Scene for SceneState {
     fn frame(&mut self) -> GameLoopState {
         match self {
@@ -1,7 +1,10 @@
 use crate::scene::GameLoopState;
+use crate::screen::control_server::ControlServer;
 use crate::screen::main_scene_input_source::MainSceneInputSource;
+use crate::world::console::ConsoleCommand;
 use crate::world::map::CellIndex;
 use crate::world::World;
+use draw_map::EmptyLayers;
 use drawer_trait::DrawerTrait;
 use drawing_state::DrawingState;
 use gui::gui_actions::GuiActions;
@@ -9,14 +12,18 @@ use gui::Gui;
 use mq_basics::Color;
 
 pub mod assets;
+pub mod control_server;
 pub mod coords;
+pub mod draw_cross_section;
 pub mod draw_map;
 pub mod drawer_trait;
 pub mod drawing_state;
 pub mod gui;
 pub mod hud;
+pub mod keybindings;
 pub mod main_scene_input;
 pub mod main_scene_input_source;
+pub mod overlay;
 
 pub const GREY: Color = Color::new(0.5, 0.5, 0.5, 1.0);
 
@@ -25,30 +32,70 @@ pub struct Screen {
     input_source: MainSceneInputSource,
     gui: Gui,
     drawing_state: DrawingState,
+    control_server: Option<ControlServer>,
+    empty_layers: EmptyLayers,
 }
 
 impl Screen {
     pub fn new(
-        mut drawer: Box<dyn DrawerTrait>,
+        drawer: Box<dyn DrawerTrait>,
         input_source: MainSceneInputSource,
         center_position: CellIndex,
     ) -> Self {
-        let gui = Gui::new(drawer.as_mut());
         let drawing_state = DrawingState::new_centered(center_position);
+        Self::new_with_drawing_state(drawer, input_source, drawing_state)
+    }
+
+    pub fn new_with_drawing_state(
+        mut drawer: Box<dyn DrawerTrait>,
+        input_source: MainSceneInputSource,
+        drawing_state: DrawingState,
+    ) -> Self {
+        let gui = Gui::new(drawer.as_mut());
         Screen {
             drawer,
             input_source,
             gui,
             drawing_state,
+            control_server: None,
+            empty_layers: EmptyLayers::default(),
         }
     }
 
+    pub fn drawing_state(&self) -> &DrawingState {
+        &self.drawing_state
+    }
+
+    pub fn into_parts(self) -> (Box<dyn DrawerTrait>, MainSceneInputSource, DrawingState) {
+        (self.drawer, self.input_source, self.drawing_state)
+    }
+
+    /// Lets external scripts send actions, see [`control_server`].
+    pub fn set_control_server(&mut self, control_server: ControlServer) {
+        self.control_server = Some(control_server);
+    }
+
+    /// They will run in the next frame, as if they were typed in the console.
+    pub fn queue_console_commands(&mut self, commands: Vec<ConsoleCommand>) {
+        self.drawing_state.console_queue.extend(commands);
+    }
+
     pub fn get_gui_actions(&mut self, world: &World) -> GuiActions {
         let input = self.input_source.get_input();
-        let gui_actions =
-            self.gui
-                .process_input(input, self.drawer.as_mut(), world, &mut self.drawing_state);
-        self.drawing_state.apply_input(&gui_actions);
+        let mut gui_actions = self.gui.process_input(
+            input,
+            self.drawer.as_mut(),
+            world,
+            &mut self.drawing_state,
+            self.input_source.keybindings(),
+        );
+        if let Some(control_server) = &mut self.control_server {
+            control_server.poll(world, &mut gui_actions);
+        }
+        if let Some((action, key)) = gui_actions.rebind {
+            self.input_source.rebind(action, key);
+        }
+        self.drawing_state.apply_input(&gui_actions, world);
         gui_actions
     }
 
@@ -57,16 +104,34 @@ impl Screen {
             self.drawing_state =
                 DrawingState::new_centered(world.map.get_ship_position().unwrap_or_default())
         }
-        draw(self.drawer.as_mut(), world, &self.drawing_state);
+        draw(
+            self.drawer.as_mut(),
+            world,
+            &self.drawing_state,
+            &mut self.empty_layers,
+        );
     }
 }
 
-pub fn draw(drawer: &mut dyn DrawerTrait, world: &World, drawing: &DrawingState) {
+pub fn draw(
+    drawer: &mut dyn DrawerTrait,
+    world: &World,
+    drawing: &DrawingState,
+    empty_layers: &mut EmptyLayers,
+) {
     drawer.clear_background(GREY);
-    draw_map::draw_map(drawer, world, drawing);
+    if drawing.view_mode.is_cross_section() {
+        draw_cross_section::draw_cross_section(drawer, world, drawing);
+    } else {
+        draw_map::draw_map(drawer, world, drawing, empty_layers);
+    }
     drawer.ui_draw();
     hud::draw_fps(drawer, &world.game_state);
     hud::draw_level(drawer, drawing.min_cell.y, drawing.max_cell.y);
+    hud::draw_overlay_legend(drawer, world, drawing.overlay);
+    if let Some(plane) = drawing.cross_section_plane() {
+        hud::draw_cross_section_plane(drawer, drawing.view_mode, plane);
+    }
     hud::draw_networks(drawer, world);
     hud::draw_age(drawer, world);
     hud::draw_life(drawer, world);
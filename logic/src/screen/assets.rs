@@ -1,3 +1,4 @@
+use crate::world::map::cell::ExtraTextures;
 use mq_basics::Image;
 
 pub const PIXELS_PER_TILE_WIDTH: u16 = 64;
@@ -6,6 +7,39 @@ pub const PIXELS_PER_TILE_HEIGHT: u16 = 64;
 /// Components refers to each color channel in a pixel, usually r,g,b,a
 pub const COMPONENTS_PER_PIXEL: usize = 4;
 
+/// The textures after the tileset ones are computed from them, starting with the zoomed robot.
+pub const TILES_IN_TILESET: usize = ExtraTextures::ZoomedRobot as usize;
+
+/// Checks that `extract_images` and the extra textures will find the tiles they expect, so that
+/// a malformed tileset can be reported instead of crashing.
+pub fn check_tileset(image: &Image) -> Result<(), String> {
+    let expected_bytes = image.width as usize * image.height as usize * COMPONENTS_PER_PIXEL;
+    if image.bytes.len() != expected_bytes {
+        return Err(format!(
+            "the tileset has {} bytes but a {}x{} image needs {}",
+            image.bytes.len(),
+            image.width,
+            image.height,
+            expected_bytes
+        ));
+    }
+    if image.width % PIXELS_PER_TILE_WIDTH != 0 || image.height % PIXELS_PER_TILE_HEIGHT != 0 {
+        return Err(format!(
+            "the tileset is {}x{} pixels, which is not a multiple of the tile size {}x{}",
+            image.width, image.height, PIXELS_PER_TILE_WIDTH, PIXELS_PER_TILE_HEIGHT
+        ));
+    }
+    let tiles = (image.width / PIXELS_PER_TILE_WIDTH) as usize
+        * (image.height / PIXELS_PER_TILE_HEIGHT) as usize;
+    if tiles != TILES_IN_TILESET {
+        return Err(format!(
+            "the tileset has {} tiles but it should have {}",
+            tiles, TILES_IN_TILESET
+        ));
+    }
+    Ok(())
+}
+
 pub fn extract_images(
     image: &Image,
     pixels_per_tile_width: u16,
@@ -122,6 +156,21 @@ pub fn zoom(image: &[u8], image_width_in_pixels: usize, factor: usize) -> Vec<u8
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_check_tileset() {
+        let image = |width: u16, height: u16| Image {
+            width,
+            height,
+            bytes: vec![0; width as usize * height as usize * COMPONENTS_PER_PIXEL],
+        };
+        assert_eq!(check_tileset(&image(512, 256)), Ok(()));
+        assert!(check_tileset(&image(512, 128)).is_err());
+        assert!(check_tileset(&image(500, 256)).is_err());
+        let mut truncated = image(512, 256);
+        truncated.bytes.pop();
+        assert!(check_tileset(&truncated).is_err());
+    }
+
     #[test]
     fn get_pixels() {
         #[rustfmt::skip]
@@ -0,0 +1,665 @@
+//! A localhost TCP server to drive the game from external scripts, like automated playtests or
+//! bots. Enabled with `--control-port`. It's polled once per frame, so it never blocks the game.
+//!
+//! It speaks line-delimited JSON: each request is an object in one line with a "cmd" and
+//! optionally an "id" that is copied in the response. Responses have `"ok": true` and the
+//! requested data, or `"ok": false` and an "error". The requests are:
+//! - `{"cmd": "transform", "cells": [[x, y, z], ...], "tile": "Wire"}`: queues a task for the
+//!   robots, like choosing a transformation in the GUI. Fails if the GUI wouldn't offer it.
+//! - `{"cmd": "cancel", "task": 0}`: cancels a task by its index in the queue.
+//! - `{"cmd": "camera", "position": [x, y, z]}`: moves the camera.
+//! - `{"cmd": "cell", "position": [x, y, z]}`, `{"cmd": "networks"}` and `{"cmd": "goal"}`:
+//!   describe the current state.
+//! - `{"cmd": "subscribe"}`: sends every new event of the event log, as lines with an "event".
+//!
+//! Only one action of each kind is applied per frame, so the next ones wait for the next frames.
+//! Clients that send or leave unread more than `MAX_BUFFERED_BYTES` are disconnected. The server
+//! is not kept across hot reloads.
+
+use crate::common::json::Json;
+use crate::screen::gui::GuiActions;
+use crate::world::event_log::{Event, EventKind};
+use crate::world::map::transform_cells::Transformation;
+use crate::world::map::{CellIndex, TileType};
+use crate::world::objectives::ObjectiveStatus;
+use crate::world::{GameGoalState, TransformationTask, World};
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// Per client and direction, so that a client can't make the game use unbounded memory.
+pub const MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+
+pub struct ControlServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    /// `EventLog::pushed_count()` when the events were last sent
+    sent_events: usize,
+}
+
+struct Client {
+    stream: TcpStream,
+    /// text received that is not a whole line yet, or lines waiting for the next frame
+    received: String,
+    /// responses that the socket didn't accept yet
+    unsent: Vec<u8>,
+    subscribed: bool,
+    closed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlRequest {
+    Transform(Vec<CellIndex>, TileType),
+    CancelTask(usize),
+    MoveCamera(CellIndex),
+    QueryCell(CellIndex),
+    QueryNetworks,
+    QueryGoal,
+    Subscribe,
+}
+
+impl ControlRequest {
+    pub fn parse(request: &Json) -> Result<Self, String> {
+        let cmd = request
+            .get("cmd")
+            .and_then(|cmd| cmd.as_str())
+            .ok_or_else(|| "missing 'cmd'".to_string())?;
+        let position = || parse_position(request.get("position"), "position");
+        let parsed = match cmd {
+            "transform" => {
+                let cells = request
+                    .get("cells")
+                    .and_then(|cells| cells.as_array())
+                    .ok_or_else(|| "'cells' has to be a list of positions".to_string())?
+                    .iter()
+                    .map(|cell| parse_position(Some(cell), "cells"))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let tile_name = request
+                    .get("tile")
+                    .and_then(|tile| tile.as_str())
+                    .ok_or_else(|| "missing 'tile'".to_string())?;
+                let tile = TileType::from_name(tile_name)
+                    .ok_or_else(|| format!("unknown tile '{}'", tile_name))?;
+                ControlRequest::Transform(cells, tile)
+            }
+            "cancel" => {
+                let task = request
+                    .get("task")
+                    .and_then(|task| task.as_i64())
+                    .filter(|task| *task >= 0)
+                    .ok_or_else(|| "'task' has to be an index of the task queue".to_string())?;
+                ControlRequest::CancelTask(task as usize)
+            }
+            "camera" => ControlRequest::MoveCamera(position()?),
+            "cell" => ControlRequest::QueryCell(position()?),
+            "networks" => ControlRequest::QueryNetworks,
+            "goal" => ControlRequest::QueryGoal,
+            "subscribe" => ControlRequest::Subscribe,
+            _ => return Err(format!("unknown cmd '{}'", cmd)),
+        };
+        Ok(parsed)
+    }
+}
+
+impl ControlServer {
+    /// Only accepts connections from this computer. Use port 0 to let the system choose one.
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("could not start the control server: {}", e);
+        let listener = TcpListener::bind(("127.0.0.1", port)).map_err(error)?;
+        listener.set_nonblocking(true).map_err(error)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+            sent_events: 0,
+        })
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.listener
+            .local_addr()
+            .ok()
+            .map(|address| address.port())
+    }
+
+    /// Accepts new clients, sends them the new events and handles their requests, adding the
+    /// actions to `gui_actions`.
+    pub fn poll(&mut self, world: &World, gui_actions: &mut GuiActions) {
+        self.accept_clients();
+        self.send_events(world);
+        for client in &mut self.clients {
+            client.receive();
+            while let Some(end) = client.received.find('\n') {
+                let line = client.received[..end].trim().to_string();
+                if line.is_empty() {
+                    client.received.drain(..=end);
+                    continue;
+                }
+                match handle_line(&line, world, gui_actions, &mut client.subscribed) {
+                    Some(response) => {
+                        client.received.drain(..=end);
+                        client.send(&response);
+                    }
+                    None => break, // try again in the next frame
+                }
+            }
+            client.flush();
+        }
+        self.clients.retain(|client| !client.closed);
+    }
+
+    fn accept_clients(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(error) = stream.set_nonblocking(true) {
+                        eprintln!("Rejected a control client: {}", error);
+                        continue;
+                    }
+                    self.clients.push(Client {
+                        stream,
+                        received: String::new(),
+                        unsent: Vec::new(),
+                        subscribed: false,
+                        closed: false,
+                    });
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    eprintln!("Error accepting a control client: {}", error);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn send_events(&mut self, world: &World) {
+        let events = world
+            .events
+            .iter_since(self.sent_events)
+            .map(event_json)
+            .collect::<Vec<_>>();
+        self.sent_events = world.events.pushed_count();
+        for client in self.clients.iter_mut().filter(|client| client.subscribed) {
+            for event in &events {
+                client.send(event);
+            }
+        }
+    }
+}
+
+impl Client {
+    fn receive(&mut self) {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(size) => {
+                    self.received
+                        .push_str(&String::from_utf8_lossy(&buffer[..size]));
+                    if self.received.len() > MAX_BUFFERED_BYTES {
+                        eprintln!("Disconnected a control client that sent too much at once");
+                        self.closed = true;
+                        break;
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Only buffers the message, as the socket is non-blocking. See `flush()`.
+    fn send(&mut self, message: &Json) {
+        self.unsent
+            .extend_from_slice(format!("{}\n", message).as_bytes());
+        if self.unsent.len() > MAX_BUFFERED_BYTES {
+            eprintln!("Disconnected a control client that is not reading the responses");
+            self.closed = true;
+        }
+    }
+
+    /// Writes as much of the buffered responses as the socket accepts without blocking.
+    fn flush(&mut self) {
+        while !self.unsent.is_empty() && !self.closed {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => self.closed = true,
+                Ok(size) => {
+                    self.unsent.drain(..size);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+}
+
+/// Returns None if the request is an action that was already taken in this frame.
+fn handle_line(
+    line: &str,
+    world: &World,
+    gui_actions: &mut GuiActions,
+    subscribed: &mut bool,
+) -> Option<Json> {
+    let request = Json::parse(line);
+    let id = request
+        .as_ref()
+        .ok()
+        .and_then(|request| request.get("id").cloned())
+        .unwrap_or(Json::Null);
+    let result = request.and_then(|request| ControlRequest::parse(&request));
+    let result = match result {
+        Ok(request) => handle_request(request, world, gui_actions, subscribed)?,
+        Err(error) => Err(error),
+    };
+    let mut response = vec![("id".to_string(), id)];
+    match result {
+        Ok(Json::Object(entries)) => {
+            response.push(("ok".to_string(), Json::Bool(true)));
+            response.extend(entries);
+        }
+        Ok(_) => response.push(("ok".to_string(), Json::Bool(true))),
+        Err(error) => {
+            response.push(("ok".to_string(), Json::Bool(false)));
+            response.push(("error".to_string(), Json::String(error)));
+        }
+    }
+    Some(Json::Object(response))
+}
+
+fn handle_request(
+    request: ControlRequest,
+    world: &World,
+    gui_actions: &mut GuiActions,
+    subscribed: &mut bool,
+) -> Option<Result<Json, String>> {
+    let result = match request {
+        ControlRequest::Transform(cells, tile) => {
+            if gui_actions.selected_cell_transformation.is_some() {
+                return None;
+            }
+            transform(world, cells, tile).map(|task| {
+                gui_actions.selected_cell_transformation = Some(task);
+                Json::Null
+            })
+        }
+        ControlRequest::CancelTask(task) => {
+            if gui_actions.cancel_task.is_some() {
+                return None;
+            }
+            if task < world.task_queue.len() {
+                gui_actions.cancel_task = Some(task);
+                Ok(Json::Null)
+            } else {
+                Err(format!("there is no task {}", task))
+            }
+        }
+        ControlRequest::MoveCamera(position) => {
+            if gui_actions.go_to_robot.is_some() {
+                return None;
+            }
+            gui_actions.go_to_robot = Some(position);
+            Ok(Json::Null)
+        }
+        ControlRequest::QueryCell(position) => cell_json(world, position),
+        ControlRequest::QueryNetworks => Ok(networks_json(world)),
+        ControlRequest::QueryGoal => Ok(goal_json(world)),
+        ControlRequest::Subscribe => {
+            *subscribed = true;
+            Ok(Json::Null)
+        }
+    };
+    Some(result)
+}
+
+/// Only the transformations that the GUI would offer for those cells.
+fn transform(
+    world: &World,
+    cells: Vec<CellIndex>,
+    tile: TileType,
+) -> Result<TransformationTask, String> {
+    for position in &cells {
+        match world.map.get_cell_optional(*position) {
+            None => return Err(format!("{} is outside the map", position)),
+            Some(cell) if cell.tile_type == TileType::Unset => {
+                return Err(format!("{} is not part of the map yet", position))
+            }
+            Some(_) => {}
+        }
+    }
+    let cells = HashSet::from_iter(cells);
+    let transformation = Transformation::to(tile);
+    if world
+        .allowed_transformations(&cells)
+        .contains(&transformation)
+    {
+        Ok(TransformationTask::new(cells, transformation))
+    } else {
+        Err(format!(
+            "those cells can not be transformed into {:?}",
+            tile
+        ))
+    }
+}
+
+fn parse_position(position: Option<&Json>, key: &str) -> Result<CellIndex, String> {
+    let coordinates = position
+        .and_then(|position| position.as_array())
+        .filter(|coordinates| coordinates.len() == 3)
+        .and_then(|coordinates| {
+            coordinates
+                .iter()
+                .map(|coordinate| coordinate.as_i64().map(|coordinate| coordinate as i32))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| format!("'{}' has to be positions like [x, y, z]", key))?;
+    Ok(CellIndex::new(
+        coordinates[0],
+        coordinates[1],
+        coordinates[2],
+    ))
+}
+
+fn position_json(position: CellIndex) -> Json {
+    Json::Array(vec![
+        position.x.into(),
+        position.y.into(),
+        position.z.into(),
+    ])
+}
+
+fn cell_json(world: &World, position: CellIndex) -> Result<Json, String> {
+    let cell = world
+        .map
+        .get_cell_optional(position)
+        .ok_or_else(|| format!("{} is outside the map", position))?;
+    Ok(Json::object(vec![
+        ("tile", Json::string(format!("{:?}", cell.tile_type))),
+        ("pressure", cell.pressure.into()),
+        ("health", (cell.health as i64).into()),
+        ("network", world.networks.get_network_index(position).into()),
+        ("life", world.life.contains(&position).into()),
+    ]))
+}
+
+/// the ship network is always the first one
+fn networks_json(world: &World) -> Json {
+    let networks = world
+        .networks
+        .iter()
+        .map(|network| {
+            Json::object(vec![
+                ("nodes", network.len().into()),
+                ("stored", network.get_stored_resources().into()),
+                ("capacity", network.get_storage_capacity().into()),
+                ("power_satisfied", network.is_power_satisfied().into()),
+            ])
+        })
+        .collect();
+    Json::object(vec![
+        ("networks", Json::Array(networks)),
+        ("air_cleaned", world.networks.get_total_air_cleaned().into()),
+    ])
+}
+
+fn goal_json(world: &World) -> Json {
+    let finished_at = match world.goal_state {
+        GameGoalState::Finished(age) => Some(age),
+        _ => None,
+    };
+    let objectives = world
+        .objectives
+        .iter()
+        .map(|(objective, status)| {
+            let (status, completed_at) = match status {
+                ObjectiveStatus::Locked => ("locked", None),
+                ObjectiveStatus::Active => ("active", None),
+                ObjectiveStatus::Completed(age) => ("completed", Some(age)),
+            };
+            Json::object(vec![
+                ("name", Json::string(objective.name.as_str())),
+                ("status", Json::string(status)),
+                ("completed_at", completed_at.into()),
+            ])
+        })
+        .collect();
+    Json::object(vec![
+        (
+            "goal_state",
+            Json::string(goal_state_name(world.goal_state)),
+        ),
+        ("finished_at", finished_at.into()),
+        ("age_in_minutes", world.age_in_minutes.into()),
+        ("life", world.life.len().into()),
+        ("air_cleaned", world.networks.get_total_air_cleaned().into()),
+        ("objectives", Json::Array(objectives)),
+    ])
+}
+
+fn goal_state_name(goal_state: GameGoalState) -> &'static str {
+    match goal_state {
+        GameGoalState::InitialDialog => "InitialDialog",
+        GameGoalState::Started => "Started",
+        GameGoalState::Finished(_) => "Finished",
+        GameGoalState::PostFinished => "PostFinished",
+    }
+}
+
+fn event_json(event: &Event) -> Json {
+    let tile = |tile: &TileType| ("tile", Json::string(format!("{:?}", tile)));
+    let (name, mut details) = match &event.kind {
+        EventKind::TaskCompleted(tile_type) => ("TaskCompleted", vec![tile(tile_type)]),
+        EventKind::TaskBlocked(tile_type, reasons) => {
+            let reasons = reasons
+                .iter()
+                .map(|reason| Json::string(format!("{:?}", reason)))
+                .collect();
+            (
+                "TaskBlocked",
+                vec![tile(tile_type), ("reasons", Json::Array(reasons))],
+            )
+        }
+        EventKind::Undone(tile_type) => ("Undone", vec![tile(tile_type)]),
+        EventKind::Redone(tile_type) => ("Redone", vec![tile(tile_type)]),
+        EventKind::HistoryOutdated(tile_type) => ("HistoryOutdated", vec![tile(tile_type)]),
+        EventKind::NetworksMerged(count) => ("NetworksMerged", vec![("networks", (*count).into())]),
+        EventKind::NetworkLostPower => ("NetworkLostPower", vec![]),
+        EventKind::TreeDied => ("TreeDied", vec![]),
+        EventKind::GoalStateChanged(goal_state) => (
+            "GoalStateChanged",
+            vec![("goal_state", Json::string(goal_state_name(*goal_state)))],
+        ),
+        EventKind::ObjectiveCompleted(name) => (
+            "ObjectiveCompleted",
+            vec![("objective", Json::string(name.as_str()))],
+        ),
+    };
+    let mut entries = vec![
+        ("event", Json::string(name)),
+        ("age_in_minutes", event.age_in_minutes.into()),
+        ("position", event.position.map(position_json).into()),
+    ];
+    entries.append(&mut details);
+    Json::object(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A stand-in for an external script.
+    struct TestClient {
+        stream: TcpStream,
+        received: String,
+    }
+
+    impl TestClient {
+        fn connect(server: &ControlServer) -> Self {
+            let stream = TcpStream::connect(("127.0.0.1", server.port().unwrap())).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(5)))
+                .unwrap();
+            Self {
+                stream,
+                received: String::new(),
+            }
+        }
+
+        fn send(&mut self, line: &str) {
+            self.stream
+                .write_all(format!("{}\n", line).as_bytes())
+                .unwrap();
+        }
+
+        /// polls the server until it sends a line
+        fn receive(
+            &mut self,
+            server: &mut ControlServer,
+            world: &World,
+            gui_actions: &mut GuiActions,
+        ) -> Json {
+            for _ in 0..200 {
+                server.poll(world, gui_actions);
+                let mut buffer = [0; 4096];
+                if let Ok(size) = self.stream.read(&mut buffer) {
+                    self.received
+                        .push_str(&String::from_utf8_lossy(&buffer[..size]));
+                }
+                if let Some(end) = self.received.find('\n') {
+                    let line = self.received[..end].to_string();
+                    self.received.drain(..=end);
+                    return Json::parse(&line).unwrap();
+                }
+            }
+            panic!("the server did not answer");
+        }
+
+        fn request(&mut self, server: &mut ControlServer, world: &World, line: &str) -> Json {
+            self.send(line);
+            self.receive(server, world, &mut GuiActions::default())
+        }
+    }
+
+    fn field<'a>(json: &'a Json, key: &str) -> &'a Json {
+        json.get(key).unwrap()
+    }
+
+    #[test]
+    fn test_queries_and_errors() {
+        let mut server = ControlServer::bind(0).unwrap();
+        let mut client = TestClient::connect(&server);
+        let world = World::new();
+
+        let response = client.request(&mut server, &world, r#"{"id": 7, "cmd": "goal"}"#);
+        assert_eq!(field(&response, "id"), &Json::Number(7.0));
+        assert_eq!(field(&response, "ok"), &Json::Bool(true));
+        assert_eq!(
+            field(&response, "goal_state").as_str(),
+            Some("InitialDialog")
+        );
+
+        let ship = world.map.get_ship_position().unwrap();
+        let request = format!(
+            r#"{{"cmd": "cell", "position": [{}, {}, {}]}}"#,
+            ship.x, ship.y, ship.z
+        );
+        let response = client.request(&mut server, &world, &request);
+        assert_eq!(field(&response, "tile").as_str(), Some("MachineShip"));
+        assert_eq!(field(&response, "network").as_i64(), Some(0));
+
+        let response = client.request(&mut server, &world, r#"{"cmd": "networks"}"#);
+        assert_eq!(field(&response, "networks").as_array().unwrap().len(), 1);
+
+        let response = client.request(&mut server, &world, r#"{"cmd": "fly"}"#);
+        assert_eq!(field(&response, "ok"), &Json::Bool(false));
+        assert_eq!(
+            field(&response, "error").as_str(),
+            Some("unknown cmd 'fly'")
+        );
+
+        let response = client.request(&mut server, &world, "not json");
+        assert_eq!(field(&response, "ok"), &Json::Bool(false));
+    }
+
+    #[test]
+    fn test_transform_and_events() {
+        let mut server = ControlServer::bind(0).unwrap();
+        let mut client = TestClient::connect(&server);
+        let mut world = World::new();
+        world.game_state.set_advance_every_frame();
+        let cell = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+
+        let response = client.request(&mut server, &world, r#"{"cmd": "subscribe"}"#);
+        assert_eq!(field(&response, "ok"), &Json::Bool(true));
+
+        client.send(&format!(
+            r#"{{"cmd": "transform", "cells": [[{}, {}, {}]], "tile": "MachineAirCleaner"}}"#,
+            cell.x, cell.y, cell.z
+        ));
+        client.send(&format!(
+            r#"{{"cmd": "transform", "cells": [[{}, {}, {}]], "tile": "Wire"}}"#,
+            cell.x, cell.y, cell.z
+        ));
+        let mut gui_actions = GuiActions::default();
+        let response = client.receive(&mut server, &world, &mut gui_actions);
+        assert_eq!(field(&response, "ok"), &Json::Bool(true));
+        assert_eq!(
+            gui_actions
+                .selected_cell_transformation
+                .as_ref()
+                .map(|task| task.transformation.new_tile_type),
+            Some(TileType::MachineAirCleaner),
+            "only the first transformation is taken in this frame"
+        );
+        world.update(gui_actions);
+        assert_eq!(
+            world.map.get_cell(cell).tile_type,
+            TileType::MachineAirCleaner
+        );
+
+        // the events of the last frame are sent before the answer to the waiting request
+        let mut gui_actions = GuiActions::default();
+        let event = client.receive(&mut server, &world, &mut gui_actions);
+        assert_eq!(field(&event, "event").as_str(), Some("TaskCompleted"));
+        assert_eq!(field(&event, "position"), &position_json(cell));
+        let mut response = client.receive(&mut server, &world, &mut gui_actions);
+        while response.get("event").is_some() {
+            response = client.receive(&mut server, &world, &mut gui_actions);
+        }
+        assert_eq!(field(&response, "ok"), &Json::Bool(true));
+        assert!(gui_actions.selected_cell_transformation.is_some());
+    }
+
+    #[test]
+    fn test_transform_only_allowed_tiles() {
+        let mut server = ControlServer::bind(0).unwrap();
+        let mut client = TestClient::connect(&server);
+        let world = World::new();
+        let cell = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+
+        for tile in ["FloorRock", "Stairs", "Unset", "MachineShip"] {
+            let request = format!(
+                r#"{{"cmd": "transform", "cells": [[{}, {}, {}]], "tile": "{}"}}"#,
+                cell.x, cell.y, cell.z, tile
+            );
+            let mut gui_actions = GuiActions::default();
+            client.send(&request);
+            let response = client.receive(&mut server, &world, &mut gui_actions);
+            assert_eq!(field(&response, "ok"), &Json::Bool(false), "{}", tile);
+            assert!(gui_actions.selected_cell_transformation.is_none());
+        }
+
+        let response = client.request(
+            &mut server,
+            &world,
+            r#"{"cmd": "transform", "cells": [], "tile": "Wire"}"#,
+        );
+        assert_eq!(field(&response, "ok"), &Json::Bool(false));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cast;
 pub mod cell_pixel;
 pub mod cell_tile;
+pub mod cross_section_pixel;
 pub mod tile_pixel;
 pub mod truncate;
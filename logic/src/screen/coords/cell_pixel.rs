@@ -1,6 +1,7 @@
 use crate::screen::coords::cell_tile::{
     cell_to_tile, subcell_to_subtile, subtile_to_subcell, subtile_to_subcell_offset, tile_to_cell,
 };
+use crate::screen::coords::cross_section_pixel::pixel_to_cross_section_cell;
 use crate::screen::coords::tile_pixel::{
     pixel_to_subtile, pixel_to_subtile_offset, pixel_to_tile, subtile_to_pixel, tile_to_pixel,
 };
@@ -11,6 +12,9 @@ use crate::world::map::CellIndex;
 use juquad::PixelPosition;
 
 pub fn clicked_cell(click: PixelPosition, screen_width: f32, drawing: &DrawingState) -> CellIndex {
+    if drawing.view_mode.is_cross_section() {
+        return pixel_to_cross_section_cell(click, drawing, screen_width);
+    }
     let moved_selected = click + hitbox_offset();
     let subcell = pixel_to_subcell_center(moved_selected, drawing, screen_width);
     let (cell, _) = truncate_cell_offset(subcell);
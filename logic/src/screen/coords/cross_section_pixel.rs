@@ -0,0 +1,123 @@
+//! In the cross-section view the cells of the slice are laid out in a flat grid: the columns are
+//! the axis not fixed by the slice plane, and the rows are the heights, with the top level
+//! (`max_cell.y`) at the top of the screen.
+
+use crate::common::trunc::trunc_towards_neg_inf_f;
+use crate::screen::assets::{PIXELS_PER_TILE_HEIGHT, PIXELS_PER_TILE_WIDTH};
+use crate::screen::drawing_state::DrawingState;
+use crate::world::map::CellIndex;
+use juquad::PixelPosition;
+
+pub const CROSS_SECTION_TOP_MARGIN: f32 = 100.0;
+
+/// Each row is half a texture tall, like the vertical offset between layers in the isometric
+/// view, so the texture of a cell covers the top face of the cell below.
+pub fn cross_section_cell_size(zoom: f32) -> PixelPosition {
+    PixelPosition::new(
+        PIXELS_PER_TILE_WIDTH as f32 * zoom,
+        PIXELS_PER_TILE_HEIGHT as f32 * 0.5 * zoom,
+    )
+}
+
+/// returns the top left corner where the texture of the cell should be drawn
+pub fn cross_section_cell_to_pixel(
+    cell_index: CellIndex,
+    drawing: &DrawingState,
+    screen_width: f32,
+) -> PixelPosition {
+    let size = cross_section_cell_size(drawing.zoom);
+    let (min_horizontal, _) = drawing.cross_section_horizontal_range();
+    let column = drawing.cross_section_horizontal_coord(cell_index) - min_horizontal;
+    let row = drawing.max_cell.y - cell_index.y;
+    PixelPosition::new(
+        get_left_margin(drawing, screen_width) + column as f32 * size.x,
+        CROSS_SECTION_TOP_MARGIN + row as f32 * size.y,
+    )
+}
+
+/// Inverse of `cross_section_cell_to_pixel`, using as hitbox the lower half of the texture,
+/// which is the part that is not covered by the cell above.
+pub fn pixel_to_cross_section_cell(
+    pixel: PixelPosition,
+    drawing: &DrawingState,
+    screen_width: f32,
+) -> CellIndex {
+    let size = cross_section_cell_size(drawing.zoom);
+    let (min_horizontal, _) = drawing.cross_section_horizontal_range();
+    let column =
+        trunc_towards_neg_inf_f((pixel.x - get_left_margin(drawing, screen_width)) / size.x);
+    let row = trunc_towards_neg_inf_f((pixel.y - CROSS_SECTION_TOP_MARGIN) / size.y) - 1.0;
+    drawing.cross_section_cell(
+        min_horizontal + column as i32,
+        drawing.max_cell.y - row as i32,
+    )
+}
+
+pub fn cross_section_cell_center_to_pixel(
+    cell_index: CellIndex,
+    drawing: &DrawingState,
+    screen_width: f32,
+) -> PixelPosition {
+    let size = cross_section_cell_size(drawing.zoom);
+    cross_section_cell_to_pixel(cell_index, drawing, screen_width)
+        + PixelPosition::new(size.x * 0.5, size.y * 1.5)
+}
+
+fn get_left_margin(drawing: &DrawingState, screen_width: f32) -> f32 {
+    let (min_horizontal, max_horizontal) = drawing.cross_section_horizontal_range();
+    let columns = max_horizontal - min_horizontal + 1;
+    (screen_width - columns as f32 * cross_section_cell_size(drawing.zoom).x) * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::drawing_state::ViewMode;
+
+    fn cell_to_pixel_to_cell(initial_cell: CellIndex, view_mode: ViewMode, zoom: f32) {
+        let mut drawing = DrawingState::new();
+        drawing.view_mode = view_mode;
+        drawing.zoom = zoom;
+        let screen_width = 800.0;
+        let pixel = cross_section_cell_center_to_pixel(initial_cell, &drawing, screen_width);
+        let final_cell = pixel_to_cross_section_cell(pixel, &drawing, screen_width);
+        assert_eq!(final_cell, initial_cell);
+    }
+
+    #[test]
+    fn test_cross_section_cell_to_pixel_to_cell() {
+        for view_mode in [ViewMode::CrossSectionX, ViewMode::CrossSectionZ] {
+            let mut drawing = DrawingState::new();
+            drawing.view_mode = view_mode;
+            let plane = drawing.cross_section_plane().unwrap();
+            for zoom in [0.5, 1.0, 1.75] {
+                cell_to_pixel_to_cell(drawing.cross_section_cell(0, 0), view_mode, zoom);
+                cell_to_pixel_to_cell(drawing.cross_section_cell(-10, -3), view_mode, zoom);
+                cell_to_pixel_to_cell(drawing.cross_section_cell(9, -10), view_mode, zoom);
+                cell_to_pixel_to_cell(drawing.cross_section_cell(plane, 1), view_mode, zoom);
+            }
+        }
+    }
+
+    #[test]
+    fn test_columns_are_centered() {
+        let mut drawing = DrawingState::new();
+        drawing.view_mode = ViewMode::CrossSectionZ;
+        let screen_width = 800.0;
+        let (min_horizontal, max_horizontal) = drawing.cross_section_horizontal_range();
+        let y = drawing.max_cell.y;
+        let left = cross_section_cell_to_pixel(
+            drawing.cross_section_cell(min_horizontal, y),
+            &drawing,
+            screen_width,
+        );
+        let right = cross_section_cell_to_pixel(
+            drawing.cross_section_cell(max_horizontal, y),
+            &drawing,
+            screen_width,
+        );
+        let size = cross_section_cell_size(drawing.zoom);
+        assert_eq!(left.x, screen_width - (right.x + size.x));
+        assert_eq!(left.y, CROSS_SECTION_TOP_MARGIN);
+    }
+}
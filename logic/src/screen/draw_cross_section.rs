@@ -0,0 +1,96 @@
+use crate::screen::coords::cross_section_pixel::{
+    cross_section_cell_center_to_pixel, cross_section_cell_size, cross_section_cell_to_pixel,
+};
+use crate::screen::draw_map::{choose_texture, grey, SELECTION_COLOR};
+use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::drawing_state::DrawingState;
+use crate::screen::gui::{FONT_SIZE, TEXT_COLOR};
+use crate::screen::overlay::{overlay_tint, tint};
+use crate::world::map::{CellIndex, TileType};
+use crate::world::World;
+use mq_basics::Color;
+
+const SHIP_NETWORK_COLOR: Color = Color::new(0.2, 0.8, 0.2, 1.0);
+const UNCONNECTED_NETWORK_COLOR: Color = Color::new(0.9, 0.6, 0.1, 1.0);
+const NETWORK_MARKER_SIZE: f32 = 6.0;
+
+/// Draws the vertical slice chosen by `drawing.view_mode`. Unlike `draw_map` there is no fog,
+/// every cell in the slice is fully visible, with its pressure and network membership on top.
+pub fn draw_cross_section(drawer: &dyn DrawerTrait, world: &World, drawing: &DrawingState) {
+    let (min_horizontal, max_horizontal) = drawing.cross_section_horizontal_range();
+    for i_y in drawing.min_cell.y..=drawing.max_cell.y {
+        for i_horizontal in min_horizontal..=max_horizontal {
+            let cell_index = drawing.cross_section_cell(i_horizontal, i_y);
+            draw_cross_section_cell(drawer, world, cell_index, drawing);
+        }
+    }
+    // the annotations go after all the textures so that the upper cells don't cover them
+    for i_y in drawing.min_cell.y..=drawing.max_cell.y {
+        for i_horizontal in min_horizontal..=max_horizontal {
+            let cell_index = drawing.cross_section_cell(i_horizontal, i_y);
+            draw_pressure_and_network(drawer, world, cell_index, drawing);
+        }
+    }
+}
+
+fn draw_cross_section_cell(
+    drawer: &dyn DrawerTrait,
+    world: &World,
+    cell_index: CellIndex,
+    drawing: &DrawingState,
+) {
+    let cell = world.map.get_cell(cell_index);
+    let tile_type = cell.tile_type;
+    let texture = choose_texture(cell, &tile_type);
+    let pixel = cross_section_cell_to_pixel(cell_index, drawing, drawer.screen_width()).round();
+
+    let mut color = if tile_type == TileType::Air && cell.pressure == 0 {
+        grey(0.0, 0.0)
+    } else {
+        grey(1.0, 1.0)
+    };
+    if let Some(overlay_color) = overlay_tint(world, cell_index, drawing.overlay) {
+        color = tint(color, overlay_color);
+    }
+    if drawing.highlighted_cells().contains(&cell_index) {
+        color.r = SELECTION_COLOR.r;
+        color.g = SELECTION_COLOR.g;
+        color.b = SELECTION_COLOR.b;
+        color.a = SELECTION_COLOR.a;
+    }
+    drawer.draw_colored_texture(texture, pixel.x, pixel.y, drawing.zoom, color);
+}
+
+fn draw_pressure_and_network(
+    drawer: &dyn DrawerTrait,
+    world: &World,
+    cell_index: CellIndex,
+    drawing: &DrawingState,
+) {
+    let cell = world.map.get_cell(cell_index);
+    let center = cross_section_cell_center_to_pixel(cell_index, drawing, drawer.screen_width());
+    let size = cross_section_cell_size(drawing.zoom);
+    if cell.pressure > 0 {
+        drawer.draw_text(
+            &format!("{}", cell.pressure),
+            center.x - size.x * 0.25,
+            center.y,
+            FONT_SIZE,
+            TEXT_COLOR,
+        );
+    }
+    if world.networks.get(cell_index).is_some() {
+        let color = if world.networks.is_in_ship_network(cell_index) {
+            SHIP_NETWORK_COLOR
+        } else {
+            UNCONNECTED_NETWORK_COLOR
+        };
+        drawer.draw_rectangle(
+            center.x + size.x * 0.25,
+            center.y - NETWORK_MARKER_SIZE * 0.5,
+            NETWORK_MARKER_SIZE,
+            NETWORK_MARKER_SIZE,
+            color,
+        );
+    }
+}
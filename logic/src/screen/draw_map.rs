@@ -6,32 +6,71 @@ use crate::screen::coords::truncate::assert_in_range_0_1;
 use crate::screen::drawer_trait::DrawerTrait;
 use crate::screen::drawing_state::{DrawingState, SubCellIndex, SubTilePosition};
 use crate::screen::gui::{FONT_SIZE, TEXT_COLOR};
+use crate::screen::overlay::{overlay_tint, tint};
 use crate::world::fluids::VERTICAL_PRESSURE_DIFFERENCE;
-use crate::world::map::cell::{is_networkable, ExtraTextures, TextureIndexTrait};
+use crate::world::map::cell::{
+    is_networkable, is_opaque_cube, ExtraTextures, TextureIndex, TextureIndexTrait,
+};
 use crate::world::map::{Cell, CellIndex, Pressure, TileType};
 use crate::world::World;
 use juquad::PixelPosition;
 use mq_basics::Color;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
-const SELECTION_COLOR: Color = Color::new(0.7, 0.8, 1.0, 1.0);
+pub const SELECTION_COLOR: Color = Color::new(0.7, 0.8, 1.0, 1.0);
+pub const GHOST_COLOR: Color = Color::new(0.8, 1.0, 0.8, 0.6);
+pub const FORBIDDEN_COLOR: Color = Color::new(1.0, 0.3, 0.3, 0.8);
 
-pub fn draw_map(drawer: &dyn DrawerTrait, world: &World, drawing: &DrawingState) {
+/// The layers that had nothing to draw, with the key they had then. While the key stays the same
+/// they are skipped without visiting their cells.
+#[derive(Default)]
+pub struct EmptyLayers {
+    keys: HashMap<i32, u64>,
+}
+
+pub fn draw_map(
+    drawer: &mut dyn DrawerTrait,
+    world: &World,
+    drawing: &DrawingState,
+    empty_layers: &mut EmptyLayers,
+) {
+    let frame_key = frame_key(drawer, world, drawing);
     let min_cell = &drawing.min_cell;
     let max_cell = &drawing.max_cell;
-    let mut fog = grey(0.5, 1.0 / (max_cell.y - min_cell.y) as f32);
+    let fog_layer_alpha = 1.0 / (max_cell.y - min_cell.y) as f32;
     // let fog = grey(0.5, 0.7);
+    let mut pending_fog_alpha = 0.0;
     for i_y in min_cell.y..=max_cell.y {
         // draw fog on lower levels, without affecting the top level textures
-        if i_y == max_cell.y {
-            fog.a += 0.4;
+        let fog_alpha = if i_y == max_cell.y {
+            fog_layer_alpha + 0.4
+        } else {
+            fog_layer_alpha
+        };
+        pending_fog_alpha = merge_fog_alphas(pending_fog_alpha, fog_alpha);
+        let key = layer_key(frame_key, world, i_y);
+        if empty_layers.keys.get(&i_y) == Some(&key) {
+            // several fog rectangles in a row are equivalent to a single more opaque one
+            continue;
         }
-        drawer.draw_rectangle(0.0, 0.0, drawer.screen_width(), drawer.screen_height(), fog);
-        for i_z in min_cell.z..=max_cell.z {
-            for i_x in min_cell.x..=max_cell.x {
-                draw_cell(drawer, world, CellIndex::new(i_x, i_y, i_z), drawing);
-            }
+        draw_fog(drawer, pending_fog_alpha);
+        pending_fog_alpha = 0.0;
+        if drawer.draw_cached_layer(i_y, key) {
+            continue;
+        }
+        let cells = get_visible_cells_in_layer(drawer, world, drawing, i_y);
+        if cells.is_empty() {
+            empty_layers.keys.insert(i_y, key);
+        } else {
+            empty_layers.keys.remove(&i_y);
+            draw_layer(drawer, i_y, key, &cells, drawing.zoom);
         }
     }
+    if pending_fog_alpha > 0.0 {
+        draw_fog(drawer, pending_fog_alpha);
+    }
     // for i_z in min_cell.z..=max_cell.z {
     //     for i_x in min_cell.x..=max_cell.x {
     //         draw_cell(drawer, world, CellIndex::new(i_x, max_cell.y + 1, i_z), drawing);
@@ -39,18 +78,115 @@ pub fn draw_map(drawer: &dyn DrawerTrait, world: &World, drawing: &DrawingState)
     // }
 }
 
-fn draw_cell(
+/// Everything needed to draw a cell.
+struct CellDraw {
+    texture: TextureIndex,
+    pixel: PixelPosition,
+    color: Color,
+}
+
+/// Changes whenever the cells of the layer change, or the `frame_key`. The cells above are included
+/// because they can hide the cells of the layer, see `is_hidden()`.
+fn layer_key(frame_key: u64, world: &World, i_y: i32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    frame_key.hash(&mut hasher);
+    i_y.hash(&mut hasher);
+    world.map.layer_version(i_y).hash(&mut hasher);
+    world.map.layer_version(i_y + 1).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Changes whenever anything that all the layers show changes: the camera, the screen, the
+/// selection, the overlay, or the state of the networks that the overlays show.
+fn frame_key(drawer: &dyn DrawerTrait, world: &World, drawing: &DrawingState) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    drawing.min_cell.hash(&mut hasher);
+    drawing.max_cell.hash(&mut hasher);
+    for value in [
+        drawing.subtile_offset.x,
+        drawing.subtile_offset.y,
+        drawing.subcell_diff.x,
+        drawing.subcell_diff.y,
+        drawing.subcell_diff.z,
+        drawing.zoom,
+        drawer.screen_width(),
+        drawer.screen_height(),
+    ] {
+        value.to_bits().hash(&mut hasher);
+    }
+    (drawing.overlay as u8).hash(&mut hasher);
+    for network in world.networks.iter() {
+        (network.len(), network.is_power_satisfied()).hash(&mut hasher);
+    }
+    // the sets have no order, so the hashes of their cells are added up instead
+    let set_hash = |cells: &mut dyn Iterator<Item = &CellIndex>| {
+        cells.fold(0_u64, |sum, cell| {
+            let mut cell_hasher = DefaultHasher::new();
+            cell.hash(&mut cell_hasher);
+            sum.wrapping_add(cell_hasher.finish())
+        })
+    };
+    set_hash(&mut drawing.highlighted_cells_merged().iter()).hash(&mut hasher);
+    if let Some(preview) = &drawing.preview {
+        (preview.transformation.new_tile_type as u8).hash(&mut hasher);
+        set_hash(&mut preview.forbidden.keys()).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn draw_layer(drawer: &mut dyn DrawerTrait, i_y: i32, key: u64, cells: &Vec<CellDraw>, zoom: f32) {
+    drawer.begin_cached_layer(i_y, key);
+    for cell in cells {
+        drawer.draw_colored_texture(&cell.texture, cell.pixel.x, cell.pixel.y, zoom, cell.color);
+    }
+    drawer.end_cached_layer();
+}
+
+fn draw_fog(drawer: &dyn DrawerTrait, alpha: f32) {
+    let fog = grey(0.5, alpha);
+    drawer.draw_rectangle(0.0, 0.0, drawer.screen_width(), drawer.screen_height(), fog);
+}
+
+/// drawing 2 rectangles of the same color is the same as drawing 1 with this opacity
+fn merge_fog_alphas(alpha_1: f32, alpha_2: f32) -> f32 {
+    1.0 - (1.0 - alpha_1) * (1.0 - alpha_2)
+}
+
+fn get_visible_cells_in_layer(
+    drawer: &dyn DrawerTrait,
+    world: &World,
+    drawing: &DrawingState,
+    i_y: i32,
+) -> Vec<CellDraw> {
+    let min_cell = &drawing.min_cell;
+    let max_cell = &drawing.max_cell;
+    let mut cells = Vec::new();
+    for i_z in min_cell.z..=max_cell.z {
+        for i_x in min_cell.x..=max_cell.x {
+            let cell_index = CellIndex::new(i_x, i_y, i_z);
+            if !is_hidden(world, cell_index, drawing) {
+                if let Some(cell_draw) = prepare_cell(drawer, world, cell_index, drawing) {
+                    cells.push(cell_draw);
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// returns None if the cell would not be visible
+fn prepare_cell(
     drawer: &dyn DrawerTrait,
     world: &World,
     cell_index: CellIndex,
     drawing: &DrawingState,
-) {
+) -> Option<CellDraw> {
     let screen_width = drawer.screen_width();
     let min_cell = &drawing.min_cell;
     let max_cell = &drawing.max_cell;
     let cell = world.map.get_cell(cell_index);
     let tile_type = cell.tile_type;
-    let texture = choose_texture(cell, &tile_type);
+    let mut texture = TextureIndex::from(choose_texture(cell, &tile_type));
     let depth = max_cell.y - cell_index.y;
 
     let mut pixel = cell_to_pixel(cell_index, drawing, screen_width);
@@ -58,6 +194,9 @@ fn draw_cell(
         subtile_to_pixel_offset(SubTilePosition::new(1.0 / 64.0, -0.5), drawing.zoom);
     pixel += level_offset * depth as f32;
     pixel = pixel.round();
+    if is_off_screen(pixel, drawing.zoom, screen_width, drawer.screen_height()) {
+        return None;
+    }
 
     // let opacity = 1.0; // for debugging
     let opacity = get_opacity(
@@ -79,6 +218,9 @@ fn draw_cell(
         let fog = 1.0;
         Color::new(fog, fog, fog, opacity)
     };
+    if let Some(overlay_color) = overlay_tint(world, cell_index, drawing.overlay) {
+        color = tint(color, overlay_color);
+    }
     if drawing.highlighted_cells().contains(&cell_index) {
         color.r = SELECTION_COLOR.r;
         color.g = SELECTION_COLOR.g;
@@ -86,17 +228,79 @@ fn draw_cell(
         if tile_type == TileType::Air {
             color.a = SELECTION_COLOR.a;
         }
+        if let Some(preview) = &drawing.preview {
+            // ghost of the hovered transformation, keeping the old texture if it would be removed
+            let new_tile_type = preview.transformation.new_tile_type;
+            if new_tile_type != TileType::Air {
+                texture = TextureIndex::from(new_tile_type);
+            }
+            color = if preview.forbidden.contains_key(&cell_index) {
+                FORBIDDEN_COLOR
+            } else {
+                GHOST_COLOR
+            };
+        }
     }
-    drawer.draw_colored_texture(texture, pixel.x, pixel.y, drawing.zoom, color);
+    if color.a <= 0.0 {
+        return None;
+    }
+    Some(CellDraw {
+        texture,
+        pixel,
+        color,
+    })
     // draw_pressure_number(drawer, cell_index, screen_width, drawing, max_cell, cell)
     // draw_cell_hit_box(drawer, game_state, cell_index);
 }
 
-fn grey(lightness: f32, opacity: f32) -> Color {
+fn is_off_screen(pixel: PixelPosition, zoom: f32, screen_width: f32, screen_height: f32) -> bool {
+    let tile_width = assets::PIXELS_PER_TILE_WIDTH as f32 * zoom;
+    let tile_height = assets::PIXELS_PER_TILE_HEIGHT as f32 * zoom;
+    pixel.x + tile_width < 0.0
+        || pixel.y + tile_height < 0.0
+        || pixel.x > screen_width
+        || pixel.y > screen_height
+}
+
+/// A cell is hidden if the 3 faces we can see (top, and the 2 facing the camera) are touching
+/// opaque cells that will be drawn after it.
+fn is_hidden(world: &World, cell_index: CellIndex, drawing: &DrawingState) -> bool {
+    let covering_neighbours = [
+        cell_index + CellIndex::new(0, 1, 0),
+        cell_index + CellIndex::new(1, 0, 0),
+        cell_index + CellIndex::new(0, 0, 1),
+    ];
+    for neighbour in covering_neighbours {
+        if !is_opaque_when_drawn(world, neighbour, drawing) {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_opaque_when_drawn(world: &World, cell_index: CellIndex, drawing: &DrawingState) -> bool {
+    let inside_drawn_cube = cell_index.x <= drawing.max_cell.x
+        && cell_index.y <= drawing.max_cell.y
+        && cell_index.z <= drawing.max_cell.z;
+    if !inside_drawn_cube {
+        return false;
+    }
+    let cell = world.map.get_cell(cell_index);
+    cell.renderable_pressure <= 0
+        && is_opaque_cube(cell.tile_type)
+        && get_border_opacity(
+            &cell_index,
+            &drawing.min_cell,
+            &drawing.max_cell,
+            &drawing.subcell_diff,
+        ) == 1.0
+}
+
+pub fn grey(lightness: f32, opacity: f32) -> Color {
     Color::new(lightness, lightness, lightness, opacity)
 }
 
-fn choose_texture<'a>(cell: &'a Cell, tile_type: &'a TileType) -> &'a dyn TextureIndexTrait {
+pub fn choose_texture<'a>(cell: &'a Cell, tile_type: &'a TileType) -> &'a dyn TextureIndexTrait {
     if cell.renderable_pressure <= 0 {
         tile_type
     } else if cell.renderable_pressure <= VERTICAL_PRESSURE_DIFFERENCE {
@@ -223,6 +427,46 @@ pub fn hitbox_offset_square() -> PixelPosition {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::world::map::{CellCubeIterator, Map};
+
+    #[test]
+    fn test_merge_fog_alphas() {
+        assert_eq!(merge_fog_alphas(0.0, 0.25), 0.25);
+        assert_eq!(merge_fog_alphas(0.5, 0.5), 0.75);
+        assert_eq!(merge_fog_alphas(1.0, 0.3), 1.0);
+    }
+
+    #[test]
+    fn test_off_screen() {
+        let off_screen = |x, y, zoom| is_off_screen(PixelPosition::new(x, y), zoom, 800.0, 600.0);
+        assert!(!off_screen(0.0, 0.0, 1.0));
+        assert!(!off_screen(-63.0, 0.0, 1.0));
+        assert!(off_screen(-65.0, 0.0, 1.0));
+        assert!(!off_screen(-65.0, 0.0, 2.0));
+        assert!(off_screen(0.0, 601.0, 1.0));
+    }
+
+    #[test]
+    fn test_hidden_cells() {
+        let mut world = World::new();
+        let mut tiles = Vec::new();
+        for cell_index in CellCubeIterator::new(CellIndex::new(0, 0, 0), CellIndex::new(3, 3, 3)) {
+            tiles.push((cell_index, TileType::WallRock));
+        }
+        world.map = Map::_new_from_tiles(Cell::new(TileType::WallRock), tiles);
+        let mut drawing = DrawingState::new();
+        drawing.min_cell = CellIndex::new(0, 0, 0);
+        drawing.max_cell = CellIndex::new(3, 3, 3);
+
+        assert!(is_hidden(&world, CellIndex::new(1, 1, 1), &drawing));
+        // nothing is drawn above the top layer, and nothing in front of the borders
+        assert!(!is_hidden(&world, CellIndex::new(1, 3, 1), &drawing));
+        assert!(!is_hidden(&world, CellIndex::new(3, 1, 1), &drawing));
+        assert!(!is_hidden(&world, CellIndex::new(1, 1, 3), &drawing));
+
+        world.map.get_cell_mut(CellIndex::new(1, 2, 1)).tile_type = TileType::Air;
+        assert!(!is_hidden(&world, CellIndex::new(1, 1, 1), &drawing));
+    }
 
     #[test]
     fn transparency_border_no_offset() {
@@ -60,6 +60,14 @@ pub trait DrawerTrait {
     fn draw_text(&self, text: &str, x: f32, y: f32, font_size: f32, color: Color);
     fn measure_text(&mut self, text: &str, font_size: f32) -> Vec2;
 
+    /// If there is a layer cached with this key, draws it and returns true. The key has to change
+    /// whenever anything drawn in that layer changes.
+    fn draw_cached_layer(&mut self, layer: i32, key: u64) -> bool;
+    /// The draw_* calls after this one go to an offscreen texture instead of the screen, until
+    /// `end_cached_layer` is called, which draws that texture on the screen.
+    fn begin_cached_layer(&mut self, layer: i32, key: u64);
+    fn end_cached_layer(&mut self);
+
     /// all ui_* methods need to run inside ui_run. This is a restriction of using egui_miniquad :(
     fn ui_run(&mut self, f: &mut dyn FnMut(&mut dyn DrawerTrait) -> ());
     fn ui_draw(&mut self);
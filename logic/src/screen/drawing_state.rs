@@ -1,11 +1,20 @@
 pub mod change_height;
+pub mod cross_section;
 pub mod highlight_cells;
 pub mod move_horizontally;
 
+use crate::common::snapshot::{
+    cell_index_str, parse_cycling, parse_value, Snapshot, SnapshotReader, SnapshotWriter,
+};
 use crate::screen::drawing_state::highlight_cells::CellIndexSet;
 use crate::screen::gui::GuiActions;
+use crate::screen::keybindings::Action;
 use crate::screen::main_scene_input::ZoomChange;
+use crate::screen::overlay::Overlay;
+use crate::world::console::ConsoleCommand;
+use crate::world::map::transformation_preview::TransformationPreview;
 use crate::world::map::CellIndex;
+use crate::world::World;
 use mq_basics::{IVec2, Vec2, Vec3};
 use std::collections::HashSet;
 
@@ -15,6 +24,7 @@ pub type SubCellIndex = Vec3;
 
 pub const DEFAULT_RENDER_DEPTH: i32 = 10;
 pub const DEFAULT_RENDER_HALF_SIDE: i32 = 10;
+pub const MAX_RENDER_DEPTH: i32 = 60;
 
 // #[derive(Clone)]
 pub struct DrawingState {
@@ -23,7 +33,28 @@ pub struct DrawingState {
     pub subtile_offset: SubTilePosition,
     pub subcell_diff: SubCellIndex,
     pub top_bar_showing: TopBarShowing,
+    /// to open the Goals panel when an objective is completed
+    pub seen_completed_objectives: usize,
     pub zoom: f32,
+    pub view_mode: ViewMode,
+    pub overlay: Overlay,
+    pub selection_mode: SelectionMode,
+    /// waiting for a key press to assign it to this action
+    pub rebinding: Option<Action>,
+    /// destructive action waiting for the user to confirm it
+    pub confirming: Option<Action>,
+    pub show_event_log: bool,
+    /// how many of the newest events are skipped in the event log panel
+    pub event_log_scroll: usize,
+    /// transformation hovered in the available actions panel, drawn as ghost tiles
+    pub preview: Option<TransformationPreview>,
+    pub console_open: bool,
+    /// command being typed in the console
+    pub console_text: String,
+    /// why the last command typed could not be parsed
+    pub console_error: Option<String>,
+    /// commands waiting to be sent to the World in the next frame, like the startup script
+    pub console_queue: Vec<ConsoleCommand>,
     cell_index_set: CellIndexSet,
     highlight_start_height: Option<i32>,
 }
@@ -32,9 +63,101 @@ pub struct DrawingState {
 pub enum TopBarShowing {
     Goals,
     Help,
+    Keys,
     None,
 }
 
+/// How the box dragged with the mouse becomes the selected cells. Box selects all of them,
+/// SameType only the ones with the tile of the first cell, Column extends the box down to the
+/// lowest layer shown, FloodFill ignores the box and selects the visible cells connected to the
+/// first one with the same tile, and Network selects all the machines of the first cell's network.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum SelectionMode {
+    Box,
+    SameType,
+    Column,
+    FloodFill,
+    Network,
+}
+
+/// CrossSectionX shows the vertical plane with a constant x, CrossSectionZ the one with a
+/// constant z. Layers is the usual isometric view.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ViewMode {
+    Layers,
+    CrossSectionX,
+    CrossSectionZ,
+}
+
+/// Only the camera and the view options are saved. The selection, the panels being shown, the
+/// console and the preview start empty after loading.
+impl Snapshot for DrawingState {
+    fn save(&self, snapshot: &mut SnapshotWriter) {
+        snapshot.line("drawing.min_cell", cell_index_str(self.min_cell));
+        snapshot.line("drawing.max_cell", cell_index_str(self.max_cell));
+        snapshot.line(
+            "drawing.subtile_offset",
+            format!("{} {}", self.subtile_offset.x, self.subtile_offset.y),
+        );
+        snapshot.line(
+            "drawing.subcell_diff",
+            format!(
+                "{} {} {}",
+                self.subcell_diff.x, self.subcell_diff.y, self.subcell_diff.z
+            ),
+        );
+        snapshot.line("drawing.zoom", self.zoom);
+        snapshot.line("drawing.view_mode", format!("{:?}", self.view_mode));
+        snapshot.line("drawing.overlay", format!("{:?}", self.overlay));
+        snapshot.line(
+            "drawing.selection_mode",
+            format!("{:?}", self.selection_mode),
+        );
+        snapshot.line("drawing.show_event_log", self.show_event_log);
+    }
+
+    fn load(snapshot: &SnapshotReader) -> Result<Self, String> {
+        let mut drawing = DrawingState::new();
+        drawing.min_cell = snapshot.get_cell_index("drawing.min_cell")?;
+        drawing.max_cell = snapshot.get_cell_index("drawing.max_cell")?;
+        let key = "drawing.subtile_offset";
+        let values = snapshot.get_single(key)?;
+        drawing.subtile_offset =
+            SubTilePosition::new(parse_value(values, 0, key)?, parse_value(values, 1, key)?);
+        let key = "drawing.subcell_diff";
+        let values = snapshot.get_single(key)?;
+        drawing.subcell_diff = SubCellIndex::new(
+            parse_value(values, 0, key)?,
+            parse_value(values, 1, key)?,
+            parse_value(values, 2, key)?,
+        );
+        drawing.zoom = snapshot.get("drawing.zoom")?;
+        let key = "drawing.view_mode";
+        drawing.view_mode = parse_cycling(
+            &snapshot.get::<String>(key)?,
+            ViewMode::Layers,
+            ViewMode::next,
+            key,
+        )?;
+        let key = "drawing.overlay";
+        drawing.overlay = parse_cycling(
+            &snapshot.get::<String>(key)?,
+            Overlay::None,
+            Overlay::next,
+            key,
+        )?;
+        let key = "drawing.selection_mode";
+        drawing.selection_mode = parse_cycling(
+            &snapshot.get::<String>(key)?,
+            SelectionMode::Box,
+            SelectionMode::next,
+            key,
+        )?;
+        drawing.show_event_log = snapshot.get("drawing.show_event_log")?;
+        Ok(drawing)
+    }
+}
+
 impl DrawingState {
     pub fn new() -> Self {
         Self::new_centered(CellIndex::new(0, 1, 0))
@@ -47,7 +170,20 @@ impl DrawingState {
             subtile_offset: SubTilePosition::new(0.0, 0.0),
             subcell_diff: SubCellIndex::new(0.0, 0.0, 0.0),
             zoom: 1.0,
+            view_mode: ViewMode::Layers,
+            overlay: Overlay::None,
+            selection_mode: SelectionMode::Box,
+            rebinding: None,
+            confirming: None,
+            show_event_log: true,
+            event_log_scroll: 0,
+            preview: None,
+            console_open: false,
+            console_text: String::new(),
+            console_error: None,
+            console_queue: Vec::new(),
             top_bar_showing: TopBarShowing::None,
+            seen_completed_objectives: 0,
             cell_index_set: CellIndexSet::new(),
             highlight_start_height: None,
         }
@@ -64,14 +200,19 @@ impl DrawingState {
         self.cell_index_set.set_highlighted_cells(cells)
     }
 
-    pub fn apply_input(&mut self, gui_actions: &GuiActions) {
+    pub fn apply_input(&mut self, gui_actions: &GuiActions, world: &World) {
         self.maybe_change_height_rel(gui_actions.change_height_rel, gui_actions.go_to_robot);
         self.maybe_move_map_horizontally(
             gui_actions.move_map_horizontally_diff,
             gui_actions.go_to_robot,
         );
-        self.maybe_select_cells_from_pixels(&gui_actions.cell_selection);
+        self.maybe_select_cells_from_pixels(&gui_actions.cell_selection, world);
         self.update_zoom(gui_actions.zoom_change);
+        for command in &gui_actions.console_commands {
+            if let ConsoleCommand::Teleport(position) = command {
+                self.re_center(*position);
+            }
+        }
     }
 
     pub fn re_center(&mut self, cell_index: CellIndex) {
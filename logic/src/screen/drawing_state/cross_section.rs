@@ -0,0 +1,95 @@
+use crate::screen::drawing_state::{DrawingState, ViewMode};
+use crate::world::map::CellIndex;
+
+impl ViewMode {
+    pub fn is_cross_section(&self) -> bool {
+        *self != ViewMode::Layers
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViewMode::Layers => "Layers",
+            ViewMode::CrossSectionX => "Slice X",
+            ViewMode::CrossSectionZ => "Slice Z",
+        }
+    }
+    pub fn next(&self) -> Self {
+        match self {
+            ViewMode::Layers => ViewMode::CrossSectionX,
+            ViewMode::CrossSectionX => ViewMode::CrossSectionZ,
+            ViewMode::CrossSectionZ => ViewMode::Layers,
+        }
+    }
+}
+
+impl DrawingState {
+    pub fn cycle_view_mode(&mut self) {
+        self.view_mode = self.view_mode.next();
+    }
+
+    /// The fixed coordinate of the slice, which is the center of the horizontal area shown in
+    /// the layers view, so that moving the map also moves the slice.
+    pub fn cross_section_plane(&self) -> Option<i32> {
+        match self.view_mode {
+            ViewMode::Layers => None,
+            ViewMode::CrossSectionX => Some((self.min_cell.x + self.max_cell.x + 1) / 2),
+            ViewMode::CrossSectionZ => Some((self.min_cell.z + self.max_cell.z + 1) / 2),
+        }
+    }
+
+    /// returns the inclusive range of the axis that is drawn horizontally on the screen
+    pub fn cross_section_horizontal_range(&self) -> (i32, i32) {
+        match self.view_mode {
+            ViewMode::CrossSectionX => (self.min_cell.z, self.max_cell.z),
+            ViewMode::Layers | ViewMode::CrossSectionZ => (self.min_cell.x, self.max_cell.x),
+        }
+    }
+
+    pub fn cross_section_horizontal_coord(&self, cell_index: CellIndex) -> i32 {
+        match self.view_mode {
+            ViewMode::CrossSectionX => cell_index.z,
+            ViewMode::Layers | ViewMode::CrossSectionZ => cell_index.x,
+        }
+    }
+
+    pub fn cross_section_cell(&self, horizontal: i32, y: i32) -> CellIndex {
+        let plane = self.cross_section_plane().unwrap_or_default();
+        match self.view_mode {
+            ViewMode::CrossSectionX => CellIndex::new(plane, y, horizontal),
+            ViewMode::Layers | ViewMode::CrossSectionZ => CellIndex::new(horizontal, y, plane),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycle_view_mode() {
+        let mut drawing = DrawingState::new();
+        assert_eq!(drawing.view_mode, ViewMode::Layers);
+        assert_eq!(drawing.cross_section_plane(), None);
+        drawing.cycle_view_mode();
+        assert_eq!(drawing.view_mode, ViewMode::CrossSectionX);
+        drawing.cycle_view_mode();
+        assert_eq!(drawing.view_mode, ViewMode::CrossSectionZ);
+        drawing.cycle_view_mode();
+        assert_eq!(drawing.view_mode, ViewMode::Layers);
+    }
+
+    #[test]
+    fn test_cross_section_cell_roundtrip() {
+        let mut drawing = DrawingState::new_centered(CellIndex::new(5, 1, -3));
+        drawing.view_mode = ViewMode::CrossSectionX;
+        let plane = drawing.cross_section_plane().unwrap();
+        assert_eq!(plane, 5);
+        let cell = drawing.cross_section_cell(-7, -2);
+        assert_eq!(cell, CellIndex::new(5, -2, -7));
+        assert_eq!(drawing.cross_section_horizontal_coord(cell), -7);
+
+        drawing.view_mode = ViewMode::CrossSectionZ;
+        let cell = drawing.cross_section_cell(4, 0);
+        assert_eq!(cell, CellIndex::new(4, 0, -3));
+        assert_eq!(drawing.cross_section_horizontal_coord(cell), 4);
+    }
+}
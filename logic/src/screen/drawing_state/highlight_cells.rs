@@ -1,28 +1,75 @@
-use crate::screen::drawing_state::DrawingState;
+use crate::screen::drawing_state::{DrawingState, SelectionMode};
 use crate::screen::main_scene_input::{CellSelection, CellSelectionState, CellSelectionType};
-use crate::world::map::cell_envelope::{is_horizontally_inside, Envelope};
-use crate::world::map::{CellCubeIterator, CellIndex};
+use crate::world::map::cell_envelope::{is_horizontally_inside, is_inside, Envelope};
+use crate::world::map::{CellCubeIterator, CellIndex, Map, TileType};
+use crate::world::networks::network::neighbours;
+use crate::world::networks::Networks;
+use crate::world::World;
 use std::collections::HashSet;
 
+impl SelectionMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SelectionMode::Box => "Box",
+            SelectionMode::SameType => "Same type",
+            SelectionMode::Column => "Column",
+            SelectionMode::FloodFill => "Flood fill",
+            SelectionMode::Network => "Network",
+        }
+    }
+    pub fn next(&self) -> Self {
+        match self {
+            SelectionMode::Box => SelectionMode::SameType,
+            SelectionMode::SameType => SelectionMode::Column,
+            SelectionMode::Column => SelectionMode::FloodFill,
+            SelectionMode::FloodFill => SelectionMode::Network,
+            SelectionMode::Network => SelectionMode::Box,
+        }
+    }
+}
+
 impl DrawingState {
-    pub fn maybe_select_cells_from_pixels(&mut self, cell_selection: &CellSelection) {
+    pub fn maybe_select_cells_from_pixels(
+        &mut self,
+        cell_selection: &CellSelection,
+        world: &World,
+    ) {
         cell_selection.selection.map(|selection| {
-            self.maybe_select_cells(
+            let start = self.maybe_select_cells(
                 selection.start,
                 selection.end,
                 cell_selection.state,
                 cell_selection.selection_type,
             );
+            if self.selection_mode != SelectionMode::Box {
+                let mode = self.selection_mode;
+                let shown_cube = Envelope {
+                    min_cell: self.min_cell,
+                    max_cell: self.max_cell,
+                };
+                self.cell_index_set.refine_in_progress(|box_cells| {
+                    refine_selection(
+                        mode,
+                        box_cells,
+                        start,
+                        &shown_cube,
+                        &world.map,
+                        &world.networks,
+                    )
+                });
+            }
         });
     }
 
+    /// returns the start cell, which might have been moved to the height where the selection
+    /// started
     fn maybe_select_cells(
         &mut self,
         mut start: CellIndex,
         end: CellIndex,
         selection_state: CellSelectionState,
         selection_type: CellSelectionType,
-    ) {
+    ) -> CellIndex {
         if self.cell_index_set.highlighted_cells_in_progress_type != selection_type
             || selection_state == CellSelectionState::Started
         {
@@ -32,16 +79,22 @@ impl DrawingState {
             min_cell: self.min_cell,
             max_cell: self.max_cell,
         };
-        if selection_state == CellSelectionState::Started {
-            self.highlight_start_height = Some(self.max_cell.y);
-        }
-        if let Some(start_height) = self.highlight_start_height {
-            start.y = start_height;
+        if self.view_mode.is_cross_section() {
+            // the slice shows several heights at once, so the selection can span them
+            self.highlight_start_height = None;
         } else {
-            self.highlight_start_height = Some(start.y);
+            if selection_state == CellSelectionState::Started {
+                self.highlight_start_height = Some(self.max_cell.y);
+            }
+            if let Some(start_height) = self.highlight_start_height {
+                start.y = start_height;
+            } else {
+                self.highlight_start_height = Some(start.y);
+            }
         }
         self.cell_index_set
             .highlight_cells(start, end, shown_cube, selection_type);
+        start
     }
 
     fn finish_selection(&mut self) {
@@ -66,6 +119,69 @@ fn highlight_cells(
     }
 }
 
+/// Turns the box dragged with the mouse into the cells that the selection mode selects.
+fn refine_selection(
+    mode: SelectionMode,
+    box_cells: HashSet<CellIndex>,
+    start: CellIndex,
+    shown_cube: &Envelope,
+    map: &Map,
+    networks: &Networks,
+) -> HashSet<CellIndex> {
+    let start_tile = tile_at(map, start);
+    match mode {
+        SelectionMode::Box => box_cells,
+        SelectionMode::SameType => box_cells
+            .into_iter()
+            .filter(|cell_index| tile_at(map, *cell_index) == start_tile)
+            .collect(),
+        SelectionMode::Column => {
+            let mut column = HashSet::new();
+            for cell_index in box_cells {
+                for y in shown_cube.min_cell.y..=cell_index.y {
+                    column.insert(CellIndex::new(cell_index.x, y, cell_index.z));
+                }
+            }
+            column
+        }
+        SelectionMode::FloodFill => {
+            let mut filled = HashSet::new();
+            if start_tile.is_none() || !is_inside(&start, shown_cube) {
+                return filled;
+            }
+            let mut pending = vec![start];
+            filled.insert(start);
+            while let Some(cell_index) = pending.pop() {
+                for neighbour in neighbours(cell_index) {
+                    if is_inside(&neighbour, shown_cube)
+                        && !filled.contains(&neighbour)
+                        && tile_at(map, neighbour) == start_tile
+                    {
+                        filled.insert(neighbour);
+                        pending.push(neighbour);
+                    }
+                }
+            }
+            filled
+        }
+        SelectionMode::Network => match networks.get_network_index(start) {
+            Some(i_network) => networks
+                .iter()
+                .nth(i_network)
+                .unwrap()
+                .nodes
+                .keys()
+                .cloned()
+                .collect(),
+            None => HashSet::new(),
+        },
+    }
+}
+
+fn tile_at(map: &Map, cell_index: CellIndex) -> Option<TileType> {
+    map.get_cell_optional(cell_index).map(|cell| cell.tile_type)
+}
+
 // #[derive(Clone)]
 pub struct CellIndexSet {
     highlighted_cells_in_progress: HashSet<CellIndex>,
@@ -102,6 +218,16 @@ impl CellIndexSet {
         self.merged_cached = self.merge_consolidated_and_in_progress();
     }
 
+    /// Replaces the cells being selected, to apply a `SelectionMode` after `highlight_cells`.
+    pub fn refine_in_progress(
+        &mut self,
+        refine: impl FnOnce(HashSet<CellIndex>) -> HashSet<CellIndex>,
+    ) {
+        let in_progress = std::mem::take(&mut self.highlighted_cells_in_progress);
+        self.highlighted_cells_in_progress = refine(in_progress);
+        self.merged_cached = self.merge_consolidated_and_in_progress();
+    }
+
     fn merged(&self) -> &HashSet<CellIndex> {
         &self.merged_cached
     }
@@ -152,8 +278,10 @@ pub fn merge_consolidated_and_in_progress(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::screen::drawing_state::ViewMode;
     use crate::screen::main_scene_input::CellSelectionState::*;
     use crate::screen::main_scene_input::CellSelectionType::*;
+    use crate::world::map::Cell;
     use crate::world::robots::up;
 
     #[test]
@@ -287,4 +415,91 @@ mod tests {
         drawing.maybe_select_cells(t.start, t.small_end, Finished, Remove);
         assert_eq!(drawing.highlighted_cells().len(), 8);
     }
+
+    fn refine_test_map() -> (Map, Envelope) {
+        let mut tiles = Vec::new();
+        for cell_index in CellCubeIterator::new(CellIndex::new(0, 0, 0), CellIndex::new(3, 1, 3)) {
+            tiles.push((cell_index, TileType::WallRock));
+        }
+        tiles.push((CellIndex::new(0, 1, 0), TileType::Wire));
+        tiles.push((CellIndex::new(1, 1, 0), TileType::Wire));
+        tiles.push((CellIndex::new(3, 1, 0), TileType::Wire));
+        let map = Map::_new_from_tiles(Cell::new(TileType::Air), tiles);
+        let shown_cube = Envelope {
+            min_cell: CellIndex::new(0, 0, 0),
+            max_cell: CellIndex::new(3, 1, 3),
+        };
+        (map, shown_cube)
+    }
+
+    fn refine(mode: SelectionMode, start: CellIndex, end: CellIndex) -> HashSet<CellIndex> {
+        let (map, shown_cube) = refine_test_map();
+        let mut networks = Networks::new(CellIndex::new(0, 1, 0));
+        networks.add(CellIndex::new(1, 1, 0), TileType::Wire, TileType::Air);
+        let box_cells = CellCubeIterator::new_from_mixed(start, end).collect();
+        refine_selection(mode, box_cells, start, &shown_cube, &map, &networks)
+    }
+
+    #[test]
+    fn same_type_selection() {
+        let selected = refine(
+            SelectionMode::SameType,
+            CellIndex::new(0, 1, 0),
+            CellIndex::new(3, 1, 1),
+        );
+        assert_eq!(
+            selected,
+            HashSet::from([
+                CellIndex::new(0, 1, 0),
+                CellIndex::new(1, 1, 0),
+                CellIndex::new(3, 1, 0)
+            ])
+        );
+    }
+
+    #[test]
+    fn flood_fill_selection() {
+        let start = CellIndex::new(0, 1, 0);
+        let selected = refine(SelectionMode::FloodFill, start, start);
+        assert_eq!(
+            selected,
+            HashSet::from([CellIndex::new(0, 1, 0), CellIndex::new(1, 1, 0)])
+        );
+    }
+
+    #[test]
+    fn column_selection() {
+        let start = CellIndex::new(2, 1, 2);
+        let selected = refine(SelectionMode::Column, start, start);
+        assert_eq!(
+            selected,
+            HashSet::from([CellIndex::new(2, 0, 2), CellIndex::new(2, 1, 2)])
+        );
+    }
+
+    #[test]
+    fn network_selection() {
+        let selected = refine(
+            SelectionMode::Network,
+            CellIndex::new(1, 1, 0),
+            CellIndex::new(1, 1, 0),
+        );
+        assert_eq!(
+            selected,
+            HashSet::from([CellIndex::new(0, 1, 0), CellIndex::new(1, 1, 0)])
+        );
+    }
+
+    #[test]
+    fn cross_section_selection_spans_heights() {
+        let mut drawing = DrawingState::new();
+        drawing.view_mode = ViewMode::CrossSectionZ;
+        let start = drawing.cross_section_cell(0, -3);
+        let end = drawing.cross_section_cell(2, 0);
+
+        drawing.maybe_select_cells(start, end, Started, Exclusive);
+        assert_eq!(drawing.highlighted_cells().len(), 12);
+        assert!(drawing.highlighted_cells().contains(&start));
+        assert!(drawing.highlighted_cells().contains(&end));
+    }
 }
@@ -9,12 +9,18 @@ use mq_basics::Color;
 use crate::screen::coords::cell_pixel::{clicked_cell, pixel_to_subcell_offset};
 use crate::screen::drawer_trait::DrawerTrait;
 use crate::screen::drawing_state::DrawingState;
+use crate::screen::gui::panels::confirmation::draw_confirmation;
+use crate::screen::gui::panels::console::{draw_console, type_in_console};
+use crate::screen::gui::panels::event_log::draw_event_log;
 use crate::screen::gui::panels::initial_dialog::draw_initial_dialog;
+use crate::screen::gui::panels::keybindings::draw_keybindings;
+use crate::screen::gui::panels::profiler::draw_profiler;
 use crate::screen::gui::panels::top_bar::draw_top_bar;
 use crate::screen::gui::panels::{
     cell_info::draw_cell_info, draw_available_transformations::show_available_transformations,
     game_finished::draw_game_finished, task_queue::draw_robot_queue,
 };
+use crate::screen::keybindings::Keybindings;
 use crate::screen::main_scene_input::{
     CellIndexSelection, CellSelection, Input, PixelCellSelection,
 };
@@ -48,15 +54,48 @@ impl Gui {
         drawer: &mut dyn DrawerTrait,
         world: &World,
         drawing: &mut DrawingState, // TODO: make const by add top_bar_showing to GuiActions
+        keybindings: &Keybindings,
     ) -> GuiActions {
+        let mut rebind = None;
+        let mut console_commands = std::mem::take(&mut drawing.console_queue);
+        let input = if let Some(action) = drawing.rebinding {
+            if let Some(key) = input.pressed_key {
+                rebind = Some((action, key));
+                drawing.rebinding = None;
+            }
+            input.without_key_actions()
+        } else if drawing.console_open {
+            if let Some(key) = input.pressed_key {
+                console_commands.extend(type_in_console(drawing, key, keybindings));
+            }
+            input.without_key_actions()
+        } else {
+            if input.toggle_console {
+                drawing.console_open = true;
+            }
+            input
+        };
         let mut gui_actions = GuiActions::default();
-        drawer.ui_run(&mut |drawer| gui_actions = filter_input(input, world, drawing, drawer));
+        drawer.ui_run(&mut |drawer| {
+            gui_actions = filter_input(input, world, drawing, drawer, keybindings)
+        });
+        gui_actions.rebind = rebind;
+        gui_actions.console_commands = console_commands;
         if input.reload_ui_skin {
             set_skin(drawer);
         }
         if input.go_to_ship {
             drawing.re_center(world.map.get_ship_position().unwrap());
         }
+        if input.toggle_cross_section {
+            drawing.cycle_view_mode();
+        }
+        if input.cycle_overlay {
+            drawing.overlay = drawing.overlay.next();
+        }
+        if input.cycle_selection_mode {
+            drawing.selection_mode = drawing.selection_mode.next();
+        }
         gui_actions
     }
 }
@@ -66,15 +105,21 @@ fn filter_input(
     world: &World,
     drawing: &mut DrawingState,
     drawer: &mut dyn DrawerTrait,
+    keybindings: &Keybindings,
 ) -> GuiActions {
     let unhandled_input = new_gui_from_input(input, drawer, drawing);
+    let unhandled_input = draw_confirmation(drawer, drawing, unhandled_input);
     let unhandled_input = draw_initial_dialog(drawer, world, unhandled_input);
     let unhandled_input = draw_game_finished(drawer, world, unhandled_input);
     let unhandled_input = show_available_transformations(drawer, world, unhandled_input, drawing);
 
     let unhandled_input = draw_robot_queue(drawer, world, unhandled_input, drawing);
-    let unhandled_input = draw_top_bar(drawer, world, drawing, unhandled_input);
+    let unhandled_input = draw_event_log(drawer, world, drawing, unhandled_input);
+    let unhandled_input = draw_top_bar(drawer, world, drawing, unhandled_input, keybindings);
+    let unhandled_input = draw_keybindings(drawer, drawing, unhandled_input, keybindings);
     let unhandled_input = draw_cell_info(drawer, world, drawing, unhandled_input);
+    let unhandled_input = draw_profiler(drawer, world, unhandled_input);
+    let unhandled_input = draw_console(drawer, world, drawing, unhandled_input);
     unhandled_input
 }
 
@@ -101,11 +146,18 @@ fn new_gui_from_input(
         selected_cell_transformation: Option::None,
         go_to_robot: Option::None,
         cancel_task: Option::None,
+        undo: input.undo,
+        redo: input.redo,
         next_game_goal_state: Option::None,
         regenerate_map: input.regenerate_map,
         toggle_profiling: input.toggle_profiling,
+        save_profile_trace: false,
         toggle_fluids: input.toggle_fluids,
         single_fluid: input.single_fluid,
+        toggle_pause: input.toggle_pause,
+        step_tick: input.step_tick,
+        set_speed: Option::None,
+        change_speed_rel: input.change_speed_rel,
         reset_quantities: input.reset_quantities,
         quit: input.quit,
         change_height_rel: input.change_height_rel,
@@ -114,6 +166,8 @@ fn new_gui_from_input(
             drawing.zoom,
         ),
         zoom_change: input.zoom_change,
+        rebind: Option::None,
+        console_commands: Vec::new(),
     };
     unhandled_input
 }
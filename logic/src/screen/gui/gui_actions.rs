@@ -1,24 +1,38 @@
 use crate::scene::GameLoopState;
 use crate::screen::drawing_state::SubCellIndex;
+use crate::screen::keybindings::Action;
 use crate::screen::main_scene_input::{CellSelection, ZoomChange};
+use crate::world::console::ConsoleCommand;
+use crate::world::game_state::GameSpeed;
 use crate::world::map::CellIndex;
 use crate::world::{GameGoalState, TransformationTask};
+use mq_basics::KeyCode;
 
 pub struct GuiActions {
     pub cell_selection: CellSelection,
     pub selected_cell_transformation: Option<TransformationTask>,
     pub go_to_robot: Option<CellIndex>,
     pub cancel_task: Option<usize>,
+    pub undo: bool,
+    pub redo: bool,
     pub next_game_goal_state: Option<GameGoalState>,
     pub regenerate_map: bool,
     pub toggle_profiling: bool,
+    pub save_profile_trace: bool,
     pub toggle_fluids: bool,
     pub single_fluid: bool,
+    pub toggle_pause: bool,
+    pub step_tick: bool,
+    pub set_speed: Option<GameSpeed>,
+    pub change_speed_rel: i32,
     pub reset_quantities: bool,
     pub quit: bool,
     pub change_height_rel: i32,
     pub move_map_horizontally_diff: SubCellIndex,
     pub zoom_change: ZoomChange,
+    pub rebind: Option<(Action, KeyCode)>,
+    /// typed in the console or read from the startup script
+    pub console_commands: Vec<ConsoleCommand>,
 }
 
 impl GuiActions {
@@ -38,16 +52,25 @@ impl Default for GuiActions {
             selected_cell_transformation: None,
             go_to_robot: None,
             cancel_task: None,
+            undo: false,
+            redo: false,
             next_game_goal_state: None,
             regenerate_map: false,
             toggle_profiling: false,
+            save_profile_trace: false,
             toggle_fluids: false,
             single_fluid: false,
+            toggle_pause: false,
+            step_tick: false,
+            set_speed: None,
+            change_speed_rel: 0,
             reset_quantities: false,
             quit: false,
             change_height_rel: 0,
             move_map_horizontally_diff: Default::default(),
             zoom_change: ZoomChange::ZoomIn,
+            rebind: None,
+            console_commands: Vec::new(),
         }
     }
 }
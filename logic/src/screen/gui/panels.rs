@@ -1,7 +1,12 @@
 pub mod cell_info;
+pub mod confirmation;
+pub mod console;
 pub mod draw_available_transformations;
+pub mod event_log;
 pub mod game_finished;
 pub mod initial_dialog;
+pub mod keybindings;
+pub mod profiler;
 pub mod task_queue;
 pub mod top_bar;
 
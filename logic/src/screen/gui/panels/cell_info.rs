@@ -5,9 +5,10 @@ use crate::screen::gui::panels::longest;
 use crate::screen::gui::panels::top_bar::TOP_BAR_HEIGHT;
 use crate::screen::gui::{GuiActions, FONT_SIZE, MARGIN};
 use crate::screen::main_scene_input::CellSelection;
-use crate::world::map::cell::is_networkable;
+use crate::world::map::cell::{is_networkable, is_soil};
+use crate::world::map::tile_registry::{tile_properties, NetworkRole};
 use crate::world::map::{is_liquid_or_air, is_walkable_horizontal, Cell, CellIndex, TileType};
-use crate::world::networks::network::{MAX_STORAGE_PER_STORAGE_MACHINE, POWER_PER_SOLAR_PANEL};
+use crate::world::networks::network::material_composition;
 use crate::world::networks::Networks;
 use crate::world::World;
 
@@ -51,31 +52,11 @@ pub fn draw_cell_info(
 
 fn cell_to_str(cell: &Cell, pos: CellIndex, networks: &Networks) -> Vec<String> {
     let tile = cell.tile_type;
-    let basic_name = match tile {
-        TileType::Unset => "Unset cell",
-        TileType::WallRock => "Wall of rock",
-        TileType::WallDirt => "Wall of dirt",
-        TileType::FloorRock => "Floor of rock",
-        TileType::FloorDirt => "Floor of Dirt",
-        TileType::Stairs => "Stairs",
-        TileType::Air => {
-            if cell.pressure <= 0 {
-                "Air"
-            } else {
-                "Water"
-            }
-        }
-        TileType::Wire => "Wire",
-        TileType::MachineAssembler => "Assembler machine",
-        TileType::MachineAirCleaner => "Air cleaner machine",
-        TileType::MachineDrill => "Drill machine",
-        TileType::MachineSolarPanel => "Solar panel machine",
-        TileType::MachineShip => "Spaceship",
-        TileType::MachineStorage => "Storage machine",
-        TileType::TreeHealthy => "Tree (Healthy)",
-        TileType::TreeSparse => "Tree (Sparse)",
-        TileType::TreeDying => "Tree (Dying)",
-        TileType::TreeDead => "Tree (Dead)",
+    let properties = tile_properties(tile);
+    let basic_name = if tile == TileType::Air && cell.pressure > 0 {
+        "Water"
+    } else {
+        properties.name.as_str()
     };
     let mut description = vec![basic_name.to_string()];
     if tile == TileType::MachineShip {
@@ -95,28 +76,32 @@ fn cell_to_str(cell: &Cell, pos: CellIndex, networks: &Networks) -> Vec<String>
             //     "  pos: ({} {} {})",
             //     node.position.x, node.position.y, node.position.z
             // ));
-            if cell.tile_type == TileType::MachineStorage {
-                description.push(format!(
-                    "    +{} storage capacity",
-                    format_grams(MAX_STORAGE_PER_STORAGE_MACHINE)
-                ));
-            } else if cell.tile_type == TileType::MachineSolarPanel {
-                description.push(format!(
-                    "    +{} power",
-                    format_watts(POWER_PER_SOLAR_PANEL)
-                ));
-            } else if cell.tile_type == TileType::MachineAirCleaner {
-                description.push(format!(
-                    "    -{} power",
-                    format_watts(POWER_PER_SOLAR_PANEL)
-                ));
-            } else if cell.tile_type == TileType::MachineShip {
+            let storage = properties.storage.unwrap_or(0.0);
+            if storage > 0.0 {
+                description.push(format!("    +{} storage capacity", format_grams(storage)));
+            }
+            if properties.power > 0.0 {
+                description.push(format!("    +{} power", format_watts(properties.power)));
+            } else if properties.power < 0.0 {
+                description.push(format!("    -{} power", format_watts(-properties.power)));
+            }
+            if properties.network == NetworkRole::Ship {
                 description.push("    Able to construct other machines".to_string());
             }
         }
     } else if !networks.is_adjacent_to_ship_network(pos) {
         description.push("  Networking: unreachable".to_string());
     }
+    if is_soil(tile) && properties.material.is_some() {
+        description.push("  Digging it yields:".to_string());
+        description.push(format!(
+            "    +{} material",
+            format_grams(material_composition(tile))
+        ));
+        if properties.water > 0 {
+            description.push("    Water that floods the hole".to_string());
+        }
+    }
     // TODO: print if a machine is working or not?
     // TODO: print contents of wires?
     description
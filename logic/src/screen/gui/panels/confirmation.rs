@@ -0,0 +1,78 @@
+use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::drawing_state::DrawingState;
+use crate::screen::gui::panels::top_bar::measure_button;
+use crate::screen::gui::{GuiActions, FONT_SIZE, MARGIN};
+use crate::screen::keybindings::Action;
+use crate::screen::main_scene_input::CellSelection;
+use mq_basics::Vec2;
+
+/// Regenerating the map or resetting the quantities can not be undone, so the key only opens
+/// a pop-up, and the action is done if the user confirms it. The quit key cancels.
+pub fn draw_confirmation(
+    drawer: &mut dyn DrawerTrait,
+    drawing: &mut DrawingState,
+    gui_actions: GuiActions,
+) -> GuiActions {
+    let mut regenerate_map = false;
+    let mut reset_quantities = false;
+    let mut quit = gui_actions.quit;
+    let mut cell_selection = gui_actions.cell_selection;
+    if gui_actions.regenerate_map {
+        drawing.confirming = Some(Action::RegenerateMap);
+    } else if gui_actions.reset_quantities {
+        drawing.confirming = Some(Action::ResetQuantities);
+    }
+    if let Some(action) = drawing.confirming {
+        cell_selection = CellSelection::no_selection();
+        if quit {
+            quit = false;
+            drawing.confirming = None;
+        } else {
+            let text = confirmation_text(action);
+            let text_size = drawer.ui_measure_text(text, FONT_SIZE);
+            let button_size = measure_button(drawer, "Confirm");
+            let panel_size = Vec2::new(
+                text_size.x + MARGIN * 4.0,
+                FONT_SIZE * 2.0 + text_size.y + button_size.y + MARGIN * 5.0,
+            );
+            let mut confirmed = false;
+            let mut cancelled = false;
+            drawer.ui_named_group(
+                action.description(),
+                drawer.screen_width() * 0.5 - panel_size.x * 0.5,
+                drawer.screen_height() * 0.5 - panel_size.y * 0.5,
+                panel_size.x,
+                panel_size.y,
+                &mut |drawer| {
+                    drawer.ui_text(text);
+                    drawer.ui_same_line(&mut |drawer| {
+                        confirmed = drawer.ui_button("Confirm").is_clicked();
+                        cancelled = drawer.ui_button("Cancel").is_clicked();
+                    });
+                },
+            );
+            if confirmed {
+                regenerate_map = action == Action::RegenerateMap;
+                reset_quantities = action == Action::ResetQuantities;
+            }
+            if confirmed || cancelled {
+                drawing.confirming = None;
+            }
+        }
+    }
+    GuiActions {
+        cell_selection,
+        regenerate_map,
+        reset_quantities,
+        quit,
+        ..gui_actions
+    }
+}
+
+fn confirmation_text(action: Action) -> &'static str {
+    match action {
+        Action::RegenerateMap => "This will delete all your constructions. Are you sure?",
+        Action::ResetQuantities => "This will reset the timer and the production. Are you sure?",
+        _ => "Are you sure?",
+    }
+}
@@ -0,0 +1,170 @@
+use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::drawing_state::DrawingState;
+use crate::screen::gui::panels::longest;
+use crate::screen::gui::panels::task_queue::task_card_height;
+use crate::screen::gui::{GuiActions, FONT_SIZE, MARGIN};
+use crate::screen::keybindings::{Action, Keybindings};
+use crate::screen::main_scene_input::CellSelection;
+use crate::world::console::ConsoleCommand;
+use crate::world::World;
+use mq_basics::{KeyCode, Vec2};
+
+const VISIBLE_LINES: usize = 12;
+const MIN_LINE_WIDTH: &str = "tile X Y Z TILE: set the tile of a cell, keeping the networks";
+
+/// Shows the output of the last commands and the command being typed, at the bottom center.
+pub fn draw_console(
+    drawer: &mut dyn DrawerTrait,
+    world: &World,
+    drawing: &DrawingState,
+    gui_actions: GuiActions,
+) -> GuiActions {
+    if !drawing.console_open {
+        return gui_actions;
+    }
+    let mut cell_selection = gui_actions.cell_selection;
+    let mut lines = world
+        .console
+        .iter_newest()
+        .take(VISIBLE_LINES)
+        .cloned()
+        .collect::<Vec<_>>();
+    lines.reverse();
+    lines.push(format!("> {}_", drawing.console_text));
+    if let Some(error) = &drawing.console_error {
+        lines.push(error.clone());
+    }
+    let min_line_width = MIN_LINE_WIDTH.to_string();
+    let longest_line = longest(lines.iter(), &min_line_width);
+    let text_width = drawer.ui_measure_text(longest_line, FONT_SIZE).x;
+    let panel_size = Vec2::new(
+        text_width + MARGIN * 2.0,
+        FONT_SIZE * 2.0 + (VISIBLE_LINES + 2) as f32 * FONT_SIZE * 1.2 + MARGIN * 2.0,
+    );
+    let panel = drawer.ui_named_group(
+        "Console",
+        (drawer.screen_width() - panel_size.x) * 0.5,
+        drawer.screen_height() - task_card_height() - panel_size.y - MARGIN * 2.0,
+        panel_size.x,
+        panel_size.y,
+        &mut |drawer| {
+            for line in &lines {
+                drawer.ui_text(line);
+            }
+        },
+    );
+    if panel.is_hovered_or_clicked() {
+        cell_selection = CellSelection::no_selection();
+    }
+    GuiActions {
+        cell_selection,
+        ..gui_actions
+    }
+}
+
+/// Edits the command being typed. Returns the command if Enter was pressed and it was valid.
+pub fn type_in_console(
+    drawing: &mut DrawingState,
+    key: KeyCode,
+    keybindings: &Keybindings,
+) -> Option<ConsoleCommand> {
+    if key == KeyCode::Escape || keybindings.keys(Action::ToggleConsole).contains(&key) {
+        drawing.console_open = false;
+    } else if key == KeyCode::Enter || key == KeyCode::KpEnter {
+        match ConsoleCommand::parse(&drawing.console_text) {
+            Ok(command) => {
+                drawing.console_text.clear();
+                drawing.console_error = None;
+                return Some(command);
+            }
+            Err(error) => drawing.console_error = Some(format!("Error: {}", error)),
+        }
+    } else if key == KeyCode::Backspace {
+        drawing.console_text.pop();
+    } else if let Some(character) = key_char(key) {
+        drawing.console_text.push(character);
+    }
+    None
+}
+
+/// Only the characters needed by the commands can be typed.
+fn key_char(key: KeyCode) -> Option<char> {
+    let character = match key {
+        KeyCode::Space => ' ',
+        KeyCode::Minus => '-',
+        KeyCode::Period => '.',
+        KeyCode::Key0 => '0',
+        KeyCode::Key1 => '1',
+        KeyCode::Key2 => '2',
+        KeyCode::Key3 => '3',
+        KeyCode::Key4 => '4',
+        KeyCode::Key5 => '5',
+        KeyCode::Key6 => '6',
+        KeyCode::Key7 => '7',
+        KeyCode::Key8 => '8',
+        KeyCode::Key9 => '9',
+        _ => {
+            // the letters are the only keys with a single character name
+            let name = format!("{:?}", key);
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(letter), None) if letter.is_ascii_alphabetic() => letter.to_ascii_lowercase(),
+                _ => return None,
+            }
+        }
+    };
+    Some(character)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::CellIndex;
+
+    #[test]
+    fn test_type_command() {
+        let keybindings = Keybindings::new();
+        let mut drawing = DrawingState::new();
+        drawing.console_open = true;
+        let keys = [
+            KeyCode::R,
+            KeyCode::O,
+            KeyCode::B,
+            KeyCode::O,
+            KeyCode::T,
+            KeyCode::Space,
+            KeyCode::Key1,
+            KeyCode::Space,
+            KeyCode::Minus,
+            KeyCode::Key2,
+            KeyCode::Space,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Backspace,
+        ];
+        for key in keys {
+            assert_eq!(type_in_console(&mut drawing, key, &keybindings), None);
+        }
+        assert_eq!(drawing.console_text, "robot 1 -2 3");
+        let command = type_in_console(&mut drawing, KeyCode::Enter, &keybindings);
+        assert_eq!(
+            command,
+            Some(ConsoleCommand::SpawnRobot(CellIndex::new(1, -2, 3)))
+        );
+        assert_eq!(drawing.console_text, "");
+
+        type_in_console(&mut drawing, KeyCode::X, &keybindings);
+        assert_eq!(
+            type_in_console(&mut drawing, KeyCode::Enter, &keybindings),
+            None
+        );
+        assert!(drawing.console_error.is_some());
+        assert_eq!(
+            drawing.console_text, "x",
+            "invalid commands are kept to fix them"
+        );
+
+        type_in_console(&mut drawing, KeyCode::GraveAccent, &keybindings);
+        assert!(!drawing.console_open);
+    }
+}
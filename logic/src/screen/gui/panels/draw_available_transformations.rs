@@ -1,11 +1,15 @@
 use crate::screen::drawer_trait::{DrawerTrait, Interaction};
 use crate::screen::drawing_state::DrawingState;
+use crate::screen::gui::format_units::{format_grams, format_watts};
 use crate::screen::gui::gui_actions::GuiActions;
 use crate::screen::gui::panels::longest;
+use crate::screen::gui::panels::task_queue::reason_str;
 use crate::screen::gui::panels::top_bar::TOP_BAR_HEIGHT;
 use crate::screen::gui::{FONT_SIZE, MARGIN};
 use crate::screen::main_scene_input::CellSelection;
-use crate::world::map::transform_cells::allowed_transformations;
+use crate::world::event_log::sorted_reasons;
+use crate::world::map::tile_registry::tile_properties;
+use crate::world::map::transformation_preview::TransformationPreview;
 use crate::world::map::TileType;
 use crate::world::{TransformationTask, World};
 use mq_basics::Rect;
@@ -14,14 +18,15 @@ pub fn show_available_transformations(
     drawer: &mut dyn DrawerTrait,
     world: &World,
     unhandled_input: GuiActions,
-    drawing: &DrawingState,
+    drawing: &mut DrawingState,
 ) -> GuiActions {
+    drawing.preview = None;
     let mut transformation_clicked = Option::None;
     let mut cell_selection = unhandled_input.cell_selection;
     let highlighted_cells = drawing.highlighted_cells();
     if highlighted_cells.len() > 0 {
         let mut transformations =
-            allowed_transformations(&highlighted_cells.highlighted_cells(), &world.map);
+            world.allowed_transformations(&highlighted_cells.highlighted_cells());
         transformations.sort_by(|t_1, t_2| {
             to_action_str(t_1.new_tile_type).cmp(to_action_str(t_2.new_tile_type))
         });
@@ -65,7 +70,7 @@ pub fn show_available_transformations(
                             transformation_clicked = Option::Some(transformation_task);
                         }
                         Interaction::Hovered => {
-                            hovered_opt = Some(transformation.new_tile_type);
+                            hovered_opt = Some(*transformation);
                         }
                         Interaction::None => {}
                     }
@@ -76,27 +81,37 @@ pub fn show_available_transformations(
             },
         );
         if let Some(hovered) = hovered_opt {
-            if let Some(tooltip) = to_tooltip_str(hovered) {
-                let title = to_action_str(hovered);
-                let longest_line = longest(tooltip.iter(), &title);
-                let max_line_width = drawer.ui_measure_text(longest_line, FONT_SIZE).x;
-                let pad_width = panel_margin + 1.0 * FONT_SIZE;
-                let panel_width = max_line_width + 2.0 * pad_width;
-                let line_height = FONT_SIZE * 1.5;
-                let panel_height = (tooltip.len() + 2).max(10) as f32 * line_height;
-                drawer.ui_named_group(
-                    title,
-                    panel.x + panel.w + panel_margin,
-                    panel.y,
-                    panel_width,
-                    panel_height,
-                    &mut |drawer| {
-                        for line in &tooltip {
-                            drawer.ui_text(line);
-                        }
-                    },
-                );
+            let preview = TransformationPreview::new(
+                highlighted_cells.highlighted_cells(),
+                hovered,
+                &world.map,
+                &world.networks,
+            );
+            let mut tooltip = preview_lines(&preview, highlighted_cells.len());
+            if let Some(description) = to_tooltip_str(hovered.new_tile_type) {
+                tooltip.push("".to_string());
+                tooltip.extend(description.iter().cloned());
             }
+            let title = to_action_str(hovered.new_tile_type).to_string();
+            let longest_line = longest(tooltip.iter(), &title);
+            let max_line_width = drawer.ui_measure_text(longest_line, FONT_SIZE).x;
+            let pad_width = panel_margin + 1.0 * FONT_SIZE;
+            let panel_width = max_line_width + 2.0 * pad_width;
+            let line_height = FONT_SIZE * 1.5;
+            let panel_height = (tooltip.len() + 2).max(10) as f32 * line_height;
+            drawer.ui_named_group(
+                &title,
+                panel.x + panel.w + panel_margin,
+                panel.y,
+                panel_width,
+                panel_height,
+                &mut |drawer| {
+                    for line in &tooltip {
+                        drawer.ui_text(line);
+                    }
+                },
+            );
+            drawing.preview = Some(preview);
         }
         if transformations_panel.is_hovered_or_clicked() || transformation_clicked.is_some() {
             cell_selection = CellSelection::no_selection();
@@ -116,88 +131,50 @@ pub fn show_available_transformations(
     }
 }
 
-pub fn to_action_str(tile: TileType) -> &'static str {
-    match tile {
-        TileType::Unset => {
-            panic!()
+/// Material and power that the transformation would change, and why some cells would be blocked.
+fn preview_lines(preview: &TransformationPreview, cells_count: usize) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "Material: {}",
+            signed(preview.material_change, format_grams)
+        ),
+        format!("Power: {}", signed(preview.power_change, format_watts)),
+    ];
+    if preview.forbidden.len() > 0 {
+        lines.push(format!(
+            "Blocked in {} of {} cells because:",
+            preview.forbidden.len(),
+            cells_count
+        ));
+        let mut reasons = sorted_reasons(preview.forbidden.values());
+        reasons.dedup();
+        for reason in reasons {
+            lines.push(format!("  {}", reason_str(&reason)));
         }
-        TileType::WallRock => "Build rock wall",
-        TileType::WallDirt => "Build dirt wall",
-        TileType::FloorRock => "Flatten rock",
-        TileType::FloorDirt => "Flatten dirt",
-        TileType::Stairs => "Build stairs",
-        TileType::Air => "Remove cell",
-        TileType::Wire => "Build plumbing",
-        TileType::MachineAssembler => "Build assembler",
-        TileType::MachineAirCleaner => "Build air cleaner",
-        TileType::MachineDrill => "Build drill",
-        TileType::MachineSolarPanel => "Build solar panel",
-        TileType::MachineShip => "Build space ship",
-        TileType::MachineStorage => "Build storage",
-        // TileType::DirtyWaterSurface => "Dirty water surface",
-        // TileType::CleanWaterSurface => "Clean water surface",
-        // TileType::DirtyWaterWall => "Dirty water wall",
-        // TileType::CleanWaterWall => "Clean water wall",
-        TileType::TreeHealthy => "Plant tree",
-        TileType::TreeSparse => "Plant sparse tree",
-        TileType::TreeDying => "Plant dying tree",
-        TileType::TreeDead => "Kill tree",
     }
+    lines
 }
 
-fn to_tooltip_str(tile: TileType) -> Option<Vec<&'static str>> {
-    match tile {
-        TileType::Unset => {
-            panic!()
-        }
-        TileType::WallRock => None,
-        TileType::WallDirt => None,
-        TileType::FloorRock => Some(vec![
-            "- Makes a rock floor",
-            "",
-            "- Disassembles machines",
-            "",
-            "- Uproots plants",
-        ]),
-        TileType::FloorDirt => None,
-        TileType::Stairs => Some(vec!["- Gives access to", "  underground levels"]),
-        TileType::Air => None,
-        TileType::Wire => Some(vec![
-            "- Connects machines to",
-            "  be part of the",
-            "  same network",
-        ]),
-        TileType::MachineAssembler => None,
-        TileType::MachineAirCleaner => Some(vec!["- Consumes 1KW"]),
-        TileType::MachineDrill => None,
-        TileType::MachineSolarPanel => Some(vec![
-            "- Produces 1KW",
-            "",
-            "- Can not be built",
-            "  underground",
-        ]),
-        TileType::MachineShip => None,
-        TileType::MachineStorage => Some(vec![
-            "- Increases storage capacity",
-            "  by 9.9 tonnes (9.9 Mg)",
-            "",
-            "- Increases available material",
-            "  for construction by the",
-            "  same amount",
-        ]),
-        // TileType::DirtyWaterSurface => None,
-        // TileType::CleanWaterSurface => None,
-        // TileType::DirtyWaterWall => None,
-        // TileType::CleanWaterWall => None,
-        TileType::TreeHealthy => Some(vec![
-            "- Toxic air will kill",
-            "  the tree.",
-            // "",
-            // "- Darkness will kill",
-            // "  the tree",
-        ]),
-        TileType::TreeSparse => None,
-        TileType::TreeDying => None,
-        TileType::TreeDead => None,
+fn signed(quantity: f64, format: fn(f64) -> String) -> String {
+    if quantity > 0.0 {
+        format!("+{}", format(quantity))
+    } else {
+        format(quantity)
+    }
+}
+
+pub fn to_action_str(tile: TileType) -> &'static str {
+    match &tile_properties(tile).action {
+        Some(action) => action,
+        None => panic!("{:?} is not the result of any action", tile),
+    }
+}
+
+fn to_tooltip_str(tile: TileType) -> Option<&'static [String]> {
+    let tooltip = &tile_properties(tile).tooltip;
+    if tooltip.is_empty() {
+        None
+    } else {
+        Some(tooltip)
     }
 }
@@ -0,0 +1,131 @@
+use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::drawing_state::DrawingState;
+use crate::screen::gui::format_units::format_age;
+use crate::screen::gui::panels::draw_available_transformations::to_action_str;
+use crate::screen::gui::panels::longest;
+use crate::screen::gui::panels::task_queue::{reason_str, task_card_height};
+use crate::screen::gui::panels::top_bar::measure_button;
+use crate::screen::gui::{GuiActions, FONT_SIZE, MARGIN};
+use crate::screen::main_scene_input::CellSelection;
+use crate::world::event_log::{Event, EventKind};
+use crate::world::{GameGoalState, World};
+use mq_basics::Vec2;
+use std::collections::HashSet;
+
+const VISIBLE_EVENTS: usize = 8;
+
+/// Shows the most recent events at the bottom left. Clicking an event with a position moves the
+/// camera there and highlights the cell.
+pub fn draw_event_log(
+    drawer: &mut dyn DrawerTrait,
+    world: &World,
+    drawing: &mut DrawingState,
+    gui_actions: GuiActions,
+) -> GuiActions {
+    let mut cell_selection = gui_actions.cell_selection;
+    if !drawing.show_event_log || world.events.len() == 0 {
+        return gui_actions;
+    }
+    let max_scroll = world.events.len().saturating_sub(VISIBLE_EVENTS);
+    drawing.event_log_scroll = drawing.event_log_scroll.min(max_scroll);
+    let shown = world
+        .events
+        .iter_newest()
+        .skip(drawing.event_log_scroll)
+        .take(VISIBLE_EVENTS)
+        .collect::<Vec<_>>();
+    let lines = shown
+        .iter()
+        .map(|event| event_str(world, event))
+        .collect::<Vec<_>>();
+    let title = format!("Events ({})", world.events.len());
+    let longest_line = longest(lines.iter(), &title);
+    let button_size = measure_button(drawer, longest_line);
+    let line_height = button_size.y + MARGIN * 0.5;
+    let panel_size = Vec2::new(
+        button_size.x + MARGIN * 3.0,
+        FONT_SIZE * 2.0 + (VISIBLE_EVENTS + 1) as f32 * line_height + MARGIN * 2.0,
+    );
+    let mut scroll = drawing.event_log_scroll;
+    let mut clicked = None;
+    let panel = drawer.ui_named_group(
+        &title,
+        MARGIN,
+        drawer.screen_height() - task_card_height() - panel_size.y - MARGIN * 2.0,
+        panel_size.x,
+        panel_size.y,
+        &mut |drawer| {
+            drawer.ui_same_line(&mut |drawer| {
+                if drawer.ui_button("Newer").is_clicked() {
+                    scroll = scroll.saturating_sub(VISIBLE_EVENTS);
+                }
+                if drawer.ui_button("Older").is_clicked() {
+                    scroll = (scroll + VISIBLE_EVENTS).min(max_scroll);
+                }
+            });
+            for (event, line) in shown.iter().zip(&lines) {
+                if event.position.is_some() {
+                    if drawer.ui_button(line).is_clicked() {
+                        clicked = event.position;
+                    }
+                } else {
+                    drawer.ui_text(line);
+                }
+            }
+        },
+    );
+    drawing.event_log_scroll = scroll;
+    if let Some(position) = clicked {
+        drawing.set_highlighted_cells(HashSet::from([position]));
+        drawing.re_center(position);
+    }
+    if panel.is_hovered_or_clicked() {
+        cell_selection = CellSelection::no_selection();
+    }
+    GuiActions {
+        cell_selection,
+        ..gui_actions
+    }
+}
+
+fn event_str(world: &World, event: &Event) -> String {
+    let description = match &event.kind {
+        EventKind::TaskCompleted(tile) => format!("Done: {}", to_action_str(*tile)),
+        EventKind::TaskBlocked(tile, reasons) => {
+            let reasons = reasons
+                .iter()
+                .map(|reason| reason_str(reason))
+                .collect::<Vec<_>>()
+                .join(". ");
+            format!("Blocked: {}. {}", to_action_str(*tile), reasons)
+        }
+        EventKind::Undone(tile) => format!("Undone: {}", to_action_str(*tile)),
+        EventKind::Redone(tile) => format!("Redone: {}", to_action_str(*tile)),
+        EventKind::HistoryOutdated(tile) => format!(
+            "Can't undo or redo {} anymore, its cells changed",
+            to_action_str(*tile)
+        ),
+        EventKind::NetworksMerged(count) => format!("{} machine networks merged", count),
+        EventKind::NetworkLostPower => "A machine network lost power".to_string(),
+        EventKind::TreeDied => "A tree died".to_string(),
+        EventKind::GoalStateChanged(goal_state) => goal_state_str(*goal_state).to_string(),
+        EventKind::ObjectiveCompleted(name) => {
+            let title = world
+                .objectives
+                .get(name)
+                .map(|objective| objective.title.as_str())
+                .unwrap_or(name);
+            format!("Objective completed: {}", title)
+        }
+    };
+    format!("{}: {}", format_age(event.age_in_minutes), description)
+}
+
+fn goal_state_str(goal_state: GameGoalState) -> &'static str {
+    match goal_state {
+        GameGoalState::InitialDialog => "Game restarted",
+        GameGoalState::Started => "Game started",
+        GameGoalState::Finished(_) => "Planet restored!",
+        GameGoalState::PostFinished => "Playing after finishing",
+    }
+}
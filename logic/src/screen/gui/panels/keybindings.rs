@@ -0,0 +1,85 @@
+use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::drawing_state::{DrawingState, TopBarShowing};
+use crate::screen::gui::panels::longest;
+use crate::screen::gui::panels::top_bar::measure_button;
+use crate::screen::gui::{GuiActions, FONT_SIZE, MARGIN};
+use crate::screen::keybindings::{Keybindings, ACTIONS};
+use crate::screen::main_scene_input::CellSelection;
+use mq_basics::Vec2;
+
+/// Lists the key of every action, and lets the user change them. After clicking "Change" the
+/// next key pressed is assigned to that action (see `Gui::process_input`).
+pub fn draw_keybindings(
+    drawer: &mut dyn DrawerTrait,
+    drawing: &mut DrawingState,
+    gui_actions: GuiActions,
+    keybindings: &Keybindings,
+) -> GuiActions {
+    let mut cell_selection = gui_actions.cell_selection;
+    if drawing.top_bar_showing != TopBarShowing::Keys {
+        drawing.rebinding = None;
+        return gui_actions;
+    }
+    let lines = ACTIONS
+        .iter()
+        .map(|action| {
+            format!(
+                "{}: {}",
+                action.description(),
+                keybindings.keys_str(*action)
+            )
+        })
+        .collect::<Vec<_>>();
+    let waiting_text = match drawing.rebinding {
+        Some(action) => format!("Press a key for \"{}\"", action.description()),
+        None => "".to_string(),
+    };
+    let longest_line = longest(lines.iter(), &waiting_text);
+    let button_size = measure_button(drawer, "Change");
+    let line_width = drawer.ui_measure_text(longest_line, FONT_SIZE).x;
+    let line_height = button_size.y + MARGIN * 0.5;
+    let panel_size = Vec2::new(
+        line_width + button_size.x + MARGIN * 4.0,
+        FONT_SIZE * 2.0 + (lines.len() + 2) as f32 * line_height + MARGIN * 2.0,
+    );
+    let mut rebinding = drawing.rebinding;
+    let mut close = false;
+    let panel = drawer.ui_named_group(
+        "Keys",
+        drawer.screen_width() * 0.5 - panel_size.x * 0.5,
+        drawer.screen_height() * 0.5 - panel_size.y * 0.5,
+        panel_size.x,
+        panel_size.y,
+        &mut |drawer| {
+            for (action, line) in ACTIONS.iter().zip(&lines) {
+                drawer.ui_same_line(&mut |drawer| {
+                    if drawer.ui_button("Change").is_clicked() {
+                        rebinding = Some(*action);
+                    }
+                    drawer.ui_text(line);
+                });
+            }
+            if rebinding.is_some() {
+                drawer.ui_same_line(&mut |drawer| {
+                    if drawer.ui_button("Cancel").is_clicked() {
+                        rebinding = None;
+                    }
+                    drawer.ui_text(&waiting_text);
+                });
+            } else if drawer.ui_button("Close").is_clicked() {
+                close = true;
+            }
+        },
+    );
+    drawing.rebinding = rebinding;
+    if close {
+        drawing.top_bar_showing = TopBarShowing::None;
+    }
+    if panel.is_hovered_or_clicked() {
+        cell_selection = CellSelection::no_selection();
+    }
+    GuiActions {
+        cell_selection,
+        ..gui_actions
+    }
+}
@@ -0,0 +1,71 @@
+use crate::common::profiling::{counters, scope_stats, ScopeStats, PROFILE_TRACE_PATH};
+use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::gui::panels::longest;
+use crate::screen::gui::panels::top_bar::{measure_button, TOP_BAR_HEIGHT};
+use crate::screen::gui::{GuiActions, FONT_SIZE, MARGIN};
+use crate::screen::main_scene_input::CellSelection;
+use crate::world::World;
+use mq_basics::Vec2;
+
+/// While profiling, shows the timings of the recent scopes and the counters at the top center.
+pub fn draw_profiler(
+    drawer: &mut dyn DrawerTrait,
+    world: &World,
+    gui_actions: GuiActions,
+) -> GuiActions {
+    if !world.game_state.profile {
+        return gui_actions;
+    }
+    let mut cell_selection = gui_actions.cell_selection;
+    let mut save_profile_trace = gui_actions.save_profile_trace;
+    let mut lines = scope_stats()
+        .iter()
+        .map(|stats| stats_str(stats))
+        .collect::<Vec<_>>();
+    for (name, value) in counters() {
+        lines.push(format!("{}: {}", name, value));
+    }
+    let button_text = format!("Save trace to {}", PROFILE_TRACE_PATH);
+    let longest_line = longest(lines.iter(), &button_text);
+    let text_width = drawer.ui_measure_text(longest_line, FONT_SIZE).x;
+    let button_size = measure_button(drawer, &button_text);
+    let panel_size = Vec2::new(
+        text_width.max(button_size.x) + MARGIN * 2.0,
+        FONT_SIZE * 2.0 + lines.len() as f32 * FONT_SIZE * 1.2 + button_size.y + MARGIN * 2.0,
+    );
+    let panel = drawer.ui_named_group(
+        "Profiler",
+        (drawer.screen_width() - panel_size.x) * 0.5,
+        TOP_BAR_HEIGHT + MARGIN,
+        panel_size.x,
+        panel_size.y,
+        &mut |drawer| {
+            for line in &lines {
+                drawer.ui_text(line);
+            }
+            if drawer.ui_button(&button_text).is_clicked() {
+                save_profile_trace = true;
+            }
+        },
+    );
+    if panel.is_hovered_or_clicked() {
+        cell_selection = CellSelection::no_selection();
+    }
+    GuiActions {
+        cell_selection,
+        save_profile_trace,
+        ..gui_actions
+    }
+}
+
+fn stats_str(stats: &ScopeStats) -> String {
+    format!(
+        "{}{}: {} calls, min {:.3} ms, mean {:.3} ms, p99 {:.3} ms",
+        "  ".repeat(stats.depth),
+        stats.name,
+        stats.count,
+        stats.min * 1000.0,
+        stats.mean * 1000.0,
+        stats.p99 * 1000.0,
+    )
+}
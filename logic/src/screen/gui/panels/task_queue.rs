@@ -19,11 +19,8 @@ pub fn draw_robot_queue(
     let mut cell_selection = gui_actions.cell_selection;
     let margin = MARGIN;
     let icon_width = PIXELS_PER_TILE_WIDTH as f32 * 1.0;
-    let icon_height = PIXELS_PER_TILE_HEIGHT as f32 * 1.0;
-    let title_height = FONT_SIZE * 1.25;
-    let button_height = FONT_SIZE * 1.5;
     let group_width = icon_width + 3.5 * margin; // for some reason the button has a left margin bigger than MARGIN
-    let group_height = title_height + button_height + icon_height + 3.0 * margin;
+    let group_height = task_card_height();
     let go_to_robot = Option::None;
 
     let mut cancel_task = Option::None;
@@ -103,26 +100,20 @@ pub fn draw_robot_queue(
     }
 }
 
+/// height of each task panel at the bottom of the screen
+pub fn task_card_height() -> f32 {
+    let icon_height = PIXELS_PER_TILE_HEIGHT as f32 * 1.0;
+    let title_height = FONT_SIZE * 1.25;
+    let button_height = FONT_SIZE * 1.5;
+    title_height + button_height + icon_height + 3.0 * MARGIN
+}
+
 fn format_reasons(reasons: &Option<HashSet<TransformationFailure>>) -> Vec<String> {
     if let Some(reasons) = reasons {
         let mut message = vec!["Blocked because:".to_string()];
         let mut reasons_lines = Vec::new();
         for transformation_result in reasons {
-            let reason_line = match transformation_result {
-                TransformationFailure::NotEnoughMaterial => "  Not enough resources (You can build storage machines underground to dig out resources)",
-                TransformationFailure::NotEnoughStorage => "  Not enough storage capacity",
-                TransformationFailure::AboveWouldCollapse => "  Cells above would collapse",
-                TransformationFailure::NoSturdyBase => "  Cells below can not support it",
-                TransformationFailure::WouldOccludeSolarPanel => "  Would occlude solar panel below",
-                TransformationFailure::OccludedSolarPanel => "  Solar panels should have nothing above",
-                TransformationFailure::OutOfShipReach => "  The spaceship network can't reach",
-                TransformationFailure::CanNotDeconstructShip => {
-                    "  You're not allowed to remove the spaceship"
-                }
-                TransformationFailure::SplitNetwork => "  The machine network should not be split",
-            }
-            .to_string();
-            reasons_lines.push(reason_line);
+            reasons_lines.push(format!("  {}", reason_str(transformation_result)));
         }
         reasons_lines.sort();
         message.append(&mut reasons_lines);
@@ -132,6 +123,25 @@ fn format_reasons(reasons: &Option<HashSet<TransformationFailure>>) -> Vec<Strin
     }
 }
 
+pub fn reason_str(reason: &TransformationFailure) -> &'static str {
+    match reason {
+        TransformationFailure::NotEnoughMaterial => {
+            "Not enough resources (You can build storage machines underground to dig out resources)"
+        }
+        TransformationFailure::NotEnoughStorage => "Not enough storage capacity",
+        TransformationFailure::AboveWouldCollapse => "Cells above would collapse",
+        TransformationFailure::NoSturdyBase => "Cells below can not support it",
+        TransformationFailure::WouldOccludeSolarPanel => "Would occlude solar panel below",
+        TransformationFailure::OccludedSolarPanel => "Solar panels should have nothing above",
+        TransformationFailure::OutOfShipReach => "The spaceship network can't reach",
+        TransformationFailure::CanNotDeconstructShip => {
+            "You're not allowed to remove the spaceship"
+        }
+        TransformationFailure::SplitNetwork => "The machine network should not be split",
+        TransformationFailure::Locked => "Not unlocked in this scenario",
+    }
+}
+
 fn draw_task_queue_tooltip(
     drawer: &mut dyn DrawerTrait,
     group_height: f32,
@@ -1,10 +1,13 @@
 use crate::screen::drawer_trait::{DrawerTrait, Interaction};
-use crate::screen::drawing_state::{DrawingState, TopBarShowing};
-use crate::screen::gui::format_units::format_liters;
+use crate::screen::drawing_state::{DrawingState, TopBarShowing, MAX_RENDER_DEPTH};
+use crate::screen::gui::format_units::{format_age, format_grams, format_liters};
 use crate::screen::gui::{GuiActions, FONT_SIZE, MARGIN};
+use crate::screen::keybindings::{Action, Keybindings};
 use crate::screen::main_scene_input::{CellSelection, ZoomChange};
-use crate::world::game_state::{get_goal_air_cleaned, get_goal_air_cleaned_str};
-use crate::world::{World, LIFE_COUNT_REQUIRED_FOR_WINNING};
+use crate::world::game_state::GAME_SPEEDS;
+use crate::world::map::tile_registry::tile_properties;
+use crate::world::objectives::{Comparison, Condition, ObjectiveStatus, Quantity};
+use crate::world::World;
 use mq_basics::Vec2;
 
 pub const TOP_BAR_HEIGHT: f32 = FONT_SIZE * 3.0;
@@ -14,11 +17,18 @@ pub fn draw_top_bar(
     world: &World,
     drawing: &mut DrawingState,
     gui_actions: GuiActions,
+    keybindings: &Keybindings,
 ) -> GuiActions {
     let panel_height = TOP_BAR_HEIGHT;
     let mut goals = Interaction::None;
     let mut help = Interaction::None;
+    let mut keys = Interaction::None;
     let mut cell_selection = gui_actions.cell_selection;
+    let mut undo = gui_actions.undo;
+    let mut redo = gui_actions.redo;
+    let mut toggle_pause = gui_actions.toggle_pause;
+    let mut step_tick = gui_actions.step_tick;
+    let mut set_speed = gui_actions.set_speed;
 
     let mut interactions = Vec::new();
     interactions.push(drawer.ui_group(
@@ -30,12 +40,45 @@ pub fn draw_top_bar(
             drawer.ui_same_line(&mut |drawer: &mut dyn DrawerTrait| {
                 goals = drawer.ui_button("Goals");
                 help = drawer.ui_button("Help");
+                keys = drawer.ui_button("Keys");
+                if drawer.ui_button("Events").is_clicked() {
+                    drawing.show_event_log = !drawing.show_event_log;
+                }
+                if world.history.can_undo() && drawer.ui_button("Undo").is_clicked() {
+                    undo = true;
+                }
+                if world.history.can_redo() && drawer.ui_button("Redo").is_clicked() {
+                    redo = true;
+                }
+                drawer.ui_text("    Speed:");
+                let pause_text = if world.game_state.paused {
+                    "Resume"
+                } else {
+                    "Pause"
+                };
+                if drawer.ui_button(pause_text).is_clicked() {
+                    toggle_pause = true;
+                }
+                if world.game_state.paused && drawer.ui_button("Step").is_clicked() {
+                    step_tick = true;
+                }
+                for speed in GAME_SPEEDS {
+                    let text = if speed == world.game_state.speed {
+                        format!("[{}]", speed.name())
+                    } else {
+                        speed.name().to_string()
+                    };
+                    if drawer.ui_button(&text).is_clicked() {
+                        set_speed = Some(speed);
+                    }
+                }
                 drawer.ui_text(&format!(
                     "    Render depth: {}",
                     drawing.max_cell.y - drawing.min_cell.y
                 ));
                 if drawer.ui_button("+").is_clicked() {
-                    drawing.min_cell.y = (drawing.min_cell.y - 1).max(drawing.max_cell.y - 15);
+                    drawing.min_cell.y =
+                        (drawing.min_cell.y - 1).max(drawing.max_cell.y - MAX_RENDER_DEPTH);
                     drawing.maybe_change_height_rel(0, None);
                 }
                 if drawer.ui_button("-").is_clicked() {
@@ -49,11 +92,26 @@ pub fn draw_top_bar(
                 if drawer.ui_button("-").is_clicked() {
                     drawing.update_zoom(ZoomChange::ZoomOut)
                 }
+                drawer.ui_text("    View:");
+                if drawer.ui_button(drawing.view_mode.name()).is_clicked() {
+                    drawing.cycle_view_mode();
+                }
+                drawer.ui_text("    Overlay:");
+                if drawer.ui_button(drawing.overlay.name()).is_clicked() {
+                    drawing.overlay = drawing.overlay.next();
+                }
+                drawer.ui_text("    Select:");
+                if drawer.ui_button(drawing.selection_mode.name()).is_clicked() {
+                    drawing.selection_mode = drawing.selection_mode.next();
+                }
             });
         },
     ));
     interactions.push(maybe_draw_goals(drawer, drawing, world, goals));
-    interactions.push(maybe_draw_help(drawer, drawing, help));
+    interactions.push(maybe_draw_help(drawer, drawing, help, keybindings));
+    if keys.is_clicked() {
+        toggle_showing_or_none(&mut drawing.top_bar_showing, TopBarShowing::Keys);
+    }
 
     for interaction in &interactions {
         if interaction.is_hovered_or_clicked() {
@@ -66,6 +124,11 @@ pub fn draw_top_bar(
 
     GuiActions {
         cell_selection,
+        undo,
+        redo,
+        toggle_pause,
+        step_tick,
+        set_speed,
         ..gui_actions
     }
 }
@@ -79,12 +142,16 @@ fn maybe_draw_goals(
     if goals.is_clicked() {
         toggle_showing_or_none(&mut drawing.top_bar_showing, TopBarShowing::Goals);
     }
+    // show the dialogs of the objectives that became active, unless the game was won
+    let completed = world.objectives.completed_count();
+    if completed != drawing.seen_completed_objectives {
+        if completed > drawing.seen_completed_objectives && !world.objectives.all_completed() {
+            drawing.top_bar_showing = TopBarShowing::Goals;
+        }
+        drawing.seen_completed_objectives = completed;
+    }
     return if drawing.top_bar_showing == TopBarShowing::Goals {
-        let text_lines = goals_text_lines(
-            world.networks.get_total_air_cleaned(),
-            world.networks.get_non_ship_machine_count(),
-            world.life.len(),
-        );
+        let text_lines = goals_text_lines(world);
         draw_pop_up(drawer, drawing, "Goals", &text_lines, |_| {})
     } else {
         Interaction::None
@@ -162,87 +229,148 @@ fn toggle_showing_or_none(top_bar_showing: &mut TopBarShowing, showing: TopBarSh
     };
 }
 
-fn goals_text_lines(air_cleaned: f64, machines: i32, trees: usize) -> Vec<String> {
-    fn get_symbol_is_done(done: bool) -> &'static str {
-        if done {
-            ""
-        } else {
-            ""
+fn goals_text_lines(world: &World) -> Vec<String> {
+    let mut lines = vec![
+        "You are an Artificial Intelligence sent to this barren planet".to_string(),
+        "to put life on it.".to_string(),
+    ];
+    for (objective, status) in world.objectives.iter() {
+        match status {
+            ObjectiveStatus::Locked => {}
+            ObjectiveStatus::Completed(age) => {
+                lines.push(String::new());
+                lines.push(format!(
+                    "{} (completed at {})",
+                    objective.title,
+                    format_age(age)
+                ));
+            }
+            ObjectiveStatus::Active => {
+                lines.push(String::new());
+                lines.push(objective.title.clone());
+                for line in &objective.dialog {
+                    lines.push(format!("    {}", line));
+                }
+                for condition in &objective.conditions {
+                    let value = world.measure(condition.quantity);
+                    lines.push(format!(
+                        "    {} {}",
+                        progress_bar(condition.progress(value)),
+                        condition_str(condition, value)
+                    ));
+                }
+            }
         }
     }
-    let goal_air = get_goal_air_cleaned();
-    let air_cleaned_str = format_liters(air_cleaned);
-    let goal_air_str = get_goal_air_cleaned_str();
-    let air_done = get_symbol_is_done(air_cleaned >= get_goal_air_cleaned());
-    let machines_done = get_symbol_is_done(machines == 0);
-    let trees_goal = LIFE_COUNT_REQUIRED_FOR_WINNING;
-    let trees_done = get_symbol_is_done(trees == LIFE_COUNT_REQUIRED_FOR_WINNING);
-    let progress_bar_air = progress_bar(air_cleaned, goal_air);
-    let progress_bar_machines = progress_bar((4.0 - (machines as f64 + 1.0).log10()).max(0.0), 4.0);
-    let progress_bar_trees = progress_bar(trees as f64, trees_goal as f64);
-
-    // TODO: draw progress bars
-    format!(
-        r#"You are an Artificial Intelligence sent to this barren planet
-to put life on it.
+    lines
+}
 
-You have to:
-{progress_bar_air} Clean {goal_air} liters of air (or more): {air_cleaned_str}/{goal_air_str} {air_done}
-{progress_bar_machines} Have no machines: {machines}/0 {machines_done}
-{progress_bar_trees} Keep {trees_goal} trees alive (or more): {trees}/{trees_goal} {trees_done}"#,
+fn condition_str(condition: &Condition, value: f64) -> String {
+    let format_value = |quantity: f64| match condition.quantity {
+        Quantity::AirCleaned => format_liters(quantity),
+        Quantity::StoredResources => format_grams(quantity),
+        Quantity::Minutes => format_age(quantity as i64),
+        Quantity::Life | Quantity::Machines | Quantity::Tiles(_) => format!("{:.0}", quantity),
+    };
+    let label = match condition.quantity {
+        Quantity::AirCleaned => "Air cleaned".to_string(),
+        Quantity::StoredResources => "Material stored".to_string(),
+        Quantity::Life => "Trees alive".to_string(),
+        Quantity::Machines => "Machines apart from the spaceship".to_string(),
+        Quantity::Minutes => "Time".to_string(),
+        Quantity::Tiles(tile) => tile_properties(tile).name.clone(),
+    };
+    let comparison = match condition.comparison {
+        Comparison::AtLeast => "or more",
+        Comparison::AtMost => "or less",
+    };
+    format!(
+        "{} ({}): {}/{}",
+        label,
+        comparison,
+        format_value(value),
+        format_value(condition.target_value())
     )
-    .split("\n")
-    .map(|s| s.to_string())
-    .collect()
 }
 
-fn progress_bar(progress: f64, goal: f64) -> &'static str {
-    let ratio = progress / goal;
-    if ratio < 0.25 {
-        "...."
-    } else if ratio < 0.5 {
-        "|..."
-    } else if ratio < 0.75 {
-        "||.."
-    } else if ratio < 1.0 {
-        "|||."
-    } else {
-        "||||"
-    }
+/// `progress` goes from 0 to 1.
+fn progress_bar(progress: f64) -> String {
+    const SEGMENTS: usize = 10;
+    let filled = ((progress * SEGMENTS as f64).floor() as usize).min(SEGMENTS);
+    format!("{}{}", "|".repeat(filled), ".".repeat(SEGMENTS - filled))
 }
 
 fn maybe_draw_help(
     drawer: &mut dyn DrawerTrait,
     drawing: &mut DrawingState,
     help: Interaction,
+    keybindings: &Keybindings,
 ) -> Interaction {
     if help.is_clicked() {
         toggle_showing_or_none(&mut drawing.top_bar_showing, TopBarShowing::Help);
     }
     return if drawing.top_bar_showing == TopBarShowing::Help {
-        draw_pop_up(drawer, drawing, "Help", &help_text_lines(), |_| {})
+        draw_pop_up(
+            drawer,
+            drawing,
+            "Help",
+            &help_text_lines(keybindings),
+            |_| {},
+        )
     } else {
         Interaction::None
     };
 }
 
-fn help_text_lines() -> Vec<String> {
-    let text = r#"Controls
+fn help_text_lines(keybindings: &Keybindings) -> Vec<String> {
+    let keys = |action: Action| keybindings.keys_str(action);
+    let text = format!(
+        r#"Controls
     
 - click (optionally drag): select cells
   - CTRL (or CMD) + left click (opt. drag): add cells to the selection
   - CTRL (or CMD) + right click (opt. drag): remove cells from the selection
-- arrow UP and DOWN, or W and S, or mouse wheel up and down: change height layer
+- {} and {}, or mouse wheel up and down: change height layer
 - right click and drag: move the map horizontally
-- Q and D: move the map horizontally to top left or to bottom down (Z axis in 3D)
-- A and E: move the map horizontally to bottom left or to top right (X axis 3D)
-- SHIFT + {W,A,S,D,Q,E}: faster move
-- G: go to spaceship
+- {} and {}: move the map horizontally to top left or to bottom down (Z axis in 3D)
+- {} and {}: move the map horizontally to bottom left or to top right (X axis 3D)
+- SHIFT + moving keys: faster move
+- {}: go to spaceship
+- {}: switch between the layers view and a vertical slice along X or Z
+- {}: cycle the data overlays: pressure, networks, power and tree health
+- {}: cycle the selection modes: box, same type, column, flood fill and network
 - CTRL (or CMD) + wheel: zoom in or out
-- L: enable or disable fluid simulaton (CPU heavy)
-- N: single step of fluid simulation
-- R: reset timer and accumulated production
-- M: regenerate map (delete all constructions)"#
-        .to_string();
+- {} and {}: undo and redo the last transformations done by the robots
+- {}: pause or resume the game. {}: pause and advance a single tick
+- {} and {}: make the game faster (up to 10x) or slower
+- {}: enable or disable fluid simulaton (CPU heavy)
+- {}: single step of fluid simulation
+- {}: reset timer and accumulated production (asks for confirmation)
+- {}: regenerate map (delete all constructions, asks for confirmation)
+- {}: open the developer console (type "help" in it to list the commands)
+
+Use the "Keys" button to change these keys."#,
+        keys(Action::HeightUp),
+        keys(Action::HeightDown),
+        keys(Action::MoveUpLeft),
+        keys(Action::MoveDownRight),
+        keys(Action::MoveDownLeft),
+        keys(Action::MoveUpRight),
+        keys(Action::GoToShip),
+        keys(Action::ToggleCrossSection),
+        keys(Action::CycleOverlay),
+        keys(Action::CycleSelectionMode),
+        keys(Action::Undo),
+        keys(Action::Redo),
+        keys(Action::TogglePause),
+        keys(Action::StepSimulation),
+        keys(Action::SpeedUp),
+        keys(Action::SlowDown),
+        keys(Action::ToggleFluids),
+        keys(Action::SingleFluidStep),
+        keys(Action::ResetQuantities),
+        keys(Action::RegenerateMap),
+        keys(Action::ToggleConsole),
+    );
     text.split("\n").map(|s| s.to_string()).collect()
 }
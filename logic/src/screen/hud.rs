@@ -2,7 +2,9 @@
 //! visual information is displayed.
 
 use crate::screen::drawer_trait::DrawerTrait;
+use crate::screen::drawing_state::ViewMode;
 use crate::screen::gui::{FONT_SIZE, TEXT_COLOR, TEXT_COLOR_ALARM};
+use crate::screen::overlay::{legend, Overlay};
 use crate::world::game_state::{get_goal_air_cleaned_str, GameState};
 use crate::world::World;
 
@@ -39,6 +41,22 @@ pub fn draw_level(drawer: &dyn DrawerTrait, min_y: i32, max_y: i32) {
     );
 }
 
+pub fn draw_cross_section_plane(drawer: &dyn DrawerTrait, view_mode: ViewMode, plane: i32) {
+    let axis = if view_mode == ViewMode::CrossSectionX {
+        "x"
+    } else {
+        "z"
+    };
+    let text = format!("vertical slice: {} = {}", axis, plane);
+    drawer.draw_text(
+        text.as_str(),
+        20.0,
+        drawer.screen_height() - FONT_SIZE * 2.0,
+        FONT_SIZE,
+        TEXT_COLOR,
+    );
+}
+
 pub fn draw_networks(drawer: &dyn DrawerTrait, world: &World) {
     let network_count = world.networks.len();
     let text = format!(
@@ -88,7 +106,11 @@ pub fn draw_networks(drawer: &dyn DrawerTrait, world: &World) {
 
 pub fn draw_age(drawer: &dyn DrawerTrait, world: &World) {
     let network_count = world.networks.len();
-    let text = format!("Time spent: {}", world.get_age_str());
+    let text = format!(
+        "Time spent: {} ({})",
+        world.get_age_str(),
+        world.game_state.get_speed_str()
+    );
 
     drawer.draw_text(
         text.as_str(),
@@ -112,3 +134,30 @@ pub fn draw_life(drawer: &dyn DrawerTrait, world: &World) {
         TEXT_COLOR,
     );
 }
+
+/// draws the meaning of each color of the overlay, above the rest of the HUD
+pub fn draw_overlay_legend(drawer: &dyn DrawerTrait, world: &World, overlay: Overlay) {
+    let entries = legend(world, overlay);
+    if entries.is_empty() {
+        return;
+    }
+    let network_count = world.networks.len();
+    let line_height = FONT_SIZE * 1.2;
+    let x = 20.0;
+    let mut y = drawer.screen_height()
+        - FONT_SIZE * (7.0 + network_count as f32)
+        - line_height * entries.len() as f32;
+    drawer.draw_text(
+        &format!("Overlay: {}", overlay.name()),
+        x,
+        y,
+        FONT_SIZE,
+        TEXT_COLOR,
+    );
+    let square = FONT_SIZE * 0.75;
+    for (label, color) in entries {
+        y += line_height;
+        drawer.draw_rectangle(x, y - square, square, square, color);
+        drawer.draw_text(&label, x + FONT_SIZE, y, FONT_SIZE, TEXT_COLOR);
+    }
+}
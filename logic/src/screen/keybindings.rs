@@ -0,0 +1,399 @@
+//! Which keys trigger each action. They can be changed in game (see the Keys panel in the top
+//! bar) or in a text file with lines like `regenerate_map = M` or `height_up = W, Up`. The key
+//! names are the ones in `mq_basics::KeyCode`.
+//!
+//! The modifiers (Control, Shift and Super) are not configurable.
+
+use mq_basics::KeyCode;
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    RegenerateMap,
+    ReloadUiSkin,
+    ToggleProfiling,
+    ToggleFluids,
+    SingleFluidStep,
+    TogglePause,
+    StepSimulation,
+    SpeedUp,
+    SlowDown,
+    ResetQuantities,
+    GoToShip,
+    ToggleCrossSection,
+    CycleOverlay,
+    CycleSelectionMode,
+    Undo,
+    Redo,
+    MoveUpLeft,
+    MoveDownRight,
+    MoveDownLeft,
+    MoveUpRight,
+    HeightUp,
+    HeightDown,
+    ToggleConsole,
+}
+
+pub const ACTIONS: [Action; 24] = [
+    Action::Quit,
+    Action::RegenerateMap,
+    Action::ReloadUiSkin,
+    Action::ToggleProfiling,
+    Action::ToggleFluids,
+    Action::SingleFluidStep,
+    Action::TogglePause,
+    Action::StepSimulation,
+    Action::SpeedUp,
+    Action::SlowDown,
+    Action::ResetQuantities,
+    Action::GoToShip,
+    Action::ToggleCrossSection,
+    Action::CycleOverlay,
+    Action::CycleSelectionMode,
+    Action::Undo,
+    Action::Redo,
+    Action::MoveUpLeft,
+    Action::MoveDownRight,
+    Action::MoveDownLeft,
+    Action::MoveUpRight,
+    Action::HeightUp,
+    Action::HeightDown,
+    Action::ToggleConsole,
+];
+
+impl Action {
+    /// name used in the keybindings file
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::RegenerateMap => "regenerate_map",
+            Action::ReloadUiSkin => "reload_ui_skin",
+            Action::ToggleProfiling => "toggle_profiling",
+            Action::ToggleFluids => "toggle_fluids",
+            Action::SingleFluidStep => "single_fluid_step",
+            Action::TogglePause => "toggle_pause",
+            Action::StepSimulation => "step_simulation",
+            Action::SpeedUp => "speed_up",
+            Action::SlowDown => "slow_down",
+            Action::ResetQuantities => "reset_quantities",
+            Action::GoToShip => "go_to_ship",
+            Action::ToggleCrossSection => "toggle_cross_section",
+            Action::CycleOverlay => "cycle_overlay",
+            Action::CycleSelectionMode => "cycle_selection_mode",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::MoveUpLeft => "move_up_left",
+            Action::MoveDownRight => "move_down_right",
+            Action::MoveDownLeft => "move_down_left",
+            Action::MoveUpRight => "move_up_right",
+            Action::HeightUp => "height_up",
+            Action::HeightDown => "height_down",
+            Action::ToggleConsole => "toggle_console",
+        }
+    }
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::RegenerateMap => "Regenerate map",
+            Action::ReloadUiSkin => "Reload UI skin",
+            Action::ToggleProfiling => "Toggle profiling",
+            Action::ToggleFluids => "Toggle fluid simulation",
+            Action::SingleFluidStep => "Single fluid step",
+            Action::TogglePause => "Pause or resume",
+            Action::StepSimulation => "Pause and advance one tick",
+            Action::SpeedUp => "Faster game speed",
+            Action::SlowDown => "Slower game speed",
+            Action::ResetQuantities => "Reset timer and production",
+            Action::GoToShip => "Go to spaceship",
+            Action::ToggleCrossSection => "Switch layers/slice view",
+            Action::CycleOverlay => "Cycle overlays",
+            Action::CycleSelectionMode => "Cycle selection modes",
+            Action::Undo => "Undo last transformation",
+            Action::Redo => "Redo transformation",
+            Action::MoveUpLeft => "Move map up-left",
+            Action::MoveDownRight => "Move map down-right",
+            Action::MoveDownLeft => "Move map down-left",
+            Action::MoveUpRight => "Move map up-right",
+            Action::HeightUp => "Go up a layer",
+            Action::HeightDown => "Go down a layer",
+            Action::ToggleConsole => "Open or close the console",
+        }
+    }
+    fn from_config_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|action| action.config_name() == name)
+            .cloned()
+    }
+    fn default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::Quit => vec![KeyCode::Escape],
+            Action::RegenerateMap => vec![KeyCode::M],
+            Action::ReloadUiSkin => vec![KeyCode::U],
+            Action::ToggleProfiling => vec![KeyCode::P],
+            Action::ToggleFluids => vec![KeyCode::L],
+            Action::SingleFluidStep => vec![KeyCode::N],
+            Action::TogglePause => vec![KeyCode::Space],
+            Action::StepSimulation => vec![KeyCode::Period],
+            Action::SpeedUp => vec![KeyCode::Equal],
+            Action::SlowDown => vec![KeyCode::Minus],
+            Action::ResetQuantities => vec![KeyCode::R],
+            Action::GoToShip => vec![KeyCode::G],
+            Action::ToggleCrossSection => vec![KeyCode::C],
+            Action::CycleOverlay => vec![KeyCode::O],
+            Action::CycleSelectionMode => vec![KeyCode::T],
+            Action::Undo => vec![KeyCode::Z],
+            Action::Redo => vec![KeyCode::Y],
+            Action::MoveUpLeft => vec![KeyCode::Q],
+            Action::MoveDownRight => vec![KeyCode::D],
+            Action::MoveDownLeft => vec![KeyCode::A],
+            Action::MoveUpRight => vec![KeyCode::E],
+            Action::HeightUp => vec![KeyCode::W, KeyCode::Up],
+            Action::HeightDown => vec![KeyCode::S, KeyCode::Down],
+            Action::ToggleConsole => vec![KeyCode::GraveAccent],
+        }
+    }
+}
+
+/// Keys that can be assigned to actions. Modifiers are excluded because they are used together
+/// with the mouse.
+pub const BINDABLE_KEYS: [KeyCode; 75] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Space,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::LeftBracket,
+    KeyCode::RightBracket,
+    KeyCode::Backslash,
+    KeyCode::GraveAccent,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::KpEnter,
+];
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .find(|key| key_name(**key).eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keybindings {
+    keys: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Keybindings {
+    pub fn new() -> Self {
+        let mut keys = HashMap::new();
+        for action in ACTIONS {
+            keys.insert(action, action.default_keys());
+        }
+        Self { keys }
+    }
+
+    /// Starts from the default bindings and overrides the actions present in the file.
+    /// A missing file is not an error, as it will be created when rebinding keys.
+    pub fn load(path: &str) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("in {}: {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(format!("could not read {}: {}", path, e)),
+        }
+    }
+
+    pub fn load_or_default(path: &str) -> Self {
+        Self::load(path).unwrap_or_else(|error| {
+            eprintln!(
+                "Error loading keybindings, using the default ones: {}",
+                error
+            );
+            Self::new()
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_config_string())
+            .map_err(|e| format!("could not write keybindings to {}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keybindings = Self::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = line_index + 1;
+            let (action_name, key_names) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'action = key'", line_number))?;
+            let action = Action::from_config_name(action_name.trim()).ok_or_else(|| {
+                format!(
+                    "line {}: unknown action '{}'",
+                    line_number,
+                    action_name.trim()
+                )
+            })?;
+            let mut keys = Vec::new();
+            for key_name in key_names.split(',') {
+                let key_name = key_name.trim();
+                if !key_name.is_empty() {
+                    let key = parse_key(key_name).ok_or_else(|| {
+                        format!("line {}: unknown key '{}'", line_number, key_name)
+                    })?;
+                    keys.push(key);
+                }
+            }
+            keybindings.keys.insert(action, keys);
+        }
+        Ok(keybindings)
+    }
+
+    pub fn to_config_string(&self) -> String {
+        let mut text = "# action = key, other_key\n".to_string();
+        for action in ACTIONS {
+            text += &format!("{} = {}\n", action.config_name(), self.keys_str(action));
+        }
+        text
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys
+            .get(&action)
+            .map(|keys| keys.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn keys_str(&self, action: Action) -> String {
+        let names: Vec<String> = self.keys(action).iter().map(|k| key_name(*k)).collect();
+        names.join(", ")
+    }
+
+    /// Sets the key as the only one for the action, and removes it from any other action so
+    /// that a key never triggers 2 actions.
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        for keys in self.keys.values_mut() {
+            keys.retain(|k| *k != key);
+        }
+        self.keys.insert(action, vec![key]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_roundtrip() {
+        let keybindings = Keybindings::new();
+        let text = keybindings.to_config_string();
+        assert_eq!(Keybindings::parse(&text), Ok(keybindings));
+    }
+
+    #[test]
+    fn test_parse_overrides_defaults() {
+        let keybindings = Keybindings::parse(
+            r#"
+# comments and empty lines are fine
+regenerate_map = F5
+height_up = Key8, kp_enter_is_not_a_key_name
+"#,
+        );
+        assert_eq!(
+            keybindings,
+            Err("line 4: unknown key 'kp_enter_is_not_a_key_name'".to_string())
+        );
+
+        let keybindings = Keybindings::parse("regenerate_map = F5\nheight_up = key8, Up").unwrap();
+        assert_eq!(keybindings.keys(Action::RegenerateMap), &[KeyCode::F5]);
+        assert_eq!(
+            keybindings.keys(Action::HeightUp),
+            &[KeyCode::Key8, KeyCode::Up]
+        );
+        assert_eq!(keybindings.keys(Action::Quit), &[KeyCode::Escape]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Keybindings::parse("fly = X").is_err());
+        assert!(Keybindings::parse("quit X").is_err());
+    }
+
+    #[test]
+    fn test_rebind_removes_key_from_other_actions() {
+        let mut keybindings = Keybindings::new();
+        keybindings.rebind(Action::Quit, KeyCode::M);
+        assert_eq!(keybindings.keys(Action::Quit), &[KeyCode::M]);
+        assert!(keybindings.keys(Action::RegenerateMap).is_empty());
+    }
+}
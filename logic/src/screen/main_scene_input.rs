@@ -1,5 +1,6 @@
 use crate::world::map::CellIndex;
 use juquad::PixelPosition;
+use mq_basics::KeyCode;
 
 #[derive(Copy, Clone)]
 pub struct Input {
@@ -9,6 +10,9 @@ pub struct Input {
     pub toggle_profiling: bool,
     pub toggle_fluids: bool,
     pub single_fluid: bool,
+    pub toggle_pause: bool,
+    pub step_tick: bool,
+    pub change_speed_rel: i32,
     pub change_height_rel: i32,
     pub move_map_horizontally: PixelPosition,
     pub cell_selection: PixelCellSelection,
@@ -16,6 +20,44 @@ pub struct Input {
     pub reset_quantities: bool,
     pub zoom_change: ZoomChange,
     pub go_to_ship: bool,
+    pub toggle_cross_section: bool,
+    pub cycle_overlay: bool,
+    pub cycle_selection_mode: bool,
+    pub undo: bool,
+    pub redo: bool,
+    pub toggle_console: bool,
+    /// any bindable key pressed this frame, used when choosing a new keybinding
+    pub pressed_key: Option<KeyCode>,
+}
+
+impl Input {
+    /// While waiting for a key to rebind or typing in the console, keys should not trigger their
+    /// actions.
+    pub fn without_key_actions(self) -> Self {
+        Self {
+            quit: false,
+            regenerate_map: false,
+            reload_ui_skin: false,
+            toggle_profiling: false,
+            toggle_fluids: false,
+            single_fluid: false,
+            toggle_pause: false,
+            step_tick: false,
+            change_speed_rel: 0,
+            change_height_rel: 0,
+            move_map_horizontally: PixelPosition::new(0.0, 0.0),
+            reset_quantities: false,
+            go_to_ship: false,
+            toggle_cross_section: false,
+            cycle_overlay: false,
+            cycle_selection_mode: false,
+            undo: false,
+            redo: false,
+            toggle_console: false,
+            pressed_key: None,
+            ..self
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -1,3 +1,4 @@
+use crate::screen::keybindings::{Action, Keybindings, BINDABLE_KEYS};
 use crate::screen::main_scene_input::{
     CellSelectionType, Input, PixelCellSelection, PixelSelection, ZoomChange,
 };
@@ -7,6 +8,8 @@ use mq_basics::{KeyCode, MouseButton, Vec2};
 
 pub struct MainSceneInputSource {
     input_source: Box<dyn InputTrait>,
+    keybindings: Keybindings,
+    keybindings_path: Option<String>,
     previous_wheel_click_pos: Vec2,
     previous_left_click_pos: Option<PixelPosition>,
     previous_right_click_pos_with_control: Option<PixelPosition>,
@@ -14,33 +17,97 @@ pub struct MainSceneInputSource {
 
 impl MainSceneInputSource {
     pub fn new(input_source: Box<dyn InputTrait>) -> Self {
+        Self::new_with_keybindings(input_source, Keybindings::new(), None)
+    }
+
+    /// Loads the keybindings from the file, and saves them there when they are changed in game.
+    pub fn new_with_keybindings_file(input_source: Box<dyn InputTrait>, path: &str) -> Self {
+        let keybindings = Keybindings::load_or_default(path);
+        Self::new_with_keybindings(input_source, keybindings, Some(path.to_string()))
+    }
+
+    pub fn new_with_keybindings(
+        input_source: Box<dyn InputTrait>,
+        keybindings: Keybindings,
+        keybindings_path: Option<String>,
+    ) -> Self {
         MainSceneInputSource {
             input_source,
+            keybindings,
+            keybindings_path,
             previous_wheel_click_pos: Vec2::new(0.0, 0.0),
             previous_left_click_pos: Option::None,
             previous_right_click_pos_with_control: Option::None,
         }
     }
+    pub fn keybindings(&self) -> &Keybindings {
+        &self.keybindings
+    }
+
+    pub fn into_parts(self) -> (Box<dyn InputTrait>, Keybindings, Option<String>) {
+        (self.input_source, self.keybindings, self.keybindings_path)
+    }
+
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        self.keybindings.rebind(action, key);
+        if let Some(path) = &self.keybindings_path {
+            if let Err(error) = self.keybindings.save(path) {
+                eprintln!("{}", error);
+            }
+        }
+    }
+
     pub fn get_input(&mut self) -> Input {
         Input {
-            quit: self.input_source.is_key_pressed(KeyCode::Escape),
-            regenerate_map: self.input_source.is_key_pressed(KeyCode::M),
-            reload_ui_skin: self.input_source.is_key_pressed(KeyCode::U),
-            toggle_profiling: self.input_source.is_key_pressed(KeyCode::P),
-            toggle_fluids: self.input_source.is_key_pressed(KeyCode::L),
-            single_fluid: self.input_source.is_key_pressed(KeyCode::N),
+            quit: self.is_action_pressed(Action::Quit),
+            regenerate_map: self.is_action_pressed(Action::RegenerateMap),
+            reload_ui_skin: self.is_action_pressed(Action::ReloadUiSkin),
+            toggle_profiling: self.is_action_pressed(Action::ToggleProfiling),
+            toggle_fluids: self.is_action_pressed(Action::ToggleFluids),
+            single_fluid: self.is_action_pressed(Action::SingleFluidStep),
+            toggle_pause: self.is_action_pressed(Action::TogglePause),
+            step_tick: self.is_action_pressed(Action::StepSimulation),
+            change_speed_rel: self.get_changed_speed(),
             change_height_rel: self.get_changed_height(),
             move_map_horizontally: self.get_horizontal_move(),
             cell_selection: self.get_cell_selection(),
             robot_movement: self.get_robot_movement(),
-            reset_quantities: self.input_source.is_key_pressed(KeyCode::R),
+            reset_quantities: self.is_action_pressed(Action::ResetQuantities),
             zoom_change: self.get_zoom(),
-            go_to_ship: self.input_source.is_key_down(KeyCode::G),
+            go_to_ship: self.is_action_down(Action::GoToShip),
+            toggle_cross_section: self.is_action_pressed(Action::ToggleCrossSection),
+            cycle_overlay: self.is_action_pressed(Action::CycleOverlay),
+            cycle_selection_mode: self.is_action_pressed(Action::CycleSelectionMode),
+            undo: self.is_action_pressed(Action::Undo),
+            redo: self.is_action_pressed(Action::Redo),
+            toggle_console: self.is_action_pressed(Action::ToggleConsole),
+            pressed_key: self.get_pressed_key(),
         }
     }
 }
 
 impl MainSceneInputSource {
+    fn is_action_pressed(&self, action: Action) -> bool {
+        self.keybindings
+            .keys(action)
+            .iter()
+            .any(|key| self.input_source.is_key_pressed(*key))
+    }
+
+    fn is_action_down(&self, action: Action) -> bool {
+        self.keybindings
+            .keys(action)
+            .iter()
+            .any(|key| self.input_source.is_key_down(*key))
+    }
+
+    fn get_pressed_key(&self) -> Option<KeyCode> {
+        BINDABLE_KEYS
+            .iter()
+            .find(|key| self.input_source.is_key_pressed(**key))
+            .cloned()
+    }
+
     fn get_horizontal_move(&mut self) -> PixelPosition {
         let mut diff = PixelPosition::new(0.0, 0.0);
         let control_down = self.is_control_down();
@@ -59,19 +126,19 @@ impl MainSceneInputSource {
         } else {
             let speed_x = 10.0;
             let speed_y = 5.0;
-            if self.input_source.is_key_down(KeyCode::Q) {
+            if self.is_action_down(Action::MoveUpLeft) {
                 diff.x += -speed_x;
                 diff.y += -speed_y;
             }
-            if self.input_source.is_key_down(KeyCode::D) {
+            if self.is_action_down(Action::MoveDownRight) {
                 diff.x += speed_x;
                 diff.y += speed_y;
             }
-            if self.input_source.is_key_down(KeyCode::A) {
+            if self.is_action_down(Action::MoveDownLeft) {
                 diff.x += -speed_x;
                 diff.y += speed_y;
             }
-            if self.input_source.is_key_down(KeyCode::E) {
+            if self.is_action_down(Action::MoveUpRight) {
                 diff.x += speed_x;
                 diff.y += -speed_y;
             }
@@ -204,15 +271,23 @@ impl MainSceneInputSource {
     }
 
     fn get_vertical_arrow_pressed(&mut self) -> i32 {
-        (if self.input_source.is_key_pressed(KeyCode::Up)
-            || self.input_source.is_key_pressed(KeyCode::W)
-        {
+        (if self.is_action_pressed(Action::HeightUp) {
             1
         } else {
             0
-        }) + if self.input_source.is_key_pressed(KeyCode::Down)
-            || self.input_source.is_key_pressed(KeyCode::S)
-        {
+        }) + if self.is_action_pressed(Action::HeightDown) {
+            -1
+        } else {
+            0
+        }
+    }
+
+    fn get_changed_speed(&self) -> i32 {
+        (if self.is_action_pressed(Action::SpeedUp) {
+            1
+        } else {
+            0
+        }) + if self.is_action_pressed(Action::SlowDown) {
             -1
         } else {
             0
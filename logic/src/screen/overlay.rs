@@ -0,0 +1,238 @@
+//! Overlays tint the map to show data that the textures don't show, like which network a
+//! machine belongs to. The colors multiply the texture colors, so cells that are not relevant
+//! for the current overlay are darkened with `DIMMED`.
+
+use crate::world::fluids::VERTICAL_PRESSURE_DIFFERENCE;
+use crate::world::map::{CellIndex, Pressure, TileType};
+use crate::world::World;
+use mq_basics::Color;
+
+pub const DIMMED: Color = Color::new(0.4, 0.4, 0.4, 1.0);
+const LOW_PRESSURE: Color = Color::new(0.3, 0.5, 1.0, 1.0);
+const HIGH_PRESSURE: Color = Color::new(1.0, 0.2, 0.1, 1.0);
+/// pressure of a column of 10 cells of water
+const MAX_OVERLAY_PRESSURE: Pressure = VERTICAL_PRESSURE_DIFFERENCE * 10;
+const PRESSURE_LEGEND_STEPS: i32 = 4;
+
+const POWERED: Color = Color::new(0.3, 1.0, 0.3, 1.0);
+const NOT_POWERED: Color = Color::new(1.0, 0.25, 0.25, 1.0);
+
+const SHIP_NETWORK: Color = Color::new(0.3, 1.0, 0.3, 1.0);
+const UNCONNECTED_NETWORKS: [Color; 5] = [
+    Color::new(1.0, 0.6, 0.1, 1.0),
+    Color::new(0.2, 0.9, 1.0, 1.0),
+    Color::new(1.0, 0.3, 1.0, 1.0),
+    Color::new(1.0, 1.0, 0.2, 1.0),
+    Color::new(0.6, 0.4, 1.0, 1.0),
+];
+
+const TREE_HEALTHY: Color = Color::new(0.3, 1.0, 0.3, 1.0);
+const TREE_SPARSE: Color = Color::new(0.8, 1.0, 0.2, 1.0);
+const TREE_DYING: Color = Color::new(1.0, 0.6, 0.1, 1.0);
+const TREE_DEAD: Color = Color::new(0.7, 0.2, 0.1, 1.0);
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Overlay {
+    None,
+    Pressure,
+    Network,
+    Power,
+    TreeHealth,
+}
+
+impl Overlay {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overlay::None => "None",
+            Overlay::Pressure => "Pressure",
+            Overlay::Network => "Networks",
+            Overlay::Power => "Power",
+            Overlay::TreeHealth => "Tree health",
+        }
+    }
+    pub fn next(&self) -> Self {
+        match self {
+            Overlay::None => Overlay::Pressure,
+            Overlay::Pressure => Overlay::Network,
+            Overlay::Network => Overlay::Power,
+            Overlay::Power => Overlay::TreeHealth,
+            Overlay::TreeHealth => Overlay::None,
+        }
+    }
+}
+
+/// returns None if the overlay doesn't change the color of this cell
+pub fn overlay_tint(world: &World, cell_index: CellIndex, overlay: Overlay) -> Option<Color> {
+    match overlay {
+        Overlay::None => None,
+        Overlay::Pressure => Some(pressure_tint(world.map.get_cell(cell_index).pressure)),
+        Overlay::Network => Some(
+            world
+                .networks
+                .get_network_index(cell_index)
+                .map(network_color)
+                .unwrap_or(DIMMED),
+        ),
+        Overlay::Power => Some(
+            world
+                .networks
+                .get_network_index(cell_index)
+                .and_then(|index| world.networks.iter().nth(index))
+                .map(|network| power_color(network.is_power_satisfied()))
+                .unwrap_or(DIMMED),
+        ),
+        Overlay::TreeHealth => {
+            Some(tree_health_color(world.map.get_cell(cell_index).tile_type).unwrap_or(DIMMED))
+        }
+    }
+}
+
+/// applies the tint to the color, keeping the opacity of the original color
+pub fn tint(color: Color, tint: Color) -> Color {
+    Color::new(
+        color.r * tint.r,
+        color.g * tint.g,
+        color.b * tint.b,
+        color.a,
+    )
+}
+
+pub fn pressure_tint(pressure: Pressure) -> Color {
+    if pressure <= 0 {
+        DIMMED
+    } else {
+        let ratio = (pressure as f32 / MAX_OVERLAY_PRESSURE as f32).min(1.0);
+        lerp(LOW_PRESSURE, HIGH_PRESSURE, ratio)
+    }
+}
+
+pub fn network_color(network_index: usize) -> Color {
+    if network_index == 0 {
+        SHIP_NETWORK
+    } else {
+        UNCONNECTED_NETWORKS[(network_index - 1) % UNCONNECTED_NETWORKS.len()]
+    }
+}
+
+pub fn power_color(power_satisfied: bool) -> Color {
+    if power_satisfied {
+        POWERED
+    } else {
+        NOT_POWERED
+    }
+}
+
+pub fn tree_health_color(tile: TileType) -> Option<Color> {
+    match tile {
+        TileType::TreeHealthy => Some(TREE_HEALTHY),
+        TileType::TreeSparse => Some(TREE_SPARSE),
+        TileType::TreeDying => Some(TREE_DYING),
+        TileType::TreeDead => Some(TREE_DEAD),
+        _ => None,
+    }
+}
+
+/// Returns the entries (label and color) to explain the overlay in the HUD.
+pub fn legend(world: &World, overlay: Overlay) -> Vec<(String, Color)> {
+    match overlay {
+        Overlay::None => Vec::new(),
+        Overlay::Pressure => {
+            let mut entries = vec![("no water".to_string(), DIMMED)];
+            for step in 1..=PRESSURE_LEGEND_STEPS {
+                let pressure = MAX_OVERLAY_PRESSURE * step / PRESSURE_LEGEND_STEPS;
+                let or_more = if step == PRESSURE_LEGEND_STEPS {
+                    " or more"
+                } else {
+                    ""
+                };
+                entries.push((
+                    format!("pressure {}{}", pressure, or_more),
+                    pressure_tint(pressure),
+                ));
+            }
+            entries
+        }
+        Overlay::Network => {
+            let mut entries = Vec::new();
+            for network_index in 0..world.networks.len() {
+                let ship = if network_index == 0 { " (ship)" } else { "" };
+                entries.push((
+                    format!("Network #{}{}", network_index + 1, ship),
+                    network_color(network_index),
+                ));
+            }
+            entries.push(("not in a network".to_string(), DIMMED));
+            entries
+        }
+        Overlay::Power => vec![
+            ("network with enough power".to_string(), POWERED),
+            ("network without enough power".to_string(), NOT_POWERED),
+            ("not in a network".to_string(), DIMMED),
+        ],
+        Overlay::TreeHealth => vec![
+            ("healthy tree".to_string(), TREE_HEALTHY),
+            ("sparse tree".to_string(), TREE_SPARSE),
+            ("dying tree".to_string(), TREE_DYING),
+            ("dead tree".to_string(), TREE_DEAD),
+        ],
+    }
+}
+
+fn lerp(from: Color, to: Color, t: f32) -> Color {
+    Color::new(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+        from.a + (to.a - from.a) * t,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::cell::is_tree;
+
+    #[test]
+    fn test_pressure_tint_is_clamped() {
+        assert_eq!(pressure_tint(0), DIMMED);
+        let high = pressure_tint(MAX_OVERLAY_PRESSURE);
+        assert_eq!(pressure_tint(MAX_OVERLAY_PRESSURE * 3), high);
+        assert!(high.r > high.b);
+        let low = pressure_tint(1);
+        assert!(low.b > low.r);
+    }
+
+    #[test]
+    fn test_network_colors() {
+        assert_eq!(network_color(0), SHIP_NETWORK);
+        assert_ne!(network_color(1), network_color(2));
+        assert_eq!(
+            network_color(1),
+            network_color(1 + UNCONNECTED_NETWORKS.len())
+        );
+    }
+
+    #[test]
+    fn test_tree_health_only_for_trees() {
+        for tile in [
+            TileType::TreeHealthy,
+            TileType::TreeSparse,
+            TileType::TreeDying,
+            TileType::TreeDead,
+            TileType::WallRock,
+            TileType::Air,
+        ] {
+            assert_eq!(tree_health_color(tile).is_some(), is_tree(tile));
+        }
+    }
+
+    #[test]
+    fn test_legend_has_every_network() {
+        let world = World::new();
+        assert_eq!(
+            legend(&world, Overlay::Network).len(),
+            world.networks.len() + 1
+        );
+        assert!(legend(&world, Overlay::None).is_empty());
+    }
+}
@@ -1,35 +1,47 @@
+pub mod balance;
+pub mod console;
+pub mod environment;
+pub mod event_log;
 pub mod fluids;
 pub mod game_state;
 pub mod gameplay_tests;
+pub mod history;
 pub mod map;
 pub mod networks;
+pub mod objectives;
 pub mod robots;
+pub mod scenario;
+pub mod snapshot;
+pub mod validation;
 
 use std::collections::{HashSet, VecDeque};
 
+use crate::common::profiling::{write_chrome_trace, PROFILE_TRACE_PATH};
 use crate::scene::GameLoopState;
-use crate::screen::gui::format_units::format_age;
+use crate::screen::gui::format_units::{format_age, Grams};
+use balance::refresh_balance;
+use console::ConsoleLog;
+use event_log::{representative_cell, sorted_reasons, EventKind, EventLog};
 use fluids::FluidMode;
 use fluids::Fluids;
-use game_state::get_goal_air_cleaned;
 use game_state::GameState;
-use map::transform_cells::Transformation;
+use history::{AppliedTransformation, CellChange, History};
+use map::transform_cells::{allowed_transformations, Transformation};
 use map::transformation_rules::TransformationRules;
 use map::CellIndex;
 use map::Map;
-use networks::Networks;
+use networks::{NetworkEvent, Networks};
+use objectives::{Campaign, Objectives};
 use robots::Robot;
 
 use crate::screen::gui::gui_actions::GuiActions;
 use crate::world::game_state::{DEFAULT_ADVANCING_FLUIDS, DEFAULT_PROFILE_ENABLED};
-use crate::world::map::cell::{ages, transition_aging_tile};
+use crate::world::map::cell::{ages, is_networkable, transition_aging_tile};
 use crate::world::map::transform_cells::TransformationFailure;
-use crate::world::map::{Cell, MapType, TileType, DEFAULT_MAP_TYPE};
+use crate::world::map::{Cell, CellCubeIterator, MapType, TileType, DEFAULT_MAP_TYPE};
 
 type AgeInMinutes = i64;
 
-pub const LIFE_COUNT_REQUIRED_FOR_WINNING: usize = 50;
-
 pub struct World {
     pub map: Map,
     pub fluids: Fluids,
@@ -40,7 +52,13 @@ pub struct World {
     pub life: HashSet<CellIndex>,
     pub game_state: GameState,
     pub goal_state: GameGoalState,
+    pub objectives: Objectives,
+    /// the tiles that can be built, or None if all of them can. See `scenario`.
+    pub unlocked_tiles: Option<Vec<TileType>>,
     pub age_in_minutes: AgeInMinutes,
+    pub events: EventLog,
+    pub history: History,
+    pub console: ConsoleLog,
 }
 
 #[derive(Clone)]
@@ -59,8 +77,8 @@ pub struct TransformationTask {
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum GameGoalState {
     InitialDialog,
+    /// playing until all the objectives are completed, see `objectives`
     Started,
-    ReachedProduction,
     Finished(AgeInMinutes),
     PostFinished,
 }
@@ -76,8 +94,12 @@ impl World {
     }
 
     pub fn new_with_options(profile: bool, fluids: bool, map_type: MapType) -> Self {
+        Self::new_with_map(profile, fluids, Map::new_generated(map_type))
+    }
+
+    /// The map needs to have a ship.
+    pub fn new_with_map(profile: bool, fluids: bool, map: Map) -> Self {
         let game_state = GameState::new(fluids);
-        let map = Map::new_generated(map_type);
         let ship_position = map.get_ship_position();
         let fluids = Fluids::new(FluidMode::InStages);
         let robots = Self::reset_robots(ship_position);
@@ -91,12 +113,32 @@ impl World {
             life: HashSet::new(),
             game_state,
             goal_state: GameGoalState::InitialDialog,
+            objectives: Objectives::new(Campaign::default_campaign()),
+            unlocked_tiles: None,
             age_in_minutes: 0,
+            events: EventLog::new(),
+            history: History::new(),
+            console: ConsoleLog::new(),
         };
         world.set_profile(profile);
         world
     }
 
+    /// Like `new_with_map()`, but the machines and trees that are already in the map join the
+    /// networks and life, e.g. for maps made in other tools.
+    pub fn new_with_built_map(profile: bool, fluids: bool, map: Map) -> Self {
+        let built = CellCubeIterator::new(map.min_cell(), map.max_cell())
+            .map(|position| (position, *map.get_cell(position)))
+            .filter(|(_, cell)| {
+                let tile = cell.tile_type;
+                (is_networkable(tile) && tile != TileType::MachineShip) || ages(tile)
+            })
+            .collect();
+        let mut world = Self::new_with_map(profile, fluids, map);
+        world.force_cells(built, 0.0);
+        world
+    }
+
     pub fn set_profile(&mut self, profile: bool) {
         self.game_state.profile = profile;
         self.fluids.set_profile(profile);
@@ -104,39 +146,81 @@ impl World {
 
     /// returns if the game should do another iteration
     pub fn update(&mut self, gui_actions: GuiActions) -> GameLoopState {
+        if let Some(path) = &self.game_state.balance_path {
+            refresh_balance(path);
+        }
         let should_continue = self.update_with_gui_actions(&gui_actions);
-        self.advance_frame();
+        for _ in 0..self.game_state.ticks_to_run() {
+            self.tick();
+        }
+        self.game_state.advance_frame();
         should_continue
     }
 
+    /// Applies what the player did this frame. The simulation itself advances in `tick()`.
     pub fn update_with_gui_actions(&mut self, gui_actions: &GuiActions) -> GameLoopState {
         if gui_actions.toggle_profiling {
             self.set_profile(!self.game_state.profile);
         }
+        if gui_actions.save_profile_trace {
+            if let Err(error) = write_chrome_trace(PROFILE_TRACE_PATH) {
+                eprintln!("{}", error);
+            }
+        }
 
         self.game_state.update_with_gui_actions(gui_actions);
 
-        self.update_task_queue(gui_actions);
-
-        if self.game_state.should_advance_fluids_this_frame() {
-            self.fluids.advance(&mut self.map);
+        for command in &gui_actions.console_commands {
+            self.run_console_command(command);
         }
 
+        self.update_task_queue(gui_actions);
+
         let should_continue = if gui_actions.regenerate_map {
             self.map.regenerate();
             self.networks.clear();
+            self.aging_tiles.clear();
+            self.life.clear();
             self.robots = Self::reset_robots(self.map.get_ship_position());
             self.task_queue.clear();
+            self.events.clear();
+            self.history.clear();
             GameLoopState::ShouldRepositionCamera
         } else {
             gui_actions.should_continue()
         };
+        self.update_goal_state(gui_actions);
+        self.map.compress();
+        should_continue
+    }
+
+    /// Advances the simulation a fixed amount of time. Each phase only runs in some ticks,
+    /// see `GameState`.
+    fn tick(&mut self) {
+        if self.game_state.should_advance_robots_this_tick() {
+            self.advance_construction_task_queue();
+            // self.advance_robots_task_queue();
+        }
+        if self.game_state.should_advance_fluids_this_tick() {
+            self.fluids.advance(&mut self.map);
+        }
         self.networks.update();
-        if self.game_state.should_age_this_frame() {
+        self.log_network_events();
+        if self.game_state.should_age_this_tick() {
             self.age_tiles();
         }
-        self.update_goal_state(gui_actions);
-        should_continue
+        let previous_goal_state = self.goal_state;
+        self.update_objectives();
+        self.log_goal_state_change(previous_goal_state);
+        if self.game_state.advance_tick() {
+            self.age_in_minutes += 1;
+        }
+        if self.game_state.validate {
+            for violation in self.validate() {
+                eprintln!("tick {}: {}", self.game_state.tick_index, violation);
+            }
+        }
+        self.map.compress();
     }
 
     fn reset_robots(ship_position: Option<CellIndex>) -> Vec<Robot> {
@@ -150,16 +234,17 @@ impl World {
         if let Option::Some(cancel_task) = gui_actions.cancel_task {
             self.task_queue.remove(cancel_task);
         }
+        if gui_actions.undo {
+            self.undo();
+        }
+        if gui_actions.redo {
+            self.redo();
+        }
 
         if let Option::Some(transformation_task) = gui_actions.selected_cell_transformation.clone()
         {
             self.queue_transformation(transformation_task);
         }
-
-        if self.game_state.should_advance_robots_this_frame() {
-            self.advance_construction_task_queue();
-            // self.advance_robots_task_queue();
-        }
     }
 
     fn queue_transformation(&mut self, transformation_task: TransformationTask) {
@@ -176,8 +261,28 @@ impl World {
         if let Some(task) = self.task_queue.pop_back() {
             match task {
                 Task::Transform(task) => {
+                    let new_tile_type = task.transformation.new_tile_type;
+                    let position = representative_cell(task.to_transform.iter());
+                    let previous_reasons = task.blocked_because.clone();
                     if let Some(remaining_task) = self.try_build(task) {
+                        if let Task::Transform(remaining) = &remaining_task {
+                            // only log when the reasons change, as blocked tasks are retried often
+                            if remaining.blocked_because != previous_reasons {
+                                let reasons = remaining.blocked_because.iter().flatten();
+                                self.events.push(
+                                    self.age_in_minutes,
+                                    EventKind::TaskBlocked(new_tile_type, sorted_reasons(reasons)),
+                                    representative_cell(remaining.to_transform.iter()),
+                                );
+                            }
+                        }
                         self.task_queue.push_back(remaining_task);
+                    } else {
+                        self.events.push(
+                            self.age_in_minutes,
+                            EventKind::TaskCompleted(new_tile_type),
+                            position,
+                        );
                     }
                 }
                 Task::Movement(_) => {}
@@ -185,6 +290,20 @@ impl World {
         }
     }
 
+    pub fn is_unlocked(&self, tile: TileType) -> bool {
+        match &self.unlocked_tiles {
+            Some(unlocked_tiles) => unlocked_tiles.contains(&tile),
+            None => true,
+        }
+    }
+
+    /// The transformations that can be applied to all the cells, without the locked ones.
+    pub fn allowed_transformations(&self, cells: &HashSet<CellIndex>) -> Vec<Transformation> {
+        let mut transformations = allowed_transformations(cells, &self.map);
+        transformations.retain(|transformation| self.is_unlocked(transformation.new_tile_type));
+        transformations
+    }
+
     /// returns a pending task for whatever is left from the given transformation,
     /// or None if the transformation was finished
     fn try_build(&mut self, task: TransformationTask) -> Option<Task> {
@@ -196,12 +315,17 @@ impl World {
         let mut remaining = HashSet::new();
         let mut reasons = HashSet::new();
         let mut adjacent = Vec::<CellIndex>::new();
+        let mut changes = Vec::new();
+        let stored_before = self.networks.get_stored_resources();
+        let unlocked = self.is_unlocked(transformation.new_tile_type);
         let mut add_reason = |position, reason| {
             remaining.insert(position);
             reasons.insert(reason);
         };
         for pos_to_transform in to_transform {
-            if self.networks.is_adjacent_to_ship_network(pos_to_transform) {
+            if !unlocked {
+                add_reason(pos_to_transform, TransformationFailure::Locked);
+            } else if self.networks.is_adjacent_to_ship_network(pos_to_transform) {
                 adjacent.push(pos_to_transform);
             } else {
                 add_reason(pos_to_transform, TransformationFailure::OutOfShipReach);
@@ -211,12 +335,26 @@ impl World {
             let rules =
                 TransformationRules::new(pos_to_transform, transformation.new_tile_type, &self.map);
 
+            let previous = self.map.get_cell(pos_to_transform).tile_type;
             if let Some(reason) = rules.is_forbidden() {
                 add_reason(pos_to_transform, reason);
             } else if let Some(reason) = self.try_update_network(transformation, pos_to_transform) {
                 add_reason(pos_to_transform, reason);
+            } else {
+                changes.push(CellChange {
+                    position: pos_to_transform,
+                    previous,
+                    new: self.map.get_cell(pos_to_transform).tile_type,
+                });
             }
         }
+        if changes.len() > 0 {
+            self.history.push(AppliedTransformation {
+                transformation,
+                changes,
+                resources_delta: self.networks.get_stored_resources() - stored_before,
+            });
+        }
         if remaining.len() > 0 {
             Some(Task::Transform(TransformationTask::new_with_reason(
                 remaining,
@@ -234,57 +372,154 @@ impl World {
         transformation: Transformation,
         pos_to_transform: CellIndex,
     ) -> Option<TransformationFailure> {
-        let cell = self.map.get_cell_mut(pos_to_transform);
+        let cell = self.map.get_cell(pos_to_transform);
         let mut cell_copy = cell.clone();
         transformation.apply(&mut cell_copy);
         let was_transformed =
             self.networks
                 .add_with_reason(pos_to_transform, cell_copy.tile_type, cell.tile_type);
         if was_transformed == None {
-            *cell = cell_copy;
-            if ages(cell.tile_type) {
-                // TODO: is_alive(). otherwise it doesn't make sense to have aging_tiles and life as separate variables
-                self.aging_tiles.insert(pos_to_transform);
-                self.life.insert(pos_to_transform);
+            *self.map.get_cell_mut(pos_to_transform) = cell_copy;
+            self.update_life(pos_to_transform);
+        }
+        was_transformed
+    }
+
+    fn update_life(&mut self, position: CellIndex) {
+        if ages(self.map.get_cell(position).tile_type) {
+            // TODO: is_alive(). otherwise it doesn't make sense to have aging_tiles and life as separate variables
+            self.aging_tiles.insert(position);
+            self.life.insert(position);
+        } else {
+            self.aging_tiles.remove(&position);
+            self.life.remove(&position);
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(applied) = self.history.undo().cloned() {
+            let tiles = applied
+                .changes
+                .iter()
+                .map(|c| (c.position, c.new, c.previous));
+            let new_tile_type = applied.transformation.new_tile_type;
+            let kind = if self.restore_tiles(tiles.collect(), -applied.resources_delta) {
+                EventKind::Undone(new_tile_type)
+            } else {
+                self.history.forget_undone();
+                EventKind::HistoryOutdated(new_tile_type)
+            };
+            let position = representative_cell(applied.changes.iter().map(|c| &c.position));
+            self.events.push(self.age_in_minutes, kind, position);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(applied) = self.history.redo().cloned() {
+            let tiles = applied
+                .changes
+                .iter()
+                .map(|c| (c.position, c.previous, c.new));
+            let new_tile_type = applied.transformation.new_tile_type;
+            let kind = if self.restore_tiles(tiles.collect(), applied.resources_delta) {
+                EventKind::Redone(new_tile_type)
             } else {
-                self.aging_tiles.remove(&pos_to_transform);
-                self.life.remove(&pos_to_transform);
+                self.history.forget_redone();
+                EventKind::HistoryOutdated(new_tile_type)
+            };
+            let position = representative_cell(applied.changes.iter().map(|c| &c.position));
+            self.events.push(self.age_in_minutes, kind, position);
+        }
+    }
+
+    /// Changes each `(position, expected, tile)` from the `expected` tile to `tile`, as if it was
+    /// built again, so the fluids and health stay as they are now. Returns false without changing
+    /// anything if any cell is not the `expected` tile anymore.
+    fn restore_tiles(
+        &mut self,
+        tiles: Vec<(CellIndex, TileType, TileType)>,
+        resources_delta: Grams,
+    ) -> bool {
+        let outdated = tiles
+            .iter()
+            .any(|(position, expected, _)| self.map.get_cell(*position).tile_type != *expected);
+        if outdated {
+            return false;
+        }
+        let cells = tiles
+            .into_iter()
+            .map(|(position, _, tile)| {
+                let mut cell = *self.map.get_cell(position);
+                Transformation::to(tile).apply(&mut cell);
+                (position, cell)
+            })
+            .collect();
+        self.force_cells(cells, resources_delta);
+        true
+    }
+
+    /// Sets the cells without checking the transformation rules, keeping the networks in sync.
+    fn force_cells(&mut self, cells: Vec<(CellIndex, Cell)>, resources_delta: Grams) {
+        let mut tiles = Vec::new();
+        for (position, cell) in cells {
+            if *self.map.get_cell(position) != cell {
+                *self.map.get_cell_mut(position) = cell;
             }
+            self.update_life(position);
+            tiles.push((position, cell.tile_type));
         }
-        was_transformed
+        self.networks.force_replace(&tiles, resources_delta);
     }
 
     fn age_tiles(&mut self) {
+        let trees_age = self.goal_state == GameGoalState::Started && !self.objectives.stops_aging();
         for cell_index in &self.aging_tiles {
-            let cell = self.map.get_cell_mut(cell_index.clone());
-            let died = age_tile(cell, self.goal_state);
-            if died {
-                self.life.remove(cell_index);
+            let mut cell = *self.map.get_cell(*cell_index);
+            let died = age_tile(&mut cell, trees_age);
+            // a write changes the version of the layers, see `Map::layer_version()`
+            if cell != *self.map.get_cell(*cell_index) {
+                *self.map.get_cell_mut(*cell_index) = cell;
+            }
+            if died && self.life.remove(cell_index) {
+                self.events
+                    .push(self.age_in_minutes, EventKind::TreeDied, Some(*cell_index));
             }
         }
     }
 
+    fn log_network_events(&mut self) {
+        for event in self.networks.take_events() {
+            let (kind, position) = match event {
+                NetworkEvent::Merged(position, count) => {
+                    (EventKind::NetworksMerged(count), position)
+                }
+                NetworkEvent::LostPower(position) => (EventKind::NetworkLostPower, position),
+            };
+            self.events.push(self.age_in_minutes, kind, Some(position));
+        }
+    }
+
     fn update_goal_state(&mut self, gui_actions: &GuiActions) {
         if gui_actions.reset_quantities {
             self.networks.reset_production();
             self.age_in_minutes = 0;
             self.goal_state = GameGoalState::InitialDialog;
+            self.objectives.reset();
         }
+        let previous_goal_state = self.goal_state;
         if let Some(new_state) = gui_actions.next_game_goal_state {
             self.goal_state = new_state;
         }
-        transition_goal_state(
-            &mut self.goal_state,
-            &self.networks,
-            self.life.len(),
-            self.age_in_minutes,
-        );
+        self.log_goal_state_change(previous_goal_state);
     }
 
-    fn advance_frame(&mut self) {
-        self.game_state.advance_frame();
-        if self.game_state.frame_index % 60 == 0 {
-            self.age_in_minutes += 1;
+    fn log_goal_state_change(&mut self, previous_goal_state: GameGoalState) {
+        if self.goal_state != previous_goal_state {
+            self.events.push(
+                self.age_in_minutes,
+                EventKind::GoalStateChanged(self.goal_state),
+                None,
+            );
         }
     }
 
@@ -293,35 +528,13 @@ impl World {
     }
 }
 
-fn transition_goal_state(
-    current: &mut GameGoalState,
-    networks: &Networks,
-    life_count: usize,
-    age: AgeInMinutes,
-) {
-    if *current == GameGoalState::Started {
-        if networks.get_total_air_cleaned() >= get_goal_air_cleaned() {
-            *current = GameGoalState::ReachedProduction;
-        }
-    } else if *current == GameGoalState::ReachedProduction {
-        if networks.get_non_ship_machine_count() == 0
-            && life_count >= LIFE_COUNT_REQUIRED_FOR_WINNING
-        {
-            *current = GameGoalState::Finished(age);
-        }
-    }
-}
-
 /// Returns true if the cell died
-fn age_tile(cell: &mut Cell, goal_state: GameGoalState) -> bool {
-    match goal_state {
-        GameGoalState::Started => {
-            cell.health -= 1;
-            if cell.health <= 0 {
-                transition_aging_tile(cell);
-            }
+fn age_tile(cell: &mut Cell, trees_age: bool) -> bool {
+    if trees_age {
+        cell.health -= 1;
+        if cell.health <= 0 {
+            transition_aging_tile(cell);
         }
-        _ => (),
     }
     return cell.tile_type == TileType::TreeDead;
 }
@@ -0,0 +1,271 @@
+//! Tuning values of the economy and the goals, in a text file with lines like
+//! `goal_air_cleaned = 100000`. The values of a tile in `assets/tiles.txt` can be overridden with
+//! lines like `MachineSolarPanel.power = 2000`.
+//!
+//! The file is loaded at startup (see `--balance`) and loaded again when it changes, so the
+//! values can be tuned while playing. Missing values keep their default.
+//!
+//! The values are read in hot paths like the storage capacity of every machine. The lock is only
+//! written when `refresh_balance` loads a new file, so the reads don't wait for each other.
+
+use crate::screen::gui::format_units::{Grams, Liters, Watts};
+use crate::world::map::cell::Health;
+use crate::world::map::TileType;
+use mq_basics::now;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// How often the file is checked for changes.
+const CHECK_PERIOD_SECONDS: f64 = 1.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Balance {
+    pub spaceship_initial_storage: Grams,
+    pub air_cleaned_per_cleaner: Liters,
+    pub air_levels_for_allowing_solar: i32,
+    pub life_count_required_for_winning: usize,
+    pub goal_air_cleaned: Liters,
+    pub default_health: Health,
+    pub tile_overrides: Vec<TileOverride>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TileOverride {
+    pub tile: TileType,
+    pub value: TileValue,
+    pub amount: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TileValue {
+    Material,
+    Storage,
+    Power,
+}
+
+impl TileValue {
+    fn config_name(&self) -> &'static str {
+        match self {
+            TileValue::Material => "material",
+            TileValue::Storage => "storage",
+            TileValue::Power => "power",
+        }
+    }
+}
+
+const TILE_VALUES: [TileValue; 3] = [TileValue::Material, TileValue::Storage, TileValue::Power];
+
+pub const DEFAULT_BALANCE: Balance = Balance {
+    spaceship_initial_storage: 5_000_000.0,
+    air_cleaned_per_cleaner: 1.0,
+    air_levels_for_allowing_solar: 20,
+    life_count_required_for_winning: 50,
+    goal_air_cleaned: 100_000.0,
+    default_health: 5,
+    tile_overrides: Vec::new(),
+};
+
+impl Balance {
+    /// A missing file is not an error, the defaults are used.
+    pub fn load(path: &str) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("in {}: {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DEFAULT_BALANCE),
+            Err(e) => Err(format!("could not read {}: {}", path, e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut balance = DEFAULT_BALANCE;
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = line_index + 1;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'name = value'", line_number))?;
+            balance
+                .set(key.trim(), value.trim())
+                .map_err(|e| format!("line {}: {}", line_number, e))?;
+        }
+        Ok(balance)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value
+                .parse::<T>()
+                .map_err(|_| format!("'{}' has an invalid value '{}'", key, value))
+        }
+        match key {
+            "spaceship_initial_storage" => self.spaceship_initial_storage = parse(key, value)?,
+            "air_cleaned_per_cleaner" => self.air_cleaned_per_cleaner = parse(key, value)?,
+            "air_levels_for_allowing_solar" => {
+                self.air_levels_for_allowing_solar = parse(key, value)?
+            }
+            "life_count_required_for_winning" => {
+                self.life_count_required_for_winning = parse(key, value)?
+            }
+            "goal_air_cleaned" => self.goal_air_cleaned = parse(key, value)?,
+            "default_health" => self.default_health = parse(key, value)?,
+            _ => {
+                let tile_override =
+                    parse_tile_override(key).ok_or_else(|| format!("unknown value '{}'", key))?;
+                self.tile_overrides
+                    .retain(|o| (o.tile, o.value) != (tile_override.tile, tile_override.value));
+                self.tile_overrides.push(TileOverride {
+                    amount: parse(key, value)?,
+                    ..tile_override
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The goals that the objectives can use as targets, see `crate::world::objectives`.
+    pub fn goal(&self, name: &str) -> Option<f64> {
+        match name {
+            "goal_air_cleaned" => Some(self.goal_air_cleaned),
+            "life_count_required_for_winning" => Some(self.life_count_required_for_winning as f64),
+            _ => None,
+        }
+    }
+
+    pub fn tile_override(&self, tile: TileType, value: TileValue) -> Option<f64> {
+        self.tile_overrides
+            .iter()
+            .find(|o| o.tile == tile && o.value == value)
+            .map(|o| o.amount)
+    }
+
+    pub fn tile_material(&self, tile: TileType) -> Option<Grams> {
+        self.tile_override(tile, TileValue::Material)
+    }
+    pub fn tile_storage(&self, tile: TileType) -> Option<Grams> {
+        self.tile_override(tile, TileValue::Storage)
+    }
+    pub fn tile_power(&self, tile: TileType) -> Option<Watts> {
+        self.tile_override(tile, TileValue::Power)
+    }
+}
+
+/// parses keys like `MachineDrill.power`, with an amount of 0 to be filled by the caller
+fn parse_tile_override(key: &str) -> Option<TileOverride> {
+    let (tile_name, value_name) = key.split_once('.')?;
+    let tile = TileType::from_name(tile_name)?;
+    let value = *TILE_VALUES
+        .iter()
+        .find(|value| value.config_name() == value_name)?;
+    Some(TileOverride {
+        tile,
+        value,
+        amount: 0.0,
+    })
+}
+
+struct LoadedBalance {
+    balance: Balance,
+    path: Option<String>,
+    modified: Option<SystemTime>,
+    last_check_ts: f64,
+}
+
+/// A static for the same reasons as the tile registry, and so that any function can read the
+/// values without passing the World around. The file path is kept in the `GameState`.
+static BALANCE: RwLock<LoadedBalance> = RwLock::new(LoadedBalance {
+    balance: DEFAULT_BALANCE,
+    path: None,
+    modified: None,
+    last_check_ts: f64::NEG_INFINITY,
+});
+
+pub fn with_balance<R, F: FnOnce(&Balance) -> R>(f: F) -> R {
+    f(&BALANCE.read().unwrap().balance)
+}
+
+/// Loads the file if it's a different one or if it changed since the last time, checking at
+/// most once per `CHECK_PERIOD_SECONDS`. If the file is invalid, the previous values are kept.
+pub fn refresh_balance(path: &str) {
+    let now_ts = now();
+    {
+        let loaded = BALANCE.read().unwrap();
+        if loaded.path.as_deref() == Some(path)
+            && now_ts - loaded.last_check_ts < CHECK_PERIOD_SECONDS
+        {
+            return;
+        }
+    }
+    let modified = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    let mut loaded = BALANCE.write().unwrap();
+    loaded.last_check_ts = now_ts;
+    let same_file = loaded.path.as_deref() == Some(path);
+    if same_file && loaded.modified == modified {
+        return;
+    }
+    loaded.path = Some(path.to_string());
+    loaded.modified = modified;
+    match Balance::load(path) {
+        Ok(balance) => {
+            if same_file {
+                println!("Reloaded the balance from {}", path);
+            }
+            loaded.balance = balance;
+        }
+        Err(error) => eprintln!(
+            "Error loading the balance, keeping the previous values: {}",
+            error
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let balance = Balance::parse(
+            "# comment\n\
+             goal_air_cleaned = 500\n\
+             MachineSolarPanel.power = 2000\n\
+             MachineSolarPanel.power = 3000\n",
+        )
+        .unwrap();
+        assert_eq!(balance.goal_air_cleaned, 500.0);
+        assert_eq!(
+            balance.default_health, DEFAULT_BALANCE.default_health,
+            "missing values keep their default"
+        );
+        assert_eq!(
+            balance.tile_power(TileType::MachineSolarPanel),
+            Some(3000.0)
+        );
+        assert_eq!(balance.tile_power(TileType::MachineDrill), None);
+        assert_eq!(balance.tile_overrides.len(), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| Balance::parse(text).err().unwrap();
+        assert_eq!(error("goal_air_cleaned"), "line 1: expected 'name = value'");
+        assert_eq!(
+            error("goal_air_cleaned = lots"),
+            "line 1: 'goal_air_cleaned' has an invalid value 'lots'"
+        );
+        assert_eq!(error("\ngravity = 3"), "line 2: unknown value 'gravity'");
+        assert_eq!(
+            error("Lava.power = 3"),
+            "line 1: unknown value 'Lava.power'"
+        );
+    }
+
+    #[test]
+    fn test_default_file_matches_defaults() {
+        let balance = Balance::parse(include_str!("../../../assets/balance.txt")).unwrap();
+        assert_eq!(balance, DEFAULT_BALANCE);
+    }
+}
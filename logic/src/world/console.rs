@@ -0,0 +1,409 @@
+//! Developer commands, typed in the console panel or read from a script file at startup (see
+//! `--script`). Each line is a command name followed by its arguments separated by spaces, like
+//! `tile 3 0 -2 wire`. Names of tiles and goal states ignore the case, as the console can only
+//! type lowercase letters. Run `help` in the console to list the commands.
+
+use crate::screen::gui::format_units::{format_grams, Grams};
+use crate::world::map::cell::{ages, default_health};
+use crate::world::map::transform_cells::Transformation;
+use crate::world::map::{Cell, CellIndex, TileType};
+use crate::world::robots::Robot;
+use crate::world::{AgeInMinutes, GameGoalState, World};
+use std::collections::VecDeque;
+
+/// Older lines are dropped to keep the memory bounded.
+pub const MAX_CONSOLE_LINES: usize = 200;
+
+const HELP: [&str; 8] = [
+    "tile X Y Z TILE: set the tile of a cell, keeping the networks in sync",
+    "resources X Y Z GRAMS: add resources to the network of a cell",
+    "robot X Y Z: spawn a robot",
+    "goal STATE [AGE]: set the goal state (initialdialog, started, finished AGE, postfinished)",
+    "teleport X Y Z: move the camera",
+    "fluids: enable or disable the fluid simulation",
+    "pressures X Y Z X2 Y2 Z2: print the pressures of a region, also in the terminal",
+    "help: show this list",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleCommand {
+    SetTile(CellIndex, TileType),
+    AddResources(CellIndex, Grams),
+    SpawnRobot(CellIndex),
+    SetGoalState(GameGoalState),
+    /// moves the camera, handled by the `DrawingState`
+    Teleport(CellIndex),
+    ToggleFluids,
+    /// inclusive region
+    DumpPressures(CellIndex, CellIndex),
+    Help,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let name = match words.first() {
+            Some(name) => *name,
+            None => return Err("empty command".to_string()),
+        };
+        let args = &words[1..];
+        let command = match name {
+            "tile" => {
+                expect_args(name, args, 4)?;
+                ConsoleCommand::SetTile(
+                    parse_position(args, 0)?,
+                    TileType::parse_placeable(args[3])?,
+                )
+            }
+            "resources" => {
+                expect_args(name, args, 4)?;
+                ConsoleCommand::AddResources(parse_position(args, 0)?, parse_number(args[3])?)
+            }
+            "robot" => {
+                expect_args(name, args, 3)?;
+                ConsoleCommand::SpawnRobot(parse_position(args, 0)?)
+            }
+            "goal" => ConsoleCommand::SetGoalState(parse_goal_state(args)?),
+            "teleport" => {
+                expect_args(name, args, 3)?;
+                ConsoleCommand::Teleport(parse_position(args, 0)?)
+            }
+            "fluids" => {
+                expect_args(name, args, 0)?;
+                ConsoleCommand::ToggleFluids
+            }
+            "pressures" => {
+                expect_args(name, args, 6)?;
+                ConsoleCommand::DumpPressures(parse_position(args, 0)?, parse_position(args, 3)?)
+            }
+            "help" => ConsoleCommand::Help,
+            _ => return Err(format!("unknown command '{}', try 'help'", name)),
+        };
+        Ok(command)
+    }
+
+    /// Parses a whole script, with one command per line. Empty lines and lines starting with
+    /// '#' are ignored.
+    pub fn parse_script(text: &str) -> Result<Vec<Self>, String> {
+        let mut commands = Vec::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let command =
+                Self::parse(line).map_err(|e| format!("line {}: {}", line_index + 1, e))?;
+            commands.push(command);
+        }
+        Ok(commands)
+    }
+
+    pub fn load_script(path: &str) -> Result<Vec<Self>, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        Self::parse_script(&text).map_err(|e| format!("in {}: {}", path, e))
+    }
+}
+
+fn expect_args(name: &str, args: &[&str], count: usize) -> Result<(), String> {
+    if args.len() == count {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{}' needs {} arguments but got {}",
+            name,
+            count,
+            args.len()
+        ))
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse::<T>()
+        .map_err(|_| format!("'{}' is not a valid number", word))
+}
+
+/// reads the 3 coordinates starting at `index`
+fn parse_position(args: &[&str], index: usize) -> Result<CellIndex, String> {
+    Ok(CellIndex::new(
+        parse_number(args[index])?,
+        parse_number(args[index + 1])?,
+        parse_number(args[index + 2])?,
+    ))
+}
+
+fn parse_goal_state(args: &[&str]) -> Result<GameGoalState, String> {
+    let name = args
+        .first()
+        .map(|name| name.to_lowercase())
+        .unwrap_or_default();
+    let (goal_state, arg_count) = match name.as_str() {
+        "initialdialog" => (GameGoalState::InitialDialog, 1),
+        "started" => (GameGoalState::Started, 1),
+        "finished" => {
+            expect_args("goal finished", &args[1..], 1)?;
+            (
+                GameGoalState::Finished(parse_number::<AgeInMinutes>(args[1])?),
+                2,
+            )
+        }
+        "postfinished" => (GameGoalState::PostFinished, 1),
+        _ => return Err(format!("unknown goal state '{}'", name)),
+    };
+    expect_args("goal", args, arg_count)?;
+    Ok(goal_state)
+}
+
+/// The output of the commands, shown in the console panel.
+pub struct ConsoleLog {
+    lines: VecDeque<String>,
+}
+
+impl ConsoleLog {
+    pub fn new() -> Self {
+        Self {
+            lines: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: String) {
+        if self.lines.len() >= MAX_CONSOLE_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// most recent lines first
+    pub fn iter_newest(&self) -> impl Iterator<Item = &String> {
+        self.lines.iter().rev()
+    }
+}
+
+impl World {
+    /// Runs the command and adds its output to the console log, and to stdout so that the output
+    /// of scripts can be read in the terminal.
+    pub fn run_console_command(&mut self, command: &ConsoleCommand) {
+        let output = match self.try_console_command(command) {
+            Ok(lines) => lines,
+            Err(error) => vec![format!("Error: {}", error)],
+        };
+        for line in output {
+            println!("{}", line);
+            self.console.push(line);
+        }
+    }
+
+    fn try_console_command(&mut self, command: &ConsoleCommand) -> Result<Vec<String>, String> {
+        let output = match command {
+            ConsoleCommand::SetTile(position, tile) => {
+                if Some(*position) == self.map.get_ship_position() {
+                    return Err(format!(
+                        "{} is the ship, which can not be replaced",
+                        position
+                    ));
+                }
+                let mut cell = *self.get_console_cell(*position)?;
+                Transformation::to(*tile).apply(&mut cell);
+                if ages(*tile) {
+                    cell.health = default_health();
+                }
+                self.force_cells(vec![(*position, cell)], 0.0);
+                format!("Set {} to {:?}", position, tile)
+            }
+            ConsoleCommand::AddResources(position, grams) => {
+                let i_network = self
+                    .networks
+                    .get_network_index(*position)
+                    .ok_or_else(|| format!("there is no network at {}", position))?;
+                let network = self.networks.iter_mut().nth(i_network).unwrap();
+                let overflow = network.try_add_resources(*grams);
+                network.stored_resources = network.stored_resources.max(0.0);
+                format!(
+                    "Network {} stores {} ({} did not fit)",
+                    i_network,
+                    network.get_stored_resources_str(),
+                    format_grams(overflow)
+                )
+            }
+            ConsoleCommand::SpawnRobot(position) => {
+                self.get_console_cell(*position)?;
+                self.robots.push(Robot {
+                    position: *position,
+                });
+                format!("Spawned a robot at {}", position)
+            }
+            ConsoleCommand::SetGoalState(goal_state) => {
+                let previous_goal_state = self.goal_state;
+                self.goal_state = *goal_state;
+                self.log_goal_state_change(previous_goal_state);
+                format!("Goal state is {:?}", goal_state)
+            }
+            ConsoleCommand::Teleport(position) => format!("Moved the camera to {}", position),
+            ConsoleCommand::ToggleFluids => {
+                self.game_state.toggle_fluids();
+                format!(
+                    "Fluid simulation {}",
+                    if self.game_state.is_advancing_fluids() {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                )
+            }
+            ConsoleCommand::DumpPressures(corner, other_corner) => {
+                return self.dump_pressures(*corner, *other_corner)
+            }
+            ConsoleCommand::Help => return Ok(HELP.iter().map(|line| line.to_string()).collect()),
+        };
+        Ok(vec![output])
+    }
+
+    fn get_console_cell(&self, position: CellIndex) -> Result<&Cell, String> {
+        self.map
+            .get_cell_optional(position)
+            .ok_or_else(|| format!("{} is outside the map", position))
+    }
+
+    /// one line per row along X, grouped by layers from the top
+    fn dump_pressures(
+        &self,
+        corner: CellIndex,
+        other_corner: CellIndex,
+    ) -> Result<Vec<String>, String> {
+        let min = CellIndex::new(
+            corner.x.min(other_corner.x),
+            corner.y.min(other_corner.y),
+            corner.z.min(other_corner.z),
+        );
+        let max = CellIndex::new(
+            corner.x.max(other_corner.x),
+            corner.y.max(other_corner.y),
+            corner.z.max(other_corner.z),
+        );
+        self.get_console_cell(min)?;
+        self.get_console_cell(max)?;
+        let mut lines = vec![format!("Pressures from {} to {}:", min, max)];
+        for y in (min.y..=max.y).rev() {
+            lines.push(format!("y = {}", y));
+            for z in min.z..=max.z {
+                let row = (min.x..=max.x)
+                    .map(|x| {
+                        self.map
+                            .get_cell(CellIndex::new(x, y, z))
+                            .pressure
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                lines.push(format!("  z = {}: {}", z, row));
+            }
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ConsoleCommand::parse("tile 1 -2 3 machineaircleaner"),
+            Ok(ConsoleCommand::SetTile(
+                CellIndex::new(1, -2, 3),
+                TileType::MachineAirCleaner
+            ))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("  resources 0 0 0 1000.5 "),
+            Ok(ConsoleCommand::AddResources(
+                CellIndex::new(0, 0, 0),
+                1000.5
+            ))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("goal finished 30"),
+            Ok(ConsoleCommand::SetGoalState(GameGoalState::Finished(30)))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("goal Started"),
+            Ok(ConsoleCommand::SetGoalState(GameGoalState::Started))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("fluids"),
+            Ok(ConsoleCommand::ToggleFluids)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |line: &str| ConsoleCommand::parse(line).err().unwrap();
+        assert_eq!(error(""), "empty command");
+        assert_eq!(error("fly"), "unknown command 'fly', try 'help'");
+        assert_eq!(error("robot 1 2"), "'robot' needs 3 arguments but got 2");
+        assert_eq!(error("robot 1 2 x"), "'x' is not a valid number");
+        assert_eq!(error("tile 1 2 3 lava"), "unknown tile 'lava'");
+        assert_eq!(error("tile 1 2 3 unset"), "tile 'unset' can not be placed");
+        assert_eq!(
+            error("tile 1 2 3 machineship"),
+            "tile 'machineship' can not be placed"
+        );
+        assert_eq!(
+            error("goal started 5"),
+            "'goal' needs 1 arguments but got 2"
+        );
+        assert_eq!(
+            error("goal finished"),
+            "'goal finished' needs 1 arguments but got 0"
+        );
+        assert_eq!(
+            ConsoleCommand::parse_script("# setup\nfluids\n\nfly\n"),
+            Err("line 4: unknown command 'fly', try 'help'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_set_tile_keeps_the_world_valid() {
+        let mut world = World::new();
+        let position = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+        world.run_console_command(&ConsoleCommand::SetTile(position, TileType::MachineDrill));
+        assert_eq!(
+            world.map.get_cell(position).tile_type,
+            TileType::MachineDrill
+        );
+        assert!(world.networks.is_in_ship_network(position));
+        world.run_console_command(&ConsoleCommand::SetTile(position, TileType::TreeHealthy));
+        assert!(world.life.contains(&position));
+        assert_eq!(world.validate(), Vec::new());
+    }
+
+    #[test]
+    fn test_set_tile_keeps_the_ship() {
+        let mut world = World::new();
+        let ship = world.map.get_ship_position().unwrap();
+        world.run_console_command(&ConsoleCommand::SetTile(ship, TileType::TreeHealthy));
+        assert_eq!(world.map.get_cell(ship).tile_type, TileType::MachineShip);
+        assert_eq!(world.map.get_ship_position(), Some(ship));
+        assert_eq!(
+            world.console.iter_newest().next().unwrap(),
+            &format!("Error: {} is the ship, which can not be replaced", ship)
+        );
+    }
+
+    #[test]
+    fn test_errors_are_logged() {
+        let mut world = World::new();
+        let outside = world.map.max_cell() + CellIndex::new(1, 1, 1);
+        world.run_console_command(&ConsoleCommand::SpawnRobot(outside));
+        assert_eq!(world.robots.len(), 1);
+        assert_eq!(
+            world.console.iter_newest().next().unwrap(),
+            &format!("Error: {} is outside the map", outside)
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! A reinforcement-learning style interface over the World, to train agents that terraform the
+//! planet without the GUI. It follows the usual gym shape: `reset(seed)`, then `step(action)`
+//! repeatedly until `done`.
+//!
+//! The action space has a fixed size: each action is a pair of a cell around the ship and one of
+//! the `ACTION_TILES`. Most of them are not allowed at a given moment, see `action_mask()`, and
+//! taking one that is not allowed just wastes the step.
+
+use crate::screen::gui::gui_actions::GuiActions;
+use crate::world::balance::with_balance;
+use crate::world::game_state::get_goal_air_cleaned;
+use crate::world::map::cell::TILE_TYPES;
+use crate::world::map::transform_cells::Transformation;
+use crate::world::map::{CellCubeIterator, CellIndex, Map, MapType, TileType};
+use crate::world::{GameGoalState, TransformationTask, World};
+use std::collections::HashSet;
+
+/// How many cells around the ship are observed and can be transformed, in each direction.
+pub const HORIZONTAL_RADIUS: i32 = 4;
+pub const VERTICAL_RADIUS: i32 = 2;
+/// The dimensions of `Observation::tiles`, as (y, z, x), the last one changing fastest.
+pub const OBSERVATION_SHAPE: [usize; 3] = [
+    (VERTICAL_RADIUS * 2 + 1) as usize,
+    (HORIZONTAL_RADIUS * 2 + 1) as usize,
+    (HORIZONTAL_RADIUS * 2 + 1) as usize,
+];
+pub const OBSERVED_CELLS: usize =
+    OBSERVATION_SHAPE[0] * OBSERVATION_SHAPE[1] * OBSERVATION_SHAPE[2];
+
+/// The tiles that any cell can be transformed into, in the order used by the action indexes.
+/// FloorRock is left out, as it can only come from the tiles that generated maps don't have
+/// around the ship.
+pub const ACTION_TILES: [TileType; 7] = [
+    TileType::Air,
+    TileType::WallRock,
+    TileType::TreeHealthy,
+    TileType::Wire,
+    TileType::MachineAirCleaner,
+    TileType::MachineStorage,
+    TileType::MachineSolarPanel,
+];
+pub const ACTION_COUNT: usize = OBSERVED_CELLS * ACTION_TILES.len();
+
+/// The meaning of each value of `Observation::stats`.
+pub const STAT_NAMES: [&str; 6] = [
+    "air_cleaned",
+    "stored_resources",
+    "storage_capacity",
+    "network_count",
+    "non_ship_machine_count",
+    "life_count",
+];
+
+/// Enough ticks for the robots to attempt one construction, see `GameState`.
+pub const TICKS_PER_STEP: i32 = 15;
+pub const DEFAULT_MAX_STEPS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    /// the position in `TILE_TYPES` of the tile of each cell around the ship, with the shape
+    /// `OBSERVATION_SHAPE`. Cells outside of the map are `Unset`.
+    pub tiles: Vec<u8>,
+    /// in the order of `STAT_NAMES`
+    pub stats: Vec<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    /// the goal was finished or the maximum amount of steps was reached
+    pub done: bool,
+}
+
+pub struct Environment {
+    pub world: World,
+    map_type: MapType,
+    /// the cells that can be observed and transformed, in the order of the observation
+    cells: Vec<CellIndex>,
+    steps: usize,
+    pub max_steps: usize,
+    previous_air_cleaned: f64,
+    previous_life_count: usize,
+}
+
+impl Environment {
+    /// Starts the episode of the seed 0. Use `reset()` to choose another seed.
+    pub fn new(map_type: MapType) -> Self {
+        let mut environment = Self {
+            world: episode_world(map_type, 0),
+            map_type,
+            cells: Vec::new(),
+            steps: 0,
+            max_steps: DEFAULT_MAX_STEPS,
+            previous_air_cleaned: 0.0,
+            previous_life_count: 0,
+        };
+        environment.start_episode();
+        environment
+    }
+
+    /// Starts a new episode in a new map. The same seed gives the same episode.
+    pub fn reset(&mut self, seed: i64) -> Observation {
+        self.world = episode_world(self.map_type, seed);
+        self.start_episode();
+        self.observe()
+    }
+
+    fn start_episode(&mut self) {
+        let ship = self.world.map.get_ship_position().unwrap();
+        let radius = CellIndex::new(HORIZONTAL_RADIUS, VERTICAL_RADIUS, HORIZONTAL_RADIUS);
+        self.cells = CellCubeIterator::new(ship - radius, ship + radius).collect();
+        self.steps = 0;
+        self.previous_air_cleaned = self.world.networks.get_total_air_cleaned();
+        self.previous_life_count = self.world.life.len();
+    }
+
+    /// The cell and transformation of an action index, which must be below `ACTION_COUNT`.
+    pub fn action(&self, action: usize) -> (CellIndex, Transformation) {
+        assert!(
+            action < ACTION_COUNT,
+            "action {} is out of the action space of size {}",
+            action,
+            ACTION_COUNT
+        );
+        let cell = self.cells[action / ACTION_TILES.len()];
+        let tile = ACTION_TILES[action % ACTION_TILES.len()];
+        (cell, Transformation::to(tile))
+    }
+
+    /// Which actions are allowed right now, indexed like the actions. An allowed action can still
+    /// fail, e.g. if there is not enough material.
+    pub fn action_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; ACTION_COUNT];
+        for (cell_position, cell) in self.cells.iter().enumerate() {
+            for transformation in self.allowed_transformations(*cell) {
+                let tile_position = ACTION_TILES
+                    .iter()
+                    .position(|tile| *tile == transformation.new_tile_type);
+                if let Some(tile_position) = tile_position {
+                    mask[cell_position * ACTION_TILES.len() + tile_position] = true;
+                }
+            }
+        }
+        mask
+    }
+
+    /// Like `action_mask()`, for a single action.
+    pub fn is_allowed(&self, action: usize) -> bool {
+        let (cell, transformation) = self.action(action);
+        self.allowed_transformations(cell).contains(&transformation)
+    }
+
+    fn allowed_transformations(&self, cell: CellIndex) -> Vec<Transformation> {
+        let in_map = self
+            .world
+            .map
+            .get_cell_optional(cell)
+            .map_or(false, |cell| cell.tile_type != TileType::Unset);
+        if in_map && self.world.networks.is_adjacent_to_ship_network(cell) {
+            self.world.allowed_transformations(&HashSet::from([cell]))
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Queues the transformation like the player would, and advances the simulation until the
+    /// robots attempted it. If it was blocked, it's dropped instead of being retried. An action
+    /// that is not allowed is not queued, but the simulation still advances.
+    pub fn step(&mut self, action: usize) -> Step {
+        let (cell, transformation) = self.action(action);
+        let task = if self.is_allowed(action) {
+            Some(TransformationTask::new(
+                HashSet::from([cell]),
+                transformation,
+            ))
+        } else {
+            None
+        };
+        let gui_actions = GuiActions {
+            selected_cell_transformation: task,
+            ..GuiActions::default()
+        };
+        self.world.update_with_gui_actions(&gui_actions);
+        for _ in 0..TICKS_PER_STEP {
+            self.world.tick();
+        }
+        self.world.task_queue.clear();
+        self.steps += 1;
+
+        let air_cleaned = self.world.networks.get_total_air_cleaned();
+        let life_count = self.world.life.len();
+        let reward = reward(
+            air_cleaned - self.previous_air_cleaned,
+            life_count as f64 - self.previous_life_count as f64,
+        );
+        self.previous_air_cleaned = air_cleaned;
+        self.previous_life_count = life_count;
+        let finished = matches!(
+            self.world.goal_state,
+            GameGoalState::Finished(_) | GameGoalState::PostFinished
+        );
+        Step {
+            observation: self.observe(),
+            reward,
+            done: finished || self.steps >= self.max_steps,
+        }
+    }
+
+    pub fn observe(&self) -> Observation {
+        let map = &self.world.map;
+        let tiles = self
+            .cells
+            .iter()
+            .map(|cell| {
+                let tile = map
+                    .get_cell_optional(*cell)
+                    .map(|cell| cell.tile_type)
+                    .unwrap_or(TileType::Unset);
+                TILE_TYPES.iter().position(|t| *t == tile).unwrap() as u8
+            })
+            .collect();
+        let networks = &self.world.networks;
+        let stats = vec![
+            networks.get_total_air_cleaned() as f32,
+            networks.get_stored_resources() as f32,
+            networks.get_storage_capacity() as f32,
+            networks.len() as f32,
+            networks.get_non_ship_machine_count() as f32,
+            self.world.life.len() as f32,
+        ];
+        Observation { tiles, stats }
+    }
+}
+
+fn episode_world(map_type: MapType, seed: i64) -> World {
+    let map = Map::new_generated_with_seed(map_type, Some(seed));
+    let mut world = World::new_with_map(false, false, map);
+    // skip the introduction, so that the trees age and the goals can be reached
+    world.goal_state = GameGoalState::Started;
+    world
+}
+
+/// Scaled so that reaching each of the two goals adds up to a reward of 1.
+fn reward(air_cleaned_diff: f64, life_count_diff: f64) -> f64 {
+    let life_goal = with_balance(|balance| balance.life_count_required_for_winning) as f64;
+    air_cleaned_diff / get_goal_air_cleaned() + life_count_diff / life_goal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_action(environment: &Environment, cell: CellIndex, tile: TileType) -> usize {
+        (0..ACTION_COUNT)
+            .find(|action| environment.action(*action) == (cell, Transformation::to(tile)))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reset_is_deterministic() {
+        let mut environment = Environment::new(MapType::Simplex);
+        let first = environment.reset(7);
+        assert_eq!(first.tiles.len(), OBSERVED_CELLS);
+        assert_eq!(first.stats.len(), STAT_NAMES.len());
+        let mut other = Environment::new(MapType::Simplex);
+        assert_eq!(other.reset(7), first);
+    }
+
+    #[test]
+    fn test_action_mask() {
+        let environment = Environment::new(MapType::Island);
+        let mask = environment.action_mask();
+        assert_eq!(mask.len(), ACTION_COUNT);
+        let allowed = mask.iter().filter(|allowed| **allowed).count();
+        assert!(allowed > 0);
+        assert!(allowed < ACTION_COUNT, "far cells can not be transformed");
+        let ship = environment.world.map.get_ship_position().unwrap();
+        let far_away = ship + CellIndex::new(HORIZONTAL_RADIUS, 0, HORIZONTAL_RADIUS);
+        for tile in ACTION_TILES {
+            assert!(!mask[find_action(&environment, far_away, tile)]);
+        }
+    }
+
+    #[test]
+    fn test_planting_trees_is_rewarded() {
+        let mut environment = Environment::new(MapType::Island);
+        let ship = environment.world.map.get_ship_position().unwrap();
+        let next_to_ship = ship + CellIndex::new(1, 0, 0);
+        let action = find_action(&environment, next_to_ship, TileType::TreeHealthy);
+        assert!(environment.action_mask()[action]);
+
+        let step = environment.step(action);
+
+        assert_eq!(
+            environment.world.map.get_cell(next_to_ship).tile_type,
+            TileType::TreeHealthy
+        );
+        assert!(step.reward > 0.0);
+        assert!(!step.done);
+        assert!(environment.world.task_queue.is_empty());
+    }
+
+    #[test]
+    fn test_every_action_can_be_stepped() {
+        let mut environment = Environment::new(MapType::Island);
+        environment.max_steps = ACTION_COUNT;
+        let mask = environment.action_mask();
+        for action in 0..ACTION_COUNT {
+            assert_eq!(environment.is_allowed(action), mask[action]);
+        }
+        for action in 0..ACTION_COUNT {
+            environment.step(action);
+        }
+        assert_eq!(environment.world.validate(), Vec::new());
+    }
+
+    #[test]
+    fn test_episode_ends() {
+        let mut environment = Environment::new(MapType::Island);
+        environment.max_steps = 2;
+        let ship = environment.world.map.get_ship_position().unwrap();
+        let far_away = ship + CellIndex::new(HORIZONTAL_RADIUS, 0, 0);
+        // out of the reach of the ship, so this does nothing
+        let action = find_action(&environment, far_away, TileType::TreeHealthy);
+        assert!(!environment.action_mask()[action]);
+        assert!(!environment.step(action).done);
+        let step = environment.step(action);
+        assert_eq!(step.reward, 0.0);
+        assert!(step.done);
+    }
+}
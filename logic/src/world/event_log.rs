@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+
+use crate::world::map::transform_cells::TransformationFailure;
+use crate::world::map::{CellIndex, TileType};
+use crate::world::{AgeInMinutes, GameGoalState};
+
+/// Older events are dropped to keep the memory bounded.
+pub const MAX_EVENTS: usize = 200;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventKind {
+    TaskCompleted(TileType),
+    TaskBlocked(TileType, Vec<TransformationFailure>),
+    Undone(TileType),
+    Redone(TileType),
+    /// an undo or redo was dropped because its cells changed since it was recorded
+    HistoryOutdated(TileType),
+    NetworksMerged(usize),
+    NetworkLostPower,
+    TreeDied,
+    GoalStateChanged(GameGoalState),
+    /// the name of the objective, see `crate::world::objectives`
+    ObjectiveCompleted(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub age_in_minutes: AgeInMinutes,
+    pub kind: EventKind,
+    /// the cell involved, if any, so that the camera can go there
+    pub position: Option<CellIndex>,
+}
+
+pub struct EventLog {
+    events: VecDeque<Event>,
+    /// how many events were pushed since the start, including the dropped and cleared ones
+    pushed_count: usize,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            pushed_count: 0,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        age_in_minutes: AgeInMinutes,
+        kind: EventKind,
+        position: Option<CellIndex>,
+    ) {
+        if self.events.len() >= MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event {
+            age_in_minutes,
+            kind,
+            position,
+        });
+        self.pushed_count += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// most recent events first
+    pub fn iter_newest(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter().rev()
+    }
+
+    pub fn pushed_count(&self) -> usize {
+        self.pushed_count
+    }
+
+    /// the events pushed after `pushed_count()` returned `previous_count`, oldest first, as long
+    /// as they were not dropped or cleared
+    pub fn iter_since(&self, previous_count: usize) -> impl Iterator<Item = &Event> {
+        let new_events = self.pushed_count.saturating_sub(previous_count);
+        let skipped = self.events.len().saturating_sub(new_events);
+        self.events.iter().skip(skipped)
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+/// Sorts the reasons so that the same set of reasons always gives the same event.
+pub fn sorted_reasons<'a>(
+    reasons: impl Iterator<Item = &'a TransformationFailure>,
+) -> Vec<TransformationFailure> {
+    let mut reasons = reasons.cloned().collect::<Vec<_>>();
+    reasons.sort();
+    reasons
+}
+
+/// The lowest cell, to choose a deterministic position among a set of cells.
+pub fn representative_cell<'a>(cells: impl Iterator<Item = &'a CellIndex>) -> Option<CellIndex> {
+    cells.min_by_key(|cell| (cell.y, cell.x, cell.z)).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_is_bounded() {
+        let mut log = EventLog::new();
+        for i in 0..(MAX_EVENTS + 5) {
+            log.push(i as AgeInMinutes, EventKind::TreeDied, None);
+        }
+        assert_eq!(log.len(), MAX_EVENTS);
+        let newest = log.iter_newest().next().unwrap();
+        assert_eq!(newest.age_in_minutes, (MAX_EVENTS + 4) as AgeInMinutes);
+    }
+
+    #[test]
+    fn test_iter_since() {
+        let mut log = EventLog::new();
+        log.push(1, EventKind::TreeDied, None);
+        let count = log.pushed_count();
+        assert_eq!(log.iter_since(count).count(), 0);
+        log.push(2, EventKind::NetworkLostPower, None);
+        log.push(3, EventKind::TreeDied, None);
+        let ages = log
+            .iter_since(count)
+            .map(|event| event.age_in_minutes)
+            .collect::<Vec<_>>();
+        assert_eq!(ages, vec![2, 3]);
+        log.clear();
+        assert_eq!(log.iter_since(count).count(), 0);
+    }
+
+    #[test]
+    fn test_representative_cell() {
+        let cells = [
+            CellIndex::new(3, 0, 0),
+            CellIndex::new(1, 2, 0),
+            CellIndex::new(0, 0, 5),
+        ];
+        assert_eq!(
+            representative_cell(cells.iter()),
+            Some(CellIndex::new(0, 0, 5))
+        );
+        assert_eq!(representative_cell([].iter()), None);
+    }
+}
@@ -101,7 +101,7 @@ fn advance_fluid_downwards(map: &mut Map) {
     let pressure_threshold = -VERTICAL_PRESSURE_DIFFERENCE;
     let flow = Flow::new(map, pressure_threshold);
     let updated_map = map.clone();
-    let mut iter = updated_map.iter_mut();
+    let mut iter = updated_map.iter_mut_near_fluid();
     while let Option::Some(CellIterItem { cell_index, cell }) = iter.next() {
         if is_floodable_from_above(cell.tile_type) {
             let current_pressure = cell.pressure;
@@ -125,7 +125,7 @@ fn prepare_fluid_sideways(map: &mut Map) {
     let pressure_threshold = 0;
     let flow = Flow::new(map, pressure_threshold);
     let updated_map = map.clone();
-    let mut iter = updated_map.iter_mut();
+    let mut iter = updated_map.iter_mut_near_fluid();
     while let Option::Some(CellIterItem { cell_index, cell }) = iter.next() {
         if is_floodable_horizontal(cell.tile_type) {
             let current_pressure = cell.pressure;
@@ -155,7 +155,7 @@ fn advance_fluid_sideways(map: &mut Map) {
     let pressure_threshold = 0;
     let flow = Flow::new(map, pressure_threshold);
     let updated_map = map.clone();
-    let mut iter = updated_map.iter_mut();
+    let mut iter = updated_map.iter_mut_near_fluid();
     while let Option::Some(CellIterItem { cell_index, cell }) = iter.next() {
         if is_floodable_horizontal(cell.tile_type) {
             let current_pressure = cell.pressure;
@@ -181,7 +181,7 @@ fn advance_fluid_upwards(map: &mut Map) {
     let pressure_threshold = VERTICAL_PRESSURE_DIFFERENCE + 1;
     let flow = Flow::new(map, pressure_threshold);
     let updated_map = map.clone();
-    let mut iter = updated_map.iter_mut();
+    let mut iter = updated_map.iter_mut_near_fluid();
     while let Option::Some(CellIterItem { cell_index, cell }) = iter.next() {
         if is_floodable_from_below(cell.tile_type) {
             let current_pressure = cell.pressure;
@@ -292,7 +292,7 @@ fn is_floodable(cell_index: CellIndex, diff: CellIndex, map: &Map, outwards: boo
 
 fn update_tile_type(map: &mut Map) {
     let updated_map = map.clone();
-    let mut iter = updated_map.iter_mut();
+    let mut iter = updated_map.iter_mut_near_fluid();
     while let Option::Some(CellIterItem { cell_index, cell }) = iter.next() {
         // if is_floodable_horizontal(cell.tile_type) {
         // let nothing_above = {
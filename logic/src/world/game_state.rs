@@ -1,12 +1,63 @@
-use crate::screen::gui::format_units::format_liters;
+use crate::common::snapshot::{Snapshot, SnapshotReader, SnapshotWriter};
 use crate::screen::gui::GuiActions;
+use crate::world::balance::with_balance;
 use mq_basics::now;
 
 pub const DEFAULT_PROFILE_ENABLED: bool = false;
 pub const DEFAULT_ADVANCING_FLUIDS: bool = false;
-const DEFAULT_ADVANCE_FLUID_EVERY_N_FRAMES: i32 = 10;
-const DEFAULT_ADVANCE_ROBOTS_EVERY_N_FRAMES: i32 = 15;
-const DEFAULT_AGING_EVERY_N_FRAMES: i32 = 15;
+const DEFAULT_ADVANCE_FLUID_EVERY_N_TICKS: i64 = 10;
+const DEFAULT_ADVANCE_ROBOTS_EVERY_N_TICKS: i64 = 15;
+const DEFAULT_AGING_EVERY_N_TICKS: i64 = 15;
+
+/// The simulation advances in ticks of a fixed duration, regardless of the rendered frame rate.
+pub const TICKS_PER_SECOND: f64 = 60.0;
+pub const TICK_DURATION_SECONDS: f64 = 1.0 / TICKS_PER_SECOND;
+/// One game minute passes every real second.
+pub const TICKS_PER_GAME_MINUTE: i64 = 60;
+/// If a frame takes too long, the pending ticks above this are dropped so that the game slows
+/// down instead of needing more and more ticks every frame. Multiplied by the game speed.
+const MAX_TICKS_PER_FRAME: i32 = 10;
+
+/// How many ticks are simulated per tick of real time. Rendering is not affected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameSpeed {
+    Normal,
+    Double,
+    Fast,
+    VeryFast,
+}
+
+pub const GAME_SPEEDS: [GameSpeed; 4] = [
+    GameSpeed::Normal,
+    GameSpeed::Double,
+    GameSpeed::Fast,
+    GameSpeed::VeryFast,
+];
+
+impl GameSpeed {
+    pub fn multiplier(&self) -> i32 {
+        match self {
+            GameSpeed::Normal => 1,
+            GameSpeed::Double => 2,
+            GameSpeed::Fast => 5,
+            GameSpeed::VeryFast => 10,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameSpeed::Normal => "1x",
+            GameSpeed::Double => "2x",
+            GameSpeed::Fast => "5x",
+            GameSpeed::VeryFast => "10x",
+        }
+    }
+    /// moves `steps` speeds up (or down if negative), stopping at the slowest and fastest
+    pub fn changed(&self, steps: i32) -> GameSpeed {
+        let index = GAME_SPEEDS.iter().position(|speed| speed == self).unwrap() as i32;
+        let new_index = (index + steps).clamp(0, GAME_SPEEDS.len() as i32 - 1);
+        GAME_SPEEDS[new_index as usize]
+    }
+}
 
 enum Phase {
     Robots = 0,
@@ -15,89 +66,220 @@ enum Phase {
 }
 
 pub struct GameState {
-    pub frame_index: i32,
+    pub frame_index: i64,
+    pub tick_index: i64,
     pub previous_frame_ts: f64,
     pub current_frame_ts: f64,
     pub profile: bool,
+    pub speed: GameSpeed,
+    pub paused: bool,
+    /// run a single tick even if paused
+    step_requested: bool,
+    /// seconds of real time not yet simulated
+    tick_accumulator: f64,
+    /// ignores the real time and runs this amount of ticks per frame. Useful for tests.
+    fixed_ticks_per_frame: Option<i32>,
     advancing_fluids: bool,
     advancing_fluids_single_step: bool,
-    advance_fluid_every_n_frames: i32,
-    advance_robots_every_n_frames: i32,
-    age_every_n_frames: i32,
+    advance_fluid_every_n_ticks: i64,
+    advance_robots_every_n_ticks: i64,
+    age_every_n_ticks: i64,
+    /// the balance file, checked for changes every frame. See `crate::world::balance`.
+    pub balance_path: Option<String>,
+    /// check the invariants of the World after every tick. See `crate::world::validation`.
+    pub validate: bool,
 }
 
 impl GameState {
     pub fn new(fluids: bool) -> GameState {
         GameState {
             frame_index: 0,
+            tick_index: 0,
             previous_frame_ts: now() - 1.0,
             current_frame_ts: now(),
+            speed: GameSpeed::Normal,
+            paused: false,
+            step_requested: false,
+            tick_accumulator: 0.0,
+            fixed_ticks_per_frame: None,
             advancing_fluids: fluids,
             advancing_fluids_single_step: false,
-            advance_fluid_every_n_frames: DEFAULT_ADVANCE_FLUID_EVERY_N_FRAMES,
-            advance_robots_every_n_frames: DEFAULT_ADVANCE_ROBOTS_EVERY_N_FRAMES,
+            advance_fluid_every_n_ticks: DEFAULT_ADVANCE_FLUID_EVERY_N_TICKS,
+            advance_robots_every_n_ticks: DEFAULT_ADVANCE_ROBOTS_EVERY_N_TICKS,
             profile: DEFAULT_PROFILE_ENABLED,
-            age_every_n_frames: DEFAULT_AGING_EVERY_N_FRAMES,
+            age_every_n_ticks: DEFAULT_AGING_EVERY_N_TICKS,
+            balance_path: None,
+            validate: false,
         }
     }
 
+    pub fn toggle_fluids(&mut self) {
+        self.advancing_fluids = !self.advancing_fluids;
+    }
+
+    pub fn is_advancing_fluids(&self) -> bool {
+        self.advancing_fluids
+    }
+
     pub fn update_with_gui_actions(&mut self, gui_actions: &GuiActions) {
         if gui_actions.toggle_fluids {
-            self.advancing_fluids = !self.advancing_fluids;
+            self.toggle_fluids();
+        }
+        if gui_actions.single_fluid {
+            // consumed by the next tick, even if this frame doesn't run any
+            self.advancing_fluids_single_step = true;
+        }
+        if gui_actions.toggle_pause {
+            self.paused = !self.paused;
+        }
+        if gui_actions.step_tick {
+            // stepping only makes sense if the game doesn't advance by itself
+            self.paused = true;
+            self.step_requested = true;
         }
+        if let Some(speed) = gui_actions.set_speed {
+            self.speed = speed;
+        }
+        self.speed = self.speed.changed(gui_actions.change_speed_rel);
+    }
 
-        self.advancing_fluids_single_step = gui_actions.single_fluid;
+    /// "paused" or the speed multiplier, for the HUD
+    pub fn get_speed_str(&self) -> String {
+        if self.paused {
+            "paused".to_string()
+        } else {
+            format!("speed {}", self.speed.name())
+        }
     }
 
-    pub fn should_advance_robots_this_frame(&self) -> bool {
-        let should_process_frame = (self.frame_index + self.advance_robots_every_n_frames
-            - Phase::Robots as i32)
-            % self.advance_robots_every_n_frames
-            == 0;
-        should_process_frame
+    /// How many ticks to simulate for the last rendered frame. Call it once per frame.
+    pub fn ticks_to_run(&mut self) -> i32 {
+        if self.paused {
+            // don't catch up with the paused time when resuming
+            self.tick_accumulator = 0.0;
+            let ticks = if self.step_requested { 1 } else { 0 };
+            self.step_requested = false;
+            return ticks;
+        }
+        self.step_requested = false;
+        let multiplier = self.speed.multiplier();
+        if let Some(ticks) = self.fixed_ticks_per_frame {
+            return ticks * multiplier;
+        }
+        let frame_duration = self.current_frame_ts - self.previous_frame_ts;
+        take_ticks(
+            &mut self.tick_accumulator,
+            frame_duration * multiplier as f64,
+            MAX_TICKS_PER_FRAME * multiplier,
+        )
+    }
+
+    pub fn should_advance_robots_this_tick(&self) -> bool {
+        self.is_phase_tick(Phase::Robots, self.advance_robots_every_n_ticks)
     }
 
-    pub fn should_advance_fluids_this_frame(&self) -> bool {
+    pub fn should_advance_fluids_this_tick(&mut self) -> bool {
         if self.advancing_fluids_single_step {
-            return true;
+            self.advancing_fluids_single_step = false;
+            true
         } else {
-            if self.advancing_fluids {
-                let should_process_frame = (self.frame_index + self.advance_fluid_every_n_frames
-                    - Phase::Fluids as i32)
-                    % self.advance_fluid_every_n_frames
-                    == 0;
-                return should_process_frame;
-            }
+            self.advancing_fluids
+                && self.is_phase_tick(Phase::Fluids, self.advance_fluid_every_n_ticks)
         }
-        return false;
     }
 
-    pub fn should_age_this_frame(&self) -> bool {
-        let should_age = (self.frame_index + self.age_every_n_frames - Phase::Aging as i32)
-            % self.age_every_n_frames
-            == 0;
-        return should_age;
+    pub fn should_age_this_tick(&self) -> bool {
+        self.is_phase_tick(Phase::Aging, self.age_every_n_ticks)
     }
 
+    /// Each phase runs every n ticks, and they are shifted so that they don't all run in the
+    /// same tick.
+    fn is_phase_tick(&self, phase: Phase, every_n_ticks: i64) -> bool {
+        (self.tick_index + every_n_ticks - phase as i64) % every_n_ticks == 0
+    }
+
+    /// Runs every phase in every tick, and one tick per frame.
     pub fn set_advance_every_frame(&mut self) {
-        self.advance_robots_every_n_frames = 1;
-        self.advance_fluid_every_n_frames = 1;
-        self.age_every_n_frames = 1;
+        self.advance_robots_every_n_ticks = 1;
+        self.advance_fluid_every_n_ticks = 1;
+        self.age_every_n_ticks = 1;
+        self.fixed_ticks_per_frame = Some(1);
+    }
+
+    /// returns true if a game minute passed
+    pub fn advance_tick(&mut self) -> bool {
+        self.tick_index += 1;
+        self.tick_index % TICKS_PER_GAME_MINUTE == 0
     }
 
     pub fn advance_frame(&mut self) {
-        self.frame_index = (self.frame_index + 1) % 3600;
+        self.frame_index += 1;
         self.previous_frame_ts = self.current_frame_ts;
         self.current_frame_ts = now();
     }
 }
 
-pub fn get_goal_air_cleaned() -> f64 {
-    100_000.0
+impl Snapshot for GameState {
+    fn save(&self, snapshot: &mut SnapshotWriter) {
+        snapshot.line("game_state.frame_index", self.frame_index);
+        snapshot.line("game_state.tick_index", self.tick_index);
+        snapshot.line("game_state.profile", self.profile);
+        snapshot.line("game_state.speed", format!("{:?}", self.speed));
+        snapshot.line("game_state.paused", self.paused);
+        snapshot.line("game_state.advancing_fluids", self.advancing_fluids);
+        if let Some(path) = &self.balance_path {
+            snapshot.line("game_state.balance_path", path);
+        }
+        snapshot.line("game_state.validate", self.validate);
+    }
+
+    fn load(snapshot: &SnapshotReader) -> Result<Self, String> {
+        let mut game_state = GameState::new(snapshot.get("game_state.advancing_fluids")?);
+        game_state.frame_index = snapshot.get("game_state.frame_index")?;
+        game_state.tick_index = snapshot.get("game_state.tick_index")?;
+        game_state.profile = snapshot.get("game_state.profile")?;
+        let speed = snapshot.get::<String>("game_state.speed")?;
+        game_state.speed = *GAME_SPEEDS
+            .iter()
+            .find(|candidate| format!("{:?}", candidate) == speed)
+            .ok_or_else(|| format!("'game_state.speed' has an unknown value '{}'", speed))?;
+        game_state.paused = snapshot.get("game_state.paused")?;
+        game_state.balance_path = snapshot
+            .get_all("game_state.balance_path")
+            .first()
+            .map(|words| words.join(" "));
+        game_state.validate = snapshot.get_or("game_state.validate", false)?;
+        // the time spent reloading should not be simulated
+        game_state.previous_frame_ts = game_state.current_frame_ts;
+        Ok(game_state)
+    }
 }
 
-pub fn get_goal_air_cleaned_str() -> String {
-    format_liters(get_goal_air_cleaned())
+/// Adds the elapsed real time and returns how many whole ticks fit in it, keeping the rest for
+/// the next frames.
+fn take_ticks(tick_accumulator: &mut f64, elapsed_seconds: f64, max_ticks: i32) -> i32 {
+    *tick_accumulator += elapsed_seconds;
+    let mut ticks = 0;
+    while *tick_accumulator >= TICK_DURATION_SECONDS && ticks < max_ticks {
+        *tick_accumulator -= TICK_DURATION_SECONDS;
+        ticks += 1;
+    }
+    if ticks == max_ticks {
+        *tick_accumulator = tick_accumulator.min(TICK_DURATION_SECONDS);
+    }
+    ticks
+}
+
+pub fn ticks_to_seconds(ticks: i64) -> f64 {
+    ticks as f64 * TICK_DURATION_SECONDS
+}
+
+pub fn ticks_to_game_minutes(ticks: i64) -> i64 {
+    ticks / TICKS_PER_GAME_MINUTE
+}
+
+pub fn get_goal_air_cleaned() -> f64 {
+    with_balance(|balance| balance.goal_air_cleaned)
 }
 
 #[cfg(test)]
@@ -107,80 +289,91 @@ mod tests {
     #[test]
     fn test_advance_robots() {
         let mut game_state = GameState::new(true);
-        assert_eq!(game_state.should_advance_robots_this_frame(), true);
-        game_state.advance_frame();
-        for i in 1..DEFAULT_ADVANCE_ROBOTS_EVERY_N_FRAMES {
+        assert_eq!(game_state.should_advance_robots_this_tick(), true);
+        game_state.advance_tick();
+        for i in 1..DEFAULT_ADVANCE_ROBOTS_EVERY_N_TICKS {
             assert_eq!(
-                game_state.should_advance_robots_this_frame(),
+                game_state.should_advance_robots_this_tick(),
                 false,
                 "on iteration {}",
                 i
             );
-            game_state.advance_frame();
+            game_state.advance_tick();
         }
-        assert_eq!(game_state.should_advance_robots_this_frame(), true);
+        assert_eq!(game_state.should_advance_robots_this_tick(), true);
     }
 
     #[test]
     fn test_advance_robots_every_frame() {
         let mut game_state = GameState::new(true);
         game_state.set_advance_every_frame();
-        assert_eq!(game_state.should_advance_robots_this_frame(), true);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_advance_robots_this_frame(), true);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_advance_robots_this_frame(), true);
+        assert_eq!(game_state.ticks_to_run(), 1);
+        assert_eq!(game_state.should_advance_robots_this_tick(), true);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_advance_robots_this_tick(), true);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_advance_robots_this_tick(), true);
     }
 
     #[test]
     fn test_advance_fluids() {
         let mut game_state = GameState::new(true);
-        assert_eq!(game_state.should_advance_fluids_this_frame(), false);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_advance_fluids_this_frame(), true);
-        game_state.advance_frame();
-        for i in 1..DEFAULT_ADVANCE_FLUID_EVERY_N_FRAMES {
+        assert_eq!(game_state.should_advance_fluids_this_tick(), false);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_advance_fluids_this_tick(), true);
+        game_state.advance_tick();
+        for i in 1..DEFAULT_ADVANCE_FLUID_EVERY_N_TICKS {
             assert_eq!(
-                game_state.should_advance_fluids_this_frame(),
+                game_state.should_advance_fluids_this_tick(),
                 false,
                 "on iteration {}",
                 i
             );
-            game_state.advance_frame();
+            game_state.advance_tick();
         }
-        assert_eq!(game_state.should_advance_fluids_this_frame(), true);
+        assert_eq!(game_state.should_advance_fluids_this_tick(), true);
     }
 
     #[test]
     fn test_advance_fluids_every_frame() {
         let mut game_state = GameState::new(true);
         game_state.set_advance_every_frame();
-        assert_eq!(game_state.should_advance_fluids_this_frame(), true);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_advance_fluids_this_frame(), true);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_advance_fluids_this_frame(), true);
+        assert_eq!(game_state.should_advance_fluids_this_tick(), true);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_advance_fluids_this_tick(), true);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_advance_fluids_this_tick(), true);
+    }
+
+    #[test]
+    fn test_single_fluid_step() {
+        let mut game_state = GameState::new(false);
+        let mut gui_actions = GuiActions::default();
+        gui_actions.single_fluid = true;
+        game_state.update_with_gui_actions(&gui_actions);
+        assert_eq!(game_state.should_advance_fluids_this_tick(), true);
+        assert_eq!(game_state.should_advance_fluids_this_tick(), false);
     }
 
     #[test]
     fn test_age() {
         let mut game_state = GameState::new(true);
-        assert_eq!(game_state.should_age_this_frame(), false);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_age_this_frame(), false);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_age_this_frame(), true);
-        game_state.advance_frame();
-        for i in 1..DEFAULT_AGING_EVERY_N_FRAMES {
+        assert_eq!(game_state.should_age_this_tick(), false);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_age_this_tick(), false);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_age_this_tick(), true);
+        game_state.advance_tick();
+        for i in 1..DEFAULT_AGING_EVERY_N_TICKS {
             assert_eq!(
-                game_state.should_age_this_frame(),
+                game_state.should_age_this_tick(),
                 false,
                 "on iteration {}",
                 i
             );
-            game_state.advance_frame();
+            game_state.advance_tick();
         }
-        assert_eq!(game_state.should_age_this_frame(), true);
+        assert_eq!(game_state.should_age_this_tick(), true);
     }
 
     #[test]
@@ -188,10 +381,75 @@ mod tests {
         let mut game_state = GameState::new(true);
         game_state.set_advance_every_frame();
 
-        assert_eq!(game_state.should_age_this_frame(), true);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_age_this_frame(), true);
-        game_state.advance_frame();
-        assert_eq!(game_state.should_age_this_frame(), true);
+        assert_eq!(game_state.should_age_this_tick(), true);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_age_this_tick(), true);
+        game_state.advance_tick();
+        assert_eq!(game_state.should_age_this_tick(), true);
+    }
+
+    #[test]
+    fn test_ticks_do_not_depend_on_frame_rate() {
+        let mut slow_accumulator = 0.0;
+        let mut slow_ticks = 0;
+        for _ in 0..30 {
+            slow_ticks += take_ticks(&mut slow_accumulator, 1.0 / 30.0, MAX_TICKS_PER_FRAME);
+        }
+        let mut fast_accumulator = 0.0;
+        let mut fast_ticks = 0;
+        for _ in 0..120 {
+            fast_ticks += take_ticks(&mut fast_accumulator, 1.0 / 120.0, MAX_TICKS_PER_FRAME);
+        }
+        assert!((slow_ticks - TICKS_PER_SECOND as i32).abs() <= 1);
+        assert!((fast_ticks - TICKS_PER_SECOND as i32).abs() <= 1);
+    }
+
+    #[test]
+    fn test_long_frames_drop_ticks() {
+        let mut accumulator = 0.0;
+        assert_eq!(
+            take_ticks(&mut accumulator, 10.0, MAX_TICKS_PER_FRAME),
+            MAX_TICKS_PER_FRAME
+        );
+        assert!(take_ticks(&mut accumulator, 0.0, MAX_TICKS_PER_FRAME) <= 1);
+    }
+
+    #[test]
+    fn test_pause_and_step() {
+        let mut game_state = GameState::new(true);
+        game_state.set_advance_every_frame();
+        let mut gui_actions = GuiActions::default();
+        gui_actions.toggle_pause = true;
+        game_state.update_with_gui_actions(&gui_actions);
+        assert_eq!(game_state.ticks_to_run(), 0);
+
+        let mut gui_actions = GuiActions::default();
+        gui_actions.step_tick = true;
+        game_state.update_with_gui_actions(&gui_actions);
+        assert_eq!(game_state.ticks_to_run(), 1);
+        assert_eq!(game_state.ticks_to_run(), 0);
+        assert_eq!(game_state.paused, true);
+    }
+
+    #[test]
+    fn test_speed_multiplies_ticks() {
+        let mut game_state = GameState::new(true);
+        game_state.set_advance_every_frame();
+        let mut gui_actions = GuiActions::default();
+        gui_actions.set_speed = Some(GameSpeed::Fast);
+        game_state.update_with_gui_actions(&gui_actions);
+        assert_eq!(game_state.ticks_to_run(), 5);
+
+        let mut gui_actions = GuiActions::default();
+        gui_actions.change_speed_rel = 10;
+        game_state.update_with_gui_actions(&gui_actions);
+        assert_eq!(game_state.speed, GameSpeed::VeryFast);
+        assert_eq!(game_state.speed.changed(-10), GameSpeed::Normal);
+    }
+
+    #[test]
+    fn test_tick_conversions() {
+        assert_eq!(ticks_to_seconds(TICKS_PER_SECOND as i64), 1.0);
+        assert_eq!(ticks_to_game_minutes(TICKS_PER_GAME_MINUTE * 3 + 1), 3);
     }
 }
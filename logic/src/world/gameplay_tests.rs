@@ -1,100 +1,119 @@
 #[cfg(test)]
 mod game_goal_state_transition_tests {
+    use crate::world::balance::with_balance;
     use crate::world::game_state::get_goal_air_cleaned;
-    use crate::world::map::cell::DEFAULT_HEALTH;
+    use crate::world::map::cell::default_health;
     use crate::world::map::{Cell, CellIndex, TileType};
-    use crate::world::networks::Networks;
-    use crate::world::{age_tile, transition_goal_state, GameGoalState};
+    use crate::world::objectives::ObjectiveStatus;
+    use crate::world::{age_tile, GameGoalState, World};
+
+    fn started_world() -> World {
+        let mut world = World::new();
+        world.goal_state = GameGoalState::Started;
+        world.age_in_minutes = 100;
+        world
+    }
+
+    fn add_life(world: &mut World) {
+        for i in 0..with_balance(|balance| balance.life_count_required_for_winning) {
+            world.life.insert(CellIndex::new(i as i32, 0, 0));
+        }
+    }
 
     #[test]
     fn test_starting() {
-        let networks = Networks::new_default();
-        let mut current_goal = GameGoalState::Started;
+        let mut world = started_world();
 
-        transition_goal_state(&mut current_goal, &networks, 0, 100);
+        world.update_objectives();
 
-        assert_eq!(current_goal, GameGoalState::Started);
+        assert_eq!(world.goal_state, GameGoalState::Started);
+        assert_eq!(world.objectives.status(0), ObjectiveStatus::Active);
     }
 
     #[test]
     fn test_producing() {
-        let mut networks = Networks::new_default();
-        networks.set_production(get_goal_air_cleaned() / 2.0);
-        let mut current_goal = GameGoalState::Started;
+        let mut world = started_world();
+        world.networks.set_production(get_goal_air_cleaned() / 2.0);
 
-        transition_goal_state(&mut current_goal, &networks, 0, 100);
+        world.update_objectives();
 
-        assert_eq!(current_goal, GameGoalState::Started);
+        assert_eq!(world.goal_state, GameGoalState::Started);
+        assert_eq!(world.objectives.status(0), ObjectiveStatus::Active);
     }
 
     #[test]
     fn test_reaching_production() {
-        let mut networks = Networks::new_default();
-        networks.set_production(get_goal_air_cleaned());
-        let mut current_goal = GameGoalState::Started;
+        let mut world = started_world();
+        world.networks.set_production(get_goal_air_cleaned());
 
-        transition_goal_state(&mut current_goal, &networks, 0, 100);
+        world.update_objectives();
 
-        assert_eq!(current_goal, GameGoalState::ReachedProduction);
+        assert_eq!(world.goal_state, GameGoalState::Started);
+        assert_eq!(world.objectives.status(0), ObjectiveStatus::Completed(100));
+        assert!(world.objectives.stops_aging());
     }
 
     #[test]
     fn test_dismantling() {
-        let mut networks = Networks::new_default();
-        networks.add(
+        let mut world = started_world();
+        world.networks.add(
             CellIndex::default(),
             TileType::MachineAirCleaner,
             TileType::Air,
         );
-        networks.set_production(get_goal_air_cleaned());
-        let mut current_goal = GameGoalState::ReachedProduction;
+        world.networks.set_production(get_goal_air_cleaned());
+        add_life(&mut world);
 
-        transition_goal_state(&mut current_goal, &networks, 0, 100);
+        world.update_objectives();
+        world.update_objectives();
 
-        assert_eq!(current_goal, GameGoalState::ReachedProduction);
+        assert_eq!(world.goal_state, GameGoalState::Started);
+        assert_eq!(world.objectives.status(1), ObjectiveStatus::Active);
     }
 
     #[test]
     fn test_finishing() {
-        let mut networks = Networks::new_default();
-        networks.set_production(get_goal_air_cleaned());
-        let mut current_goal = GameGoalState::ReachedProduction;
+        let mut world = started_world();
+        world.networks.set_production(get_goal_air_cleaned());
+        add_life(&mut world);
 
-        transition_goal_state(&mut current_goal, &networks, 50, 100);
+        world.update_objectives();
+        world.update_objectives();
 
-        assert_eq!(current_goal, GameGoalState::Finished(100));
+        assert_eq!(world.goal_state, GameGoalState::Finished(100));
     }
 
     #[test]
     fn test_trees_decay() {
         let mut cell = Cell::new(TileType::TreeHealthy);
-        cell.health = DEFAULT_HEALTH;
-        age_tile(&mut cell, GameGoalState::Started);
-        assert_eq!(cell.health, DEFAULT_HEALTH - 1);
+        cell.health = default_health();
+        age_tile(&mut cell, true);
+        assert_eq!(cell.health, default_health() - 1);
     }
 
     #[test]
     fn test_trees_transition() {
         let mut cell = Cell::new(TileType::TreeHealthy);
         cell.health = 0;
-        age_tile(&mut cell, GameGoalState::Started);
-        assert_eq!(cell.health, DEFAULT_HEALTH);
+        age_tile(&mut cell, true);
+        assert_eq!(cell.health, default_health());
         assert_eq!(cell.tile_type, TileType::TreeSparse);
     }
 
     #[test]
     fn test_trees_do_not_decay_when_air_is_clean() {
         let mut cell = Cell::new(TileType::TreeHealthy);
-        cell.health = DEFAULT_HEALTH;
-        age_tile(&mut cell, GameGoalState::ReachedProduction);
-        assert_eq!(cell.health, DEFAULT_HEALTH);
+        cell.health = default_health();
+        age_tile(&mut cell, false);
+        assert_eq!(cell.health, default_health());
     }
 }
 
 #[cfg(test)]
 mod building_tests {
     use crate::screen::gui::GuiActions;
-    use crate::world::map::cell::DEFAULT_HEALTH;
+    use crate::world::event_log::EventKind;
+    use crate::world::map::cell::default_health;
     use crate::world::map::transform_cells::Transformation;
     use crate::world::map::{CellIndex, TileType};
     use crate::world::{GameGoalState, TransformationTask, World};
@@ -115,6 +134,11 @@ mod building_tests {
         gui_actions
     }
 
+    fn update_and_validate(world: &mut World, gui_actions: GuiActions) {
+        world.update(gui_actions);
+        assert_eq!(world.validate(), Vec::new());
+    }
+
     #[test]
     fn test_build_machine_next_to_ship() {
         let mut world = World::new();
@@ -124,7 +148,7 @@ mod building_tests {
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
 
         let gui_actions = gui_action_transform_tile(cell, to_tile);
-        world.update(gui_actions);
+        update_and_validate(&mut world, gui_actions);
         assert_eq!(world.map.get_cell(cell).tile_type, to_tile);
     }
 
@@ -137,7 +161,7 @@ mod building_tests {
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
 
         let gui_actions = gui_action_transform_tile(cell, to_tile);
-        world.update(gui_actions);
+        update_and_validate(&mut world, gui_actions);
         assert_eq!(world.map.get_cell(cell).tile_type, to_tile);
     }
 
@@ -151,12 +175,12 @@ mod building_tests {
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
 
         let gui_actions = gui_action_transform_tile(cell, to_tile);
-        world.update(gui_actions);
+        update_and_validate(&mut world, gui_actions);
         assert_eq!(world.map.get_cell(cell).tile_type, to_tile);
 
         let to_tile = TileType::TreeHealthy;
         let gui_actions = gui_action_transform_tile(cell, to_tile);
-        world.update(gui_actions);
+        update_and_validate(&mut world, gui_actions);
         assert_eq!(world.map.get_cell(cell).tile_type, to_tile);
     }
 
@@ -169,11 +193,11 @@ mod building_tests {
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
 
         let gui_actions = gui_action_transform_tile(cell, TileType::MachineAirCleaner);
-        world.update(gui_actions);
+        update_and_validate(&mut world, gui_actions);
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
 
         let gui_actions = gui_action_transform_tile(cell, TileType::TreeHealthy);
-        world.update(gui_actions);
+        update_and_validate(&mut world, gui_actions);
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
     }
 
@@ -190,8 +214,8 @@ mod building_tests {
             [cell, adjacent_cell].into_iter(),
             TileType::MachineAirCleaner,
         );
-        world.update(gui_actions);
-        world.update(GuiActions::default());
+        update_and_validate(&mut world, gui_actions);
+        update_and_validate(&mut world, GuiActions::default());
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
     }
 
@@ -206,23 +230,98 @@ mod building_tests {
         assert_eq!(world.map.get_cell(cell).tile_type, from_tile);
 
         let gui_actions = gui_action_transform_tile(cell, to_tile);
-        world.update(gui_actions);
+        update_and_validate(&mut world, gui_actions);
         assert_eq!(world.map.get_cell(cell).tile_type, to_tile);
-        for _ in 0..DEFAULT_HEALTH {
-            world.update(GuiActions::default());
+        for _ in 0..default_health() {
+            update_and_validate(&mut world, GuiActions::default());
         }
         assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeSparse);
-        for _ in 0..DEFAULT_HEALTH {
-            world.update(GuiActions::default());
+        for _ in 0..default_health() {
+            update_and_validate(&mut world, GuiActions::default());
         }
         assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeDying);
-        for _ in 0..DEFAULT_HEALTH {
-            world.update(GuiActions::default());
+        for _ in 0..default_health() {
+            update_and_validate(&mut world, GuiActions::default());
         }
         assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeDead);
-        for _ in 0..DEFAULT_HEALTH {
-            world.update(GuiActions::default());
+        for _ in 0..default_health() {
+            update_and_validate(&mut world, GuiActions::default());
         }
         assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeDead);
     }
+
+    #[test]
+    fn test_trees_that_dont_age_keep_the_layer_version() {
+        let mut world = World::new();
+        world.game_state.set_advance_every_frame();
+        let cell = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+        let gui_actions = gui_action_transform_tile(cell, TileType::TreeHealthy);
+        update_and_validate(&mut world, gui_actions);
+        assert_ne!(world.goal_state, GameGoalState::Started);
+        let version = world.map.layer_version(cell.y);
+        for _ in 0..default_health() {
+            update_and_validate(&mut world, GuiActions::default());
+        }
+        assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeHealthy);
+        assert_eq!(world.map.layer_version(cell.y), version);
+    }
+
+    #[test]
+    fn test_written_chunks_are_compressed_again() {
+        let mut world = World::new();
+        world.goal_state = GameGoalState::Started;
+        world.game_state.set_advance_every_frame();
+        let cell = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+        let gui_actions = gui_action_transform_tile(cell, TileType::TreeHealthy);
+        update_and_validate(&mut world, gui_actions);
+        assert!(world.map.is_compressed());
+        for _ in 0..default_health() {
+            update_and_validate(&mut world, GuiActions::default());
+            assert!(world.map.is_compressed());
+        }
+        assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeSparse);
+    }
+
+    #[test]
+    fn test_undo_refuses_changed_cells() {
+        let mut world = World::new();
+        world.goal_state = GameGoalState::Started;
+        world.game_state.set_advance_every_frame();
+        let cell = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+
+        let gui_actions = gui_action_transform_tile(cell, TileType::TreeHealthy);
+        update_and_validate(&mut world, gui_actions);
+        for _ in 0..default_health() {
+            update_and_validate(&mut world, GuiActions::default());
+        }
+        assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeSparse);
+
+        let mut gui_actions = GuiActions::default();
+        gui_actions.undo = true;
+        update_and_validate(&mut world, gui_actions);
+        assert_eq!(world.map.get_cell(cell).tile_type, TileType::TreeSparse);
+        assert!(!world.history.can_undo());
+        assert!(!world.history.can_redo());
+        let outdated = EventKind::HistoryOutdated(TileType::TreeHealthy);
+        assert!(world
+            .events
+            .iter_newest()
+            .any(|event| event.kind == outdated));
+    }
+
+    #[test]
+    fn test_undo_restores_only_the_tile() {
+        let mut world = World::new();
+        let cell = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+        let gui_actions = gui_action_transform_tile(cell, TileType::MachineAirCleaner);
+        update_and_validate(&mut world, gui_actions);
+        world.map.get_cell_mut(cell).health = 3;
+
+        let mut gui_actions = GuiActions::default();
+        gui_actions.undo = true;
+        update_and_validate(&mut world, gui_actions);
+        assert_eq!(world.map.get_cell(cell).tile_type, TileType::Air);
+        assert_eq!(world.map.get_cell(cell).health, 3);
+        assert!(world.history.can_redo());
+    }
 }
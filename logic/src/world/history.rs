@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+use crate::screen::gui::format_units::Grams;
+use crate::world::map::transform_cells::Transformation;
+use crate::world::map::{CellIndex, TileType};
+
+/// Older entries are dropped to keep the memory bounded.
+pub const MAX_HISTORY: usize = 100;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// Only the tiles are recorded. The fluids and the health keep evolving after the change, so
+/// undoing it must not bring back their old values.
+pub struct CellChange {
+    pub position: CellIndex,
+    pub previous: TileType,
+    pub new: TileType,
+}
+
+/// The cells that a robot transformed in a single step of a transformation task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppliedTransformation {
+    pub transformation: Transformation,
+    pub changes: Vec<CellChange>,
+    /// how much the stored resources of all the networks changed
+    pub resources_delta: Grams,
+}
+
+/// Undoing moves the newest applied transformation to the redo stack. Applying a new
+/// transformation forgets the redo stack, like in any text editor.
+pub struct History {
+    done: VecDeque<AppliedTransformation>,
+    undone: Vec<AppliedTransformation>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, applied: AppliedTransformation) {
+        if self.done.len() >= MAX_HISTORY {
+            self.done.pop_front();
+        }
+        self.done.push_back(applied);
+        self.undone.clear();
+    }
+
+    /// returns the transformation that has to be reverted, if any
+    pub fn undo(&mut self) -> Option<&AppliedTransformation> {
+        let applied = self.done.pop_back()?;
+        self.undone.push(applied);
+        self.undone.last()
+    }
+
+    /// returns the transformation that has to be applied again, if any
+    pub fn redo(&mut self) -> Option<&AppliedTransformation> {
+        let applied = self.undone.pop()?;
+        self.done.push_back(applied);
+        self.done.back()
+    }
+
+    /// drops the transformation that `undo()` returned, if it could not be reverted
+    pub fn forget_undone(&mut self) {
+        self.undone.pop();
+    }
+
+    /// drops the transformation that `redo()` returned, if it could not be applied again
+    pub fn forget_redone(&mut self) {
+        self.done.pop_back();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(new_tile_type: TileType) -> AppliedTransformation {
+        AppliedTransformation {
+            transformation: Transformation::to(new_tile_type),
+            changes: Vec::new(),
+            resources_delta: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_undo_redo_order() {
+        let mut history = History::new();
+        history.push(applied(TileType::Wire));
+        history.push(applied(TileType::MachineDrill));
+        assert_eq!(history.undo(), Some(&applied(TileType::MachineDrill)));
+        assert_eq!(history.undo(), Some(&applied(TileType::Wire)));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(&applied(TileType::Wire)));
+        assert!(history.can_undo());
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn test_new_transformation_forgets_redo() {
+        let mut history = History::new();
+        history.push(applied(TileType::Wire));
+        history.undo();
+        history.push(applied(TileType::MachineDrill));
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn test_forget_outdated() {
+        let mut history = History::new();
+        history.push(applied(TileType::Wire));
+        history.push(applied(TileType::MachineDrill));
+        history.undo();
+        history.forget_undone();
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(), None);
+        history.undo();
+        history.redo();
+        history.forget_redone();
+        assert!(!history.can_undo());
+        assert!(!history.can_redo());
+    }
+}
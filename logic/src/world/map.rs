@@ -2,14 +2,22 @@ pub mod cell;
 mod cell_cube_iterator;
 pub mod cell_envelope;
 pub mod chunk;
+pub mod generators;
 mod map_iterator;
 pub mod ref_mut_iterator;
+pub mod tile_registry;
 pub mod transform_cells;
+pub mod transformation_preview;
 pub mod transformation_rules;
+pub mod vox;
 
+use crate::common::snapshot::{
+    cell_index_str, parse_cell_index, parse_value, Snapshot, SnapshotReader, SnapshotWriter,
+};
 use crate::common::trunc::trunc_towards_neg_inf;
 use crate::world::fluids::VERTICAL_PRESSURE_DIFFERENCE;
 use crate::world::robots::DOWN;
+use cell::TILE_TYPES;
 pub use cell::{
     is_covering, is_liquid_or_air, is_walkable_horizontal, is_walkable_vertical, Cell, Pressure,
     TileType,
@@ -17,12 +25,16 @@ pub use cell::{
 pub use cell_cube_iterator::CellCubeIterator;
 use cell_envelope::Envelope;
 use chunk::chunks::Chunks;
-use chunk::Chunk;
-use chunk::{get_chunk_index, get_required_chunks};
+use chunk::{get_chunk_index, get_chunk_index_xyz, get_required_chunks};
+use chunk::{Chunk, ChunkIndex};
+pub use generators::{ArchipelagoParams, CavesParams, MountainsParams};
 use map_iterator::MutMapIterator;
 use mq_basics::{now, IVec3};
 use opensimplex_noise_rs::OpenSimplexNoise;
 use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
 /// The axes are isometric:
 /// - x: right towards camera
@@ -43,10 +55,52 @@ pub struct Map {
     map_type: MapType,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum MapType {
     Island,
     Simplex,
+    Caves(CavesParams),
+    Archipelago(ArchipelagoParams),
+    Mountains(MountainsParams),
+}
+
+impl MapType {
+    /// The lowercase name followed by parameters of the generator, like `caves hollowness=0.5`.
+    /// The parameters are the fields of `CavesParams`, `ArchipelagoParams` and
+    /// `MountainsParams`, and the missing ones keep their default.
+    pub fn parse(text: &str) -> Result<MapType, String> {
+        let mut words = text.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let mut map_type =
+            Self::from_name(name).ok_or_else(|| format!("unknown map '{}'", name))?;
+        for word in words {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or_else(|| format!("expected 'parameter=value' but got '{}'", word))?;
+            match &mut map_type {
+                MapType::Caves(params) => params.set(key, value)?,
+                MapType::Archipelago(params) => params.set(key, value)?,
+                MapType::Mountains(params) => params.set(key, value)?,
+                MapType::Island | MapType::Simplex => {
+                    return Err(format!("the {} map has no parameters", name))
+                }
+            }
+        }
+        Ok(map_type)
+    }
+
+    /// The lowercase name, with the default parameters.
+    pub fn from_name(name: &str) -> Option<MapType> {
+        let map_type = match name {
+            "island" => MapType::Island,
+            "simplex" => MapType::Simplex,
+            "caves" => MapType::Caves(CavesParams::default()),
+            "archipelago" => MapType::Archipelago(ArchipelagoParams::default()),
+            "mountains" => MapType::Mountains(MountainsParams::default()),
+            _ => return None,
+        };
+        Some(map_type)
+    }
 }
 
 impl Map {
@@ -55,9 +109,14 @@ impl Map {
     }
 
     pub fn new_generated(map_type: MapType) -> Self {
+        Self::new_generated_with_seed(map_type, None)
+    }
+
+    /// The same seed gives the same map. Without a seed, the current time is used.
+    pub fn new_generated_with_seed(map_type: MapType, seed: Option<i64>) -> Self {
         let mut map = Self::new();
         map.map_type = map_type;
-        map.regenerate();
+        map.generate(seed);
         map
     }
 
@@ -173,10 +232,24 @@ impl Map {
         self.get_chunk_optional(&index)
             .map(|chunk| chunk.get_cell(index))
     }
+    /// Changes the version of the layers of the chunk, so only call it to write the cell.
     pub fn get_cell_mut(&mut self, index: CellIndex) -> &mut Cell {
         self.get_chunk_mut(index).get_cell_mut(index)
     }
 
+    /// Changes whenever a cell in the layer `y` might have changed, and is different in every
+    /// map, so that the drawing of the layer can be reused while it stays the same.
+    pub fn layer_version(&self, y: i32) -> u64 {
+        let chunk_y = get_chunk_index_xyz(0, y, 0).y;
+        let mut hasher = DefaultHasher::new();
+        for (chunk_index, chunk) in self.chunks.iter() {
+            if chunk_index.y == chunk_y {
+                chunk.version().hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
     fn get_chunk(&self, index: CellIndex) -> &Chunk {
         self.get_chunk_optional(&index)
             .expect("Error: Making the map bigger dynamically is disabled.")
@@ -194,10 +267,39 @@ impl Map {
     }
 
     pub fn regenerate(&mut self) {
+        self.generate(None);
+    }
+
+    fn generate(&mut self, seed: Option<i64>) {
+        let seed = seed.unwrap_or(now() as i64);
+        // the new terrain replaces the old ship, which must not be cleared once it's landed again
+        self.ship_position = None;
         match self.map_type {
             MapType::Island => self.regenerate_island(),
-            MapType::Simplex => self.regenerate_with_simplex_noise(),
+            MapType::Simplex => self.regenerate_with_simplex_noise(seed),
+            MapType::Caves(params) => self.regenerate_caves(params, seed),
+            MapType::Archipelago(params) => self.regenerate_archipelago(params, seed),
+            MapType::Mountains(params) => self.regenerate_mountains(params, seed),
         };
+        // the island is a small handmade map, without anything to find underground
+        if self.map_type != MapType::Island {
+            self.place_deposits(seed);
+        }
+        self.compress();
+    }
+
+    /// Packs the chunks that were expanded by `get_cell_mut()` since the last call, so it's cheap
+    /// enough to call after every change. The fluid stages do it on their own, as they rebuild
+    /// the chunks they visit.
+    pub fn compress(&mut self) {
+        for (_, chunk) in &mut self.chunks {
+            chunk.compress();
+        }
+    }
+
+    /// If no chunk was written since the last `compress()`.
+    pub fn is_compressed(&self) -> bool {
+        self.chunks.iter().all(|(_, chunk)| !chunk.is_written())
     }
 
     fn regenerate_island(&mut self) {
@@ -212,9 +314,8 @@ impl Map {
         self.ship_position = Option::Some(ship_pos);
     }
 
-    fn regenerate_with_simplex_noise(&mut self) {
-        // if not provided, default seed is equal to 0
-        let noise_generator = OpenSimplexNoise::new(Some(now() as i64));
+    fn regenerate_with_simplex_noise(&mut self, seed: i64) {
+        let noise_generator = OpenSimplexNoise::new(Some(seed));
         let scale = 0.12;
         let mut min = 0.0;
         let mut max = 0.0;
@@ -233,40 +334,96 @@ impl Map {
                 choose_tile_simplex(value, cell_index, cell);
             }
         }
-        let mut ship_pos = None;
-        for x in -5..15 {
-            ship_pos = self.land_ship(x, 0);
-            if ship_pos.is_some() {
-                break;
+        self.land_ship_anywhere();
+        println!("simplex range used: [{}, {}]", min, max);
+    }
+
+    /// Lands the ship at `find_landing_site()`, or on a rock pillar in the middle of the map if
+    /// there is no dry ground.
+    fn land_ship_anywhere(&mut self) {
+        let position = match self.find_landing_site() {
+            Some(position) => position,
+            None => self.raise_landing_pillar(),
+        };
+        self.place_ship(position);
+    }
+
+    /// The dry place closest to the center of the map where the ship can land, preferring flat
+    /// places, whose 4 neighbours are at the same height.
+    pub fn find_landing_site(&self) -> Option<CellIndex> {
+        let mut columns = Vec::new();
+        for x in self.min_cell.x..=self.max_cell.x {
+            for z in self.min_cell.z..=self.max_cell.z {
+                columns.push((x, z));
             }
         }
-        if let Some(pos) = ship_pos {
-            self.get_cell_mut(pos).tile_type = TileType::MachineShip;
-            self.ship_position = Some(pos);
-        } else {
-            panic!("couldn't land ship");
+        columns.sort_by_key(|(x, z)| x * x + z * z);
+        let mut uneven_site = None;
+        for (x, z) in columns {
+            if let Some(position) = self.land_ship(x, z) {
+                let is_flat = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().all(|(dx, dz)| {
+                    self.land_ship(x + dx, z + dz) == Some(position + CellIndex::new(*dx, 0, *dz))
+                });
+                if is_flat {
+                    return Some(position);
+                }
+                uneven_site.get_or_insert(position);
+            }
         }
-        println!("simplex range used: [{}, {}]", min, max);
+        uneven_site
     }
+
+    /// The lowest cell of dry air in the column that is right above the ground, or None if the
+    /// column has water or no ground.
     pub fn land_ship(&self, x: i32, z: i32) -> Option<CellIndex> {
         let mut ship_pos = CellIndex::new(x, self.max_cell.y, z);
-        let mut below = ship_pos + DOWN;
-        let mut below_cell = self.get_cell(below);
-        while below_cell.tile_type == TileType::Air {
-            if below_cell.pressure > 0 {
+        let top_cell = self.get_cell_optional(ship_pos)?;
+        if top_cell.tile_type != TileType::Air || top_cell.pressure > 0 {
+            return None;
+        }
+        while ship_pos.y > self.min_cell.y {
+            let below_cell = self.get_cell(ship_pos + DOWN);
+            if below_cell.tile_type != TileType::Air {
+                return Some(ship_pos);
+            } else if below_cell.pressure > 0 {
                 return None;
             }
-            ship_pos = below;
+            ship_pos += DOWN;
+        }
+        None
+    }
+
+    /// Fills with rock the water below the center of the map, and returns the dry cell above.
+    fn raise_landing_pillar(&mut self) -> CellIndex {
+        let is_water = |cell: &Cell| cell.tile_type == TileType::Air && cell.pressure > 0;
+        let is_dry_air = |cell: &Cell| cell.tile_type == TileType::Air && cell.pressure == 0;
+        let mut position = CellIndex::new(0, self.max_cell.y, 0);
+        while position.y > self.min_cell.y && is_dry_air(self.get_cell(position + DOWN)) {
+            position += DOWN;
+        }
+        let mut below = position + DOWN;
+        while below.y >= self.min_cell.y && is_water(self.get_cell(below)) {
+            let cell = self.get_cell_mut(below);
+            cell.tile_type = TileType::WallRock;
+            define_pressure(TileType::WallRock, below, cell);
             below += DOWN;
-            below_cell = self.get_cell(below);
         }
-        Some(ship_pos)
+        position
     }
 
     pub fn get_ship_position(&self) -> Option<CellIndex> {
         self.ship_position
     }
 
+    /// Moves the ship, leaving air where it was.
+    pub fn place_ship(&mut self, position: CellIndex) {
+        if let Some(previous) = self.ship_position {
+            self.get_cell_mut(previous).tile_type = TileType::Air;
+        }
+        self.get_cell_mut(position).tile_type = TileType::MachineShip;
+        self.ship_position = Some(position);
+    }
+
     pub fn _get_pressures(&self, min_cell: CellIndex, max_cell: CellIndex) -> Vec<Pressure> {
         let mut cells = Vec::new();
         for cell_index in CellCubeIterator::new(min_cell, max_cell) {
@@ -296,12 +453,176 @@ impl Map {
     pub fn iter_mut(self) -> MutMapIterator {
         MutMapIterator::new(
             self.chunks,
+            Chunks::new(),
+            self.min_cell,
+            self.max_cell,
+            self.ship_position,
+            self.map_type,
+        )
+    }
+
+    /// Like `iter_mut()`, but only visits the chunks with fluid and their neighbours. The fluid
+    /// stages don't change the other chunks, so they are kept as they are, compressed.
+    pub fn iter_mut_near_fluid(self) -> MutMapIterator {
+        let near_fluid = self.chunks_near_fluid();
+        let mut visited = Chunks::new();
+        let mut skipped = Chunks::new();
+        for (chunk_index, chunk) in self.chunks {
+            if near_fluid.contains(&chunk_index) {
+                visited.insert(chunk_index, chunk);
+            } else {
+                skipped.insert(chunk_index, chunk);
+            }
+        }
+        MutMapIterator::new(
+            visited,
+            skipped,
             self.min_cell,
             self.max_cell,
             self.ship_position,
             self.map_type,
         )
     }
+
+    fn chunks_near_fluid(&self) -> HashSet<ChunkIndex> {
+        let mut near_fluid = HashSet::new();
+        for (chunk_index, chunk) in self.chunks.iter() {
+            if chunk.has_fluid() {
+                near_fluid.insert(*chunk_index);
+                for direction in [
+                    IVec3::X,
+                    IVec3::NEG_X,
+                    IVec3::Y,
+                    IVec3::NEG_Y,
+                    IVec3::Z,
+                    IVec3::NEG_Z,
+                ] {
+                    near_fluid.insert(*chunk_index + direction);
+                }
+            }
+        }
+        near_fluid
+    }
+
+    /// The bytes allocated for the cells, which is most of what cloning the map copies.
+    pub fn heap_bytes(&self) -> usize {
+        self.chunks
+            .iter()
+            .map(|(_, chunk)| chunk.heap_bytes())
+            .sum()
+    }
+}
+
+impl Snapshot for Map {
+    fn save(&self, snapshot: &mut SnapshotWriter) {
+        snapshot.line("map.min_cell", cell_index_str(self.min_cell));
+        snapshot.line("map.max_cell", cell_index_str(self.max_cell));
+        let map_type = match self.map_type {
+            MapType::Island => "Island".to_string(),
+            MapType::Simplex => "Simplex".to_string(),
+            MapType::Caves(p) => format!(
+                "Caves {} {} {} {}",
+                p.scale, p.ground_height, p.roughness, p.hollowness
+            ),
+            MapType::Archipelago(p) => format!(
+                "Archipelago {} {} {} {}",
+                p.scale, p.land_fraction, p.max_height, p.sea_depth
+            ),
+            MapType::Mountains(p) => format!(
+                "Mountains {} {} {} {} {}",
+                p.scale, p.valley_height, p.peak_height, p.cliff_height, p.tree_line
+            ),
+        };
+        snapshot.line("map.type", map_type);
+        if let Some(ship_position) = self.ship_position {
+            snapshot.line("map.ship_position", cell_index_str(ship_position));
+        }
+        for (chunk_index, chunk) in self.chunks.iter() {
+            for cell_index in chunk.iter(*chunk_index) {
+                let cell = chunk.get_cell(cell_index);
+                snapshot.line(
+                    "map.cell",
+                    format!(
+                        "{} {:?} {} {} {} {} {}",
+                        cell_index_str(cell_index),
+                        cell.tile_type,
+                        cell.pressure,
+                        cell.next_pressure,
+                        cell.renderable_pressure,
+                        cell.health,
+                        cell.can_flow_out
+                    ),
+                );
+            }
+        }
+    }
+
+    fn load(snapshot: &SnapshotReader) -> Result<Self, String> {
+        let min_cell = snapshot.get_cell_index("map.min_cell")?;
+        let max_cell = snapshot.get_cell_index("map.max_cell")?;
+        let mut map = Self::new_for_cube(min_cell, max_cell);
+        map.map_type = parse_map_type(snapshot.get_single("map.type")?)?;
+        if !snapshot.get_all("map.ship_position").is_empty() {
+            map.ship_position = Some(snapshot.get_cell_index("map.ship_position")?);
+        }
+        // parsing the tile names is the slowest part, so they are looked up in a map
+        let tiles = TILE_TYPES
+            .iter()
+            .map(|tile| (format!("{:?}", tile), *tile))
+            .collect::<HashMap<_, _>>();
+        let key = "map.cell";
+        for values in snapshot.get_all(key) {
+            let cell_index = parse_cell_index(values, 0, key)?;
+            let tile_name = parse_value::<String>(values, 3, key)?;
+            let cell = Cell {
+                tile_type: *tiles
+                    .get(&tile_name)
+                    .ok_or_else(|| format!("'{}' has an unknown tile '{}'", key, tile_name))?,
+                pressure: parse_value(values, 4, key)?,
+                next_pressure: parse_value(values, 5, key)?,
+                renderable_pressure: parse_value(values, 6, key)?,
+                health: parse_value(values, 7, key)?,
+                can_flow_out: parse_value(values, 8, key)?,
+            };
+            let chunk_index = get_chunk_index(&cell_index);
+            if map.chunks.get(&chunk_index).is_none() {
+                map.chunks
+                    .insert(chunk_index, Chunk::new_from_chunk_index(chunk_index));
+            }
+            *map.get_cell_mut(cell_index) = cell;
+        }
+        map.compress();
+        Ok(map)
+    }
+}
+
+fn parse_map_type(values: &[String]) -> Result<MapType, String> {
+    let key = "map.type";
+    let map_type = match parse_value::<String>(values, 0, key)?.as_str() {
+        "Island" => MapType::Island,
+        "Simplex" => MapType::Simplex,
+        "Caves" => MapType::Caves(CavesParams {
+            scale: parse_value(values, 1, key)?,
+            ground_height: parse_value(values, 2, key)?,
+            roughness: parse_value(values, 3, key)?,
+            hollowness: parse_value(values, 4, key)?,
+        }),
+        "Archipelago" => MapType::Archipelago(ArchipelagoParams {
+            scale: parse_value(values, 1, key)?,
+            land_fraction: parse_value(values, 2, key)?,
+            max_height: parse_value(values, 3, key)?,
+            sea_depth: parse_value(values, 4, key)?,
+        }),
+        "Mountains" => MapType::Mountains(MountainsParams {
+            scale: parse_value(values, 1, key)?,
+            valley_height: parse_value(values, 2, key)?,
+            peak_height: parse_value(values, 3, key)?,
+            cliff_height: parse_value(values, 4, key)?,
+            tree_line: parse_value(values, 5, key)?,
+        }),
+        other => return Err(format!("'{}' has an unknown value '{}'", key, other)),
+    };
+    Ok(map_type)
 }
 
 fn choose_tile_in_island_map(cell_index: CellIndex, cell: &mut Cell) {
@@ -345,12 +666,22 @@ fn choose_tile_simplex(value: f64, cell_index: CellIndex, cell: &mut Cell) {
 }
 
 fn define_pressure(tile_type: TileType, cell_index: CellIndex, cell: &mut Cell) {
+    define_pressure_up_to_sea_level(tile_type, cell_index, 0, cell);
+}
+
+/// The air at `sea_level` and below is water.
+fn define_pressure_up_to_sea_level(
+    tile_type: TileType,
+    cell_index: CellIndex,
+    sea_level: i32,
+    cell: &mut Cell,
+) {
     cell.pressure = 0;
     cell.can_flow_out = false;
     cell.next_pressure = 0;
     if tile_type == TileType::Air {
         use VERTICAL_PRESSURE_DIFFERENCE as PRESSURE;
-        cell.pressure = i32::max(0, PRESSURE - PRESSURE * cell_index.y);
+        cell.pressure = i32::max(0, PRESSURE * (sea_level + 1 - cell_index.y));
         cell.renderable_pressure = cell.pressure;
     }
 }
@@ -358,6 +689,7 @@ fn define_pressure(tile_type: TileType, cell_index: CellIndex, cell: &mut Cell)
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::world::fluids::{FluidMode, Fluids};
 
     #[test]
     fn test_in_range() {
@@ -366,6 +698,111 @@ mod tests {
         assert_eq!(map.in_range(CellIndex::new(0, 0, -MAP_SIZE)), false);
     }
 
+    #[test]
+    fn test_map_type_roundtrip() {
+        let mut map = Map::new_for_cube(CellIndex::new(0, 0, 0), CellIndex::new(1, 1, 1));
+        map.map_type = MapType::Mountains(MountainsParams {
+            scale: 0.5,
+            ..MountainsParams::default()
+        });
+        let mut writer = SnapshotWriter::new();
+        map.save(&mut writer);
+        let loaded = Map::load(&SnapshotReader::parse(&writer.finish()).unwrap()).unwrap();
+        assert_eq!(loaded.map_type, map.map_type);
+    }
+
+    #[test]
+    fn test_generated_map_is_compressed() {
+        let map = Map::new_generated_with_seed(MapType::Simplex, Some(3));
+        let compressed = map
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| chunk.is_compressed())
+            .count();
+        // at least the air above the ground and the rock deep below
+        assert!(compressed > map.chunks.len() / 2);
+
+        let mut expanded = map.clone();
+        let ship = map.get_ship_position().unwrap();
+        let ship_cell = *map.get_cell(ship);
+        *expanded.get_cell_mut(ship) = ship_cell;
+        expanded.compress();
+        for position in CellCubeIterator::new(map.min_cell(), map.max_cell()) {
+            assert_eq!(expanded.get_cell(position), map.get_cell(position));
+        }
+    }
+
+    fn archipelago() -> Map {
+        let params = ArchipelagoParams::default();
+        Map::new_generated_with_seed(MapType::Archipelago(params), Some(5))
+    }
+
+    #[test]
+    fn test_only_wet_chunks_are_stored_in_full() {
+        let map = archipelago();
+        let wet = map
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| chunk.has_fluid())
+            .count();
+        assert!(wet > 0 && wet < map.chunks.len(), "{} wet chunks", wet);
+
+        // what cloning the map copies, against storing every cell in full
+        let full_bytes = map.chunks.len() * chunk::SIZE * std::mem::size_of::<Cell>();
+        let bytes = map.clone().heap_bytes();
+        assert_eq!(bytes, map.heap_bytes());
+        assert!(
+            bytes * 2 < full_bytes,
+            "{} bytes, {} in full",
+            bytes,
+            full_bytes
+        );
+    }
+
+    #[test]
+    fn test_fluid_stages_skip_dry_chunks() {
+        let mut map = archipelago();
+        let before = map.clone();
+        let near_fluid = map.chunks_near_fluid();
+        let skipped = before
+            .chunks
+            .iter()
+            .filter(|(chunk_index, _)| !near_fluid.contains(chunk_index))
+            .count();
+        assert!(skipped > 0);
+
+        Fluids::new(FluidMode::AllTogether).advance(&mut map);
+
+        assert_eq!(map.chunks.len(), before.chunks.len());
+        for (chunk_index, chunk) in before.chunks.iter() {
+            let updated = map.chunks.get(chunk_index).unwrap();
+            assert!(updated.is_compressed() || updated.has_fluid());
+            if !near_fluid.contains(chunk_index) {
+                for cell_index in chunk.iter(*chunk_index) {
+                    assert_eq!(updated.get_cell(cell_index), chunk.get_cell(cell_index));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_layer_version() {
+        let top = chunk::SIZE_Y as i32;
+        let mut map = Map::new_for_cube(CellIndex::new(0, 0, 0), CellIndex::new(1, top, 1));
+        let version = map.layer_version(0);
+        let top_version = map.layer_version(top);
+        assert_eq!(map.clone().layer_version(0), version);
+        map.get_cell(CellIndex::new(0, 0, 0));
+        map.compress();
+        assert_eq!(map.layer_version(0), version);
+        map.get_cell_mut(CellIndex::new(0, 0, 0)).tile_type = TileType::WallRock;
+        assert_ne!(map.layer_version(0), version);
+        assert_eq!(map.layer_version(1), map.layer_version(0));
+        assert_eq!(map.layer_version(top), top_version);
+        let other = Map::new_for_cube(CellIndex::new(0, 0, 0), CellIndex::new(1, top, 1));
+        assert_ne!(other.layer_version(top), top_version);
+    }
+
     #[test]
     fn test_new_from_tiles_basic() {
         let map = Map::_new_from_tiles(
@@ -1,9 +1,14 @@
 pub type Health = i16;
 pub type Pressure = i32;
 pub type Pollution = i16;
+use crate::world::balance::with_balance;
+use crate::world::map::tile_registry::{tile_properties, NetworkRole};
 use TileType::*;
 
-pub const DEFAULT_HEALTH: Health = 5;
+/// health of a new tree, in aging steps. See `crate::world::balance`.
+pub fn default_health() -> Health {
+    with_balance(|balance| balance.default_health)
+}
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Cell {
@@ -35,6 +40,8 @@ impl Default for Cell {
     }
 }
 
+/// The values are stable identifiers, and they are also the default texture indices. The
+/// properties of each tile are in `tile_registry`.
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 #[allow(dead_code)]
 pub enum TileType {
@@ -42,6 +49,8 @@ pub enum TileType {
     // Helper = 2,
     WallRock = 16,
     WallDirt = 24,
+    WallOre = 1,
+    WallAquifer = 2,
     FloorRock = 17,
     FloorDirt = 20,
     Stairs = 18,
@@ -59,6 +68,56 @@ pub enum TileType {
     TreeDead = 11,
 }
 
+pub const TILE_TYPES: [TileType; 20] = [
+    Unset,
+    WallRock,
+    WallDirt,
+    WallOre,
+    WallAquifer,
+    FloorRock,
+    FloorDirt,
+    Stairs,
+    Air,
+    Wire,
+    MachineAssembler,
+    MachineAirCleaner,
+    MachineDrill,
+    MachineSolarPanel,
+    MachineShip,
+    MachineStorage,
+    TreeHealthy,
+    TreeSparse,
+    TreeDying,
+    TreeDead,
+];
+
+impl TileType {
+    /// the inverse of the Debug name, used in saved files
+    pub fn from_name(name: &str) -> Option<TileType> {
+        TILE_TYPES
+            .iter()
+            .find(|tile| format!("{:?}", tile) == name)
+            .cloned()
+    }
+
+    /// For the tiles typed by players and designers, in the console and the scenarios. The name
+    /// ignores the case, and only tiles that can be drawn are accepted, so not `Unset`, which
+    /// would break the rendering and the rules. Neither `MachineShip`, as the map only tracks one
+    /// ship, which scenarios place with `ship`.
+    pub fn parse_placeable(name: &str) -> Result<TileType, String> {
+        let tile = TILE_TYPES
+            .iter()
+            .find(|tile| format!("{:?}", tile).eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or_else(|| format!("unknown tile '{}'", name))?;
+        if tile_properties(tile).texture.is_none() || tile == TileType::MachineShip {
+            Err(format!("tile '{}' can not be placed", name))
+        } else {
+            Ok(tile)
+        }
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum ExtraTextures {
     DirtyWaterSurface = 6,
@@ -77,11 +136,20 @@ pub struct TextureIndex {
 }
 
 impl TextureIndex {
+    pub fn new(index: usize) -> Self {
+        Self { index }
+    }
     pub fn get_index(&self) -> usize {
         self.index
     }
 }
 
+impl TextureIndexTrait for TextureIndex {
+    fn get_index(&self) -> usize {
+        self.index
+    }
+}
+
 pub trait TextureIndexTrait {
     fn get_index(&self) -> usize;
 }
@@ -96,11 +164,9 @@ impl From<&dyn TextureIndexTrait> for TextureIndex {
 
 impl From<TileType> for TextureIndex {
     fn from(texture: TileType) -> Self {
-        if texture == Unset {
-            panic!("Tried to draw an Unset texture!");
-        }
-        Self {
-            index: texture as usize,
+        match tile_properties(texture).texture {
+            Some(index) => Self { index },
+            None => panic!("Tried to draw a {:?} texture!", texture),
         }
     }
 }
@@ -126,14 +192,7 @@ impl TextureIndexTrait for ExtraTextures {
 }
 
 pub fn is_liquid(tile: TileType) -> bool {
-    [
-        // DirtyWaterWall,
-        // CleanWaterWall,
-        // DirtyWaterSurface,
-        // CleanWaterSurface,
-        Air,
-    ]
-    .contains(&tile)
+    tile_properties(tile).liquid
 }
 
 pub fn is_liquid_or_air(tile: TileType) -> bool {
@@ -141,82 +200,39 @@ pub fn is_liquid_or_air(tile: TileType) -> bool {
 }
 
 pub fn is_walkable_horizontal(tile: TileType) -> bool {
-    [
-        FloorRock,
-        FloorDirt,
-        Stairs,
-        Wire,
-        MachineAssembler,
-        MachineAirCleaner,
-        MachineDrill,
-        MachineSolarPanel,
-        MachineShip,
-        MachineStorage,
-        TreeHealthy,
-        TreeSparse,
-        TreeDying,
-        TreeDead,
-    ]
-    .contains(&tile)
+    tile_properties(tile).walkable_horizontal
 }
 
 pub fn is_walkable_vertical(target_tile: TileType, origin_tile: TileType) -> bool {
-    let vertical_tiles = [Stairs];
-    vertical_tiles.contains(&target_tile) && vertical_tiles.contains(&origin_tile)
+    tile_properties(target_tile).walkable_vertical && tile_properties(origin_tile).walkable_vertical
 }
 
 pub fn is_floodable_horizontal(tile: TileType) -> bool {
-    ![Unset, WallDirt, WallRock].contains(&tile)
+    tile_properties(tile).floodable_horizontal
 }
 
 pub fn is_floodable_from_above(tile: TileType) -> bool {
-    ![Unset, WallDirt, WallRock].contains(&tile)
+    tile_properties(tile).floodable_from_above
 }
 
 pub fn is_floodable_from_below(tile: TileType) -> bool {
-    ![
-        Unset,
-        WallDirt,
-        WallRock,
-        FloorDirt,
-        FloorRock,
-        TreeHealthy,
-        TreeSparse,
-        TreeDying,
-        TreeDead,
-    ]
-    .contains(&tile)
+    tile_properties(tile).floodable_from_below
+}
+
+/// Returns whether the tile image is a full cube without transparent pixels, so it hides
+/// whatever is behind it.
+pub fn is_opaque_cube(tile: TileType) -> bool {
+    tile_properties(tile).opaque_cube
 }
 
 /// Returns whether the tile image is tall enough that it would cover a robot behind this tile.
 /// This function is used to reduce the opacity for such tiles.
 pub fn is_covering(tile: TileType) -> bool {
-    [
-        Stairs,
-        WallRock,
-        WallDirt,
-        // DirtyWaterWall, //TODO? do we need this kind of opacity test if there's no robots?
-        // CleanWaterWall,
-        MachineAssembler,
-        MachineAirCleaner,
-        MachineDrill,
-        MachineSolarPanel,
-        MachineShip,
-    ]
-    .contains(&tile)
+    tile_properties(tile).covering
 }
 
 pub fn is_networkable(tile: TileType) -> bool {
-    [
-        Wire,
-        MachineAssembler,
-        MachineAirCleaner,
-        MachineDrill,
-        MachineSolarPanel,
-        MachineShip,
-        MachineStorage,
-    ]
-    .contains(&tile)
+    tile_properties(tile).network != NetworkRole::None
 }
 
 /// Other things can be built on top. E.g. not trees, not air.
@@ -224,26 +240,21 @@ pub fn is_sturdy(tile: TileType) -> bool {
     is_networkable(tile) || is_soil(tile)
 }
 pub fn is_soil(tile: TileType) -> bool {
-    tile == WallRock || tile == WallDirt
+    tile_properties(tile).soil
 }
 
 pub fn is_tree(tile: TileType) -> bool {
-    [TreeHealthy, TreeSparse, TreeDying, TreeDead].contains(&tile)
+    tile_properties(tile).tree
 }
 
 pub fn ages(tile: TileType) -> bool {
-    [TreeHealthy, TreeSparse, TreeDying].contains(&tile)
+    tile_properties(tile).ages_into.is_some()
 }
 
+/// The tile becomes its `ages_into` tile, with full health if it can keep aging.
 pub fn transition_aging_tile(cell: &mut Cell) {
-    if cell.tile_type == TreeHealthy {
-        cell.tile_type = TreeSparse;
-        cell.health = DEFAULT_HEALTH;
-    } else if cell.tile_type == TreeSparse {
-        cell.tile_type = TreeDying;
-        cell.health = DEFAULT_HEALTH;
-    } else if cell.tile_type == TreeDying {
-        cell.tile_type = TreeDead;
-        cell.health = 0;
+    if let Some(next) = tile_properties(cell.tile_type).ages_into {
+        cell.tile_type = next;
+        cell.health = if ages(next) { default_health() } else { 0 };
     }
 }
@@ -1,4 +1,5 @@
 pub mod cell_iter;
+pub mod cell_storage;
 pub mod chunk_cell_index_iter;
 pub mod chunks;
 
@@ -14,18 +15,30 @@ pub const SIZE: usize = SIZE_X * SIZE_Y * SIZE_Z;
 use super::{Cell, CellIndex};
 use crate::common::trunc::trunc_towards_neg_inf;
 pub use crate::world::map::chunk::cell_iter::CellIter;
+use crate::world::map::chunk::cell_storage::CellStorage;
 use crate::world::map::chunk::chunk_cell_index_iter::{
     chunk_local_index_to_global_index, ChunkCellIndexIter,
 };
 use crate::world::map::chunk::chunks::Chunks;
 use mq_basics::IVec3;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 pub type ChunkIndex = IVec3;
 
 #[derive(Clone)]
 pub struct Chunk {
-    cells: Vec<Cell>,
+    cells: CellStorage,
     origin: CellIndex,
+    /// if `get_cell_mut()` was called since the last `compress()`
+    written: bool,
+    /// see `version()`
+    version: u64,
+}
+
+static NEXT_CHUNK_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_chunk_version() -> u64 {
+    NEXT_CHUNK_VERSION.fetch_add(1, Ordering::Relaxed)
 }
 
 impl Chunk {
@@ -35,9 +48,12 @@ impl Chunk {
     }
 
     fn new_with_default_cell(origin: CellIndex, default_cell: Cell) -> Chunk {
-        let mut cells = Vec::with_capacity(SIZE);
-        cells.resize(SIZE, default_cell);
-        Chunk { cells, origin }
+        Chunk {
+            cells: CellStorage::new_uniform(default_cell),
+            origin,
+            written: false,
+            version: next_chunk_version(),
+        }
     }
     pub fn new_from_chunk_index(chunk_index: ChunkIndex) -> Self {
         Self::new_from_chunk_index_with_default_cell(chunk_index, Cell::default())
@@ -50,20 +66,51 @@ impl Chunk {
         Self::new_with_default_cell(local_index, default_cell)
     }
     pub fn new_from_cells(cells: Vec<Cell>, origin: CellIndex) -> Self {
-        Self { cells, origin }
+        Self {
+            cells: CellStorage::new_compressed(cells),
+            origin,
+            written: false,
+            version: next_chunk_version(),
+        }
     }
     pub fn get_cell(&self, index: CellIndex) -> &Cell {
-        self.cells.get(get_cell_inner_index(index)).unwrap()
+        self.cells.get(get_cell_inner_index(index))
     }
+    /// Stores the cells in full, see `compress()`.
     pub fn get_cell_mut(&mut self, index: CellIndex) -> &mut Cell {
-        self.cells.get_mut(get_cell_inner_index(index)).unwrap()
+        self.written = true;
+        self.version = next_chunk_version();
+        self.cells.get_mut(get_cell_inner_index(index))
+    }
+    /// Only does something if a cell was written since the last time.
+    pub fn compress(&mut self) {
+        if self.written {
+            self.cells.compress();
+            self.written = false;
+        }
+    }
+    pub fn is_compressed(&self) -> bool {
+        self.cells.is_compressed()
+    }
+    pub fn is_written(&self) -> bool {
+        self.written
+    }
+    /// Changes whenever a cell might have changed, and is different in every chunk.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+    pub fn has_fluid(&self) -> bool {
+        self.cells.has_fluid()
+    }
+    pub fn heap_bytes(&self) -> usize {
+        self.cells.heap_bytes()
     }
     pub fn iter(&self, chunk_index: ChunkIndex) -> ChunkCellIndexIter {
         ChunkCellIndexIter::new(chunk_index)
     }
 
     pub fn into_iter_mut(self) -> CellIter {
-        CellIter::new(self.cells, self.origin)
+        CellIter::new(self.cells.into_vec(), self.origin)
     }
     pub fn into_hash(self, chunks: &mut Chunks) {
         chunks.insert(get_chunk_index(&self.origin), self);
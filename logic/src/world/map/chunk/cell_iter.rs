@@ -19,6 +19,10 @@ impl CellIter {
     pub fn into_chunk(self) -> Chunk {
         Chunk::new_from_cells(self.cells, self.origin)
     }
+    /// The default iterator has no cells, and no chunk to give back.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
     pub fn has_next(&self) -> bool {
         ((self.i + 1) as usize) < self.cells.len()
     }
@@ -0,0 +1,209 @@
+//! The cells of a chunk. Most chunks are solid rock or empty air, or have only a few different
+//! tiles, so they are stored as a palette with the different cells and the position in the
+//! palette of each cell. Only dry chunks are compressed, so the palette entries have no fluid. The
+//! fluid differs from cell to cell and changes in every fluid stage, so the chunks with some fluid
+//! keep every cell, and the fluid stages only visit those and their neighbours.
+//!
+//! Reading a cell works in any form, but a mutable cell needs the cells stored in full, so
+//! `get_mut()` expands them until the next `compress()`.
+
+use crate::world::map::chunk::SIZE;
+use crate::world::map::Cell;
+use std::mem::size_of;
+
+/// Chunks with more different cells than this are stored in full. The palette is searched
+/// linearly when compressing, and its positions have to fit in a byte.
+pub const MAX_PALETTE_LEN: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CellStorage {
+    /// all the cells are the same, and dry
+    Uniform(Cell),
+    /// the cell `i` is `palette[indexes[i]]`, and all of them are dry
+    Palette {
+        palette: Vec<Cell>,
+        indexes: Vec<u8>,
+    },
+    Full(Vec<Cell>),
+}
+
+fn is_dry(cell: &Cell) -> bool {
+    cell.pressure == 0
+        && cell.next_pressure == 0
+        && cell.renderable_pressure == 0
+        && !cell.can_flow_out
+}
+
+impl CellStorage {
+    pub fn new_uniform(cell: Cell) -> Self {
+        if is_dry(&cell) {
+            CellStorage::Uniform(cell)
+        } else {
+            CellStorage::Full(vec![cell; SIZE])
+        }
+    }
+
+    pub fn new_compressed(cells: Vec<Cell>) -> Self {
+        if !cells.iter().all(is_dry) {
+            return CellStorage::Full(cells);
+        }
+        let mut palette: Vec<Cell> = Vec::new();
+        let mut indexes = Vec::with_capacity(cells.len());
+        // neighbouring cells are usually the same, so the previous one is checked first
+        let mut previous = 0;
+        for cell in &cells {
+            let index = if palette.get(previous) == Some(cell) {
+                previous
+            } else if let Some(index) = palette.iter().position(|other| other == cell) {
+                index
+            } else if palette.len() < MAX_PALETTE_LEN {
+                palette.push(*cell);
+                palette.len() - 1
+            } else {
+                return CellStorage::Full(cells);
+            };
+            indexes.push(index as u8);
+            previous = index;
+        }
+        if palette.len() == 1 {
+            CellStorage::Uniform(palette[0])
+        } else {
+            CellStorage::Palette { palette, indexes }
+        }
+    }
+
+    pub fn get(&self, i: usize) -> &Cell {
+        match self {
+            CellStorage::Uniform(cell) => {
+                assert!(i < SIZE, "cell {} is out of the chunk", i);
+                cell
+            }
+            CellStorage::Palette { palette, indexes } => &palette[indexes[i] as usize],
+            CellStorage::Full(cells) => &cells[i],
+        }
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> &mut Cell {
+        if !matches!(self, CellStorage::Full(_)) {
+            *self = CellStorage::Full(self.to_vec());
+        }
+        match self {
+            CellStorage::Full(cells) => &mut cells[i],
+            _ => unreachable!("the cells were just expanded"),
+        }
+    }
+
+    pub fn compress(&mut self) {
+        if let CellStorage::Full(cells) = self {
+            *self = CellStorage::new_compressed(std::mem::take(cells));
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self, CellStorage::Full(_))
+    }
+
+    pub fn has_fluid(&self) -> bool {
+        match self {
+            CellStorage::Full(cells) => !cells.iter().all(is_dry),
+            _ => false,
+        }
+    }
+
+    /// The bytes allocated for the cells, which is what cloning a chunk copies.
+    pub fn heap_bytes(&self) -> usize {
+        match self {
+            CellStorage::Uniform(_) => 0,
+            CellStorage::Palette { palette, indexes } => {
+                palette.len() * size_of::<Cell>() + indexes.len()
+            }
+            CellStorage::Full(cells) => cells.len() * size_of::<Cell>(),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Cell> {
+        match self {
+            CellStorage::Full(cells) => cells,
+            compressed => compressed.to_vec(),
+        }
+    }
+
+    fn to_vec(&self) -> Vec<Cell> {
+        match self {
+            CellStorage::Uniform(cell) => vec![*cell; SIZE],
+            CellStorage::Palette { palette, indexes } => indexes
+                .iter()
+                .map(|index| palette[*index as usize])
+                .collect(),
+            CellStorage::Full(cells) => cells.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::cell::Health;
+    use crate::world::map::TileType;
+
+    fn cells_with_healths(health_count: usize) -> Vec<Cell> {
+        (0..SIZE)
+            .map(|i| {
+                let mut cell = Cell::new(TileType::TreeHealthy);
+                cell.health = (i % health_count) as Health;
+                cell
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compression_forms() {
+        let uniform = CellStorage::new_compressed(cells_with_healths(1));
+        assert_eq!(
+            uniform,
+            CellStorage::Uniform(Cell::new(TileType::TreeHealthy))
+        );
+        assert_eq!(uniform.heap_bytes(), 0);
+
+        let cells = cells_with_healths(MAX_PALETTE_LEN);
+        let palette = CellStorage::new_compressed(cells.clone());
+        assert!(matches!(palette, CellStorage::Palette { .. }));
+        assert_eq!(palette.into_vec(), cells);
+
+        let cells = cells_with_healths(MAX_PALETTE_LEN + 1);
+        assert_eq!(
+            CellStorage::new_compressed(cells.clone()),
+            CellStorage::Full(cells)
+        );
+    }
+
+    #[test]
+    fn test_only_dry_cells_are_compressed() {
+        let mut cells = cells_with_healths(1);
+        cells[3].pressure = 1;
+        let mut wet = CellStorage::new_compressed(cells.clone());
+        assert_eq!(wet, CellStorage::Full(cells));
+        assert!(wet.has_fluid());
+        assert_eq!(wet.get(3).pressure, 1);
+
+        wet.get_mut(3).pressure = 0;
+        wet.compress();
+        assert!(!wet.has_fluid());
+        assert_eq!(wet, CellStorage::Uniform(Cell::new(TileType::TreeHealthy)));
+    }
+
+    #[test]
+    fn test_get_mut_expands() {
+        let mut storage = CellStorage::new_compressed(cells_with_healths(2));
+        assert_eq!(storage.get(3).health, 1);
+        storage.get_mut(3).health = 5;
+        assert!(!storage.is_compressed());
+        assert_eq!(storage.get(3).health, 5);
+        assert_eq!(storage.get(4).health, 0);
+
+        storage.compress();
+        assert!(storage.is_compressed());
+        assert_eq!(storage.get(3).health, 5);
+        assert_eq!(storage.get(5).health, 1);
+    }
+}
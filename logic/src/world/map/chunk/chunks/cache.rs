@@ -1,52 +1,42 @@
+use crate::common::profiling::Counter;
 use std::cell::RefCell;
 use std::ops::Deref;
 
-/// we need this static variables because Chunks may get recreated due to the iterator fiasco.
-/// see [`crate::world::map::Chunks::into_iter`].
-pub static mut CACHE_MISSES: i64 = 0;
-pub static mut CACHE_HOT_HITS: i64 = 0;
-pub static mut CACHE_COLD_HITS: i64 = 0;
+/// Counted in the metrics registry instead of in Chunks because Chunks may get recreated due to
+/// the iterator fiasco. See [`crate::world::map::Chunks::into_iter`].
+pub static CACHE_MISSES: Counter = Counter::new("chunk cache misses");
+pub static CACHE_HOT_HITS: Counter = Counter::new("chunk cache hot hits");
+pub static CACHE_COLD_HITS: Counter = Counter::new("chunk cache cold hits");
 
 pub fn print_cache_stats(profile: bool) {
     if profile {
-        unsafe {
-            let total_requests = (CACHE_HOT_HITS + CACHE_COLD_HITS + CACHE_MISSES) as f64;
-            if total_requests > 0.0 {
-                println!(
-                    "Cache hits: hot: {}, cold: {}. cache misses: {}, ratio cached: {}, ratio hot: {}",
-                    CACHE_HOT_HITS,
-                    CACHE_COLD_HITS,
-                    CACHE_MISSES,
-                    (CACHE_HOT_HITS + CACHE_COLD_HITS) as f64 / total_requests,
-                    CACHE_HOT_HITS as f64 / total_requests
-                );
-            }
+        let misses = CACHE_MISSES.get();
+        let hot_hits = CACHE_HOT_HITS.get();
+        let cold_hits = CACHE_COLD_HITS.get();
+        let total_requests = (hot_hits + cold_hits + misses) as f64;
+        if total_requests > 0.0 {
+            println!(
+                "Cache hits: hot: {}, cold: {}. cache misses: {}, ratio cached: {}, ratio hot: {}",
+                hot_hits,
+                cold_hits,
+                misses,
+                (hot_hits + cold_hits) as f64 / total_requests,
+                hot_hits as f64 / total_requests
+            );
         }
     }
 }
 
 pub fn record_cache_miss() {
-    unsafe {
-        // done in 2 statements because I don't know how to make CLion to show me the static
-        // variable while debugging with breakpoints, but the local variable shows up even in
-        // release mode
-        let current_misses = CACHE_MISSES;
-        CACHE_MISSES = current_misses + 1;
-    }
+    CACHE_MISSES.increment();
 }
 
 pub fn record_cache_hot_hit() {
-    unsafe {
-        let current_hits = CACHE_HOT_HITS;
-        CACHE_HOT_HITS = current_hits + 1;
-    }
+    CACHE_HOT_HITS.increment();
 }
 
 pub fn record_cache_cold_hit() {
-    unsafe {
-        let current_hits = CACHE_COLD_HITS;
-        CACHE_COLD_HITS = current_hits + 1;
-    }
+    CACHE_COLD_HITS.increment();
 }
 
 /// Small cache for indexes.
@@ -0,0 +1,511 @@
+//! Generators for the map types that take parameters, and the underground deposits of all the
+//! generated maps. Like the Simplex map, the same seed gives the same map, and the air up to the
+//! sea level is water (air with pressure).
+
+use crate::world::map::{define_pressure_up_to_sea_level, Cell, CellIndex, Map, TileType};
+use opensimplex_noise_rs::OpenSimplexNoise;
+use std::str::FromStr;
+
+/// 3D noise, so that the ground can have overhangs and caves inside.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CavesParams {
+    /// bigger values give smaller and more frequent features
+    pub scale: f64,
+    /// the average height of the surface
+    pub ground_height: i32,
+    /// how many cells the surface goes up and down. Bigger values give more overhangs
+    pub roughness: f64,
+    /// from 0 to 1, how much of the underground is hollow
+    pub hollowness: f64,
+}
+
+impl CavesParams {
+    /// sets a field by its name, see `MapType::parse()`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "scale" => self.scale = parse_param(key, value)?,
+            "ground_height" => self.ground_height = parse_param(key, value)?,
+            "roughness" => self.roughness = parse_param(key, value)?,
+            "hollowness" => self.hollowness = parse_param(key, value)?,
+            _ => return Err(format!("unknown parameter '{}' of the caves map", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for CavesParams {
+    fn default() -> Self {
+        Self {
+            scale: 0.1,
+            ground_height: 4,
+            roughness: 6.0,
+            hollowness: 0.3,
+        }
+    }
+}
+
+/// Islands of several sizes in a sea at the height 0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ArchipelagoParams {
+    /// bigger values give smaller and more islands
+    pub scale: f64,
+    /// from 0 to 1, roughly how much of the surface is land
+    pub land_fraction: f64,
+    /// cells above the sea of the highest hills
+    pub max_height: i32,
+    /// cells below the sea of the deepest sea bed
+    pub sea_depth: i32,
+}
+
+impl ArchipelagoParams {
+    /// sets a field by its name, see `MapType::parse()`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "scale" => self.scale = parse_param(key, value)?,
+            "land_fraction" => self.land_fraction = parse_param(key, value)?,
+            "max_height" => self.max_height = parse_param(key, value)?,
+            "sea_depth" => self.sea_depth = parse_param(key, value)?,
+            _ => {
+                return Err(format!(
+                    "unknown parameter '{}' of the archipelago map",
+                    key
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ArchipelagoParams {
+    fn default() -> Self {
+        Self {
+            scale: 0.08,
+            land_fraction: 0.35,
+            max_height: 6,
+            sea_depth: 8,
+        }
+    }
+}
+
+/// Ridges of peaks, with the slopes in terraces separated by cliffs. Some valleys have lakes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MountainsParams {
+    /// bigger values give narrower and more frequent ridges
+    pub scale: f64,
+    /// the height of the valleys
+    pub valley_height: i32,
+    /// cells above the valleys of the highest peaks
+    pub peak_height: i32,
+    /// the height of each terrace, which is also the height of the cliffs
+    pub cliff_height: i32,
+    /// above this height the ground is bare rock
+    pub tree_line: i32,
+}
+
+impl MountainsParams {
+    /// sets a field by its name, see `MapType::parse()`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "scale" => self.scale = parse_param(key, value)?,
+            "valley_height" => self.valley_height = parse_param(key, value)?,
+            "peak_height" => self.peak_height = parse_param(key, value)?,
+            "cliff_height" => self.cliff_height = parse_param(key, value)?,
+            "tree_line" => self.tree_line = parse_param(key, value)?,
+            _ => return Err(format!("unknown parameter '{}' of the mountains map", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for MountainsParams {
+    fn default() -> Self {
+        Self {
+            scale: 0.05,
+            valley_height: -2,
+            peak_height: 24,
+            cliff_height: 3,
+            tree_line: 12,
+        }
+    }
+}
+
+fn parse_param<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("'{}' has an invalid value '{}'", key, value))
+}
+
+impl Map {
+    pub(super) fn regenerate_caves(&mut self, params: CavesParams, seed: i64) {
+        let surface_noise = OpenSimplexNoise::new(Some(seed));
+        let cave_noise = OpenSimplexNoise::new(Some(seed.wrapping_add(1)));
+        let scale = params.scale;
+        // below this, the noise carves a cave. The noise is roughly in [-1, 1]
+        let cave_threshold = 2.0 * params.hollowness - 1.0;
+        let bottom = self.min_cell.y;
+        let is_solid = |cell_index: CellIndex| {
+            if cell_index.y == bottom {
+                return true;
+            }
+            let (x, y, z) = (
+                cell_index.x as f64 * scale,
+                cell_index.y as f64 * scale,
+                cell_index.z as f64 * scale,
+            );
+            let depth = (params.ground_height - cell_index.y) as f64 / params.roughness;
+            let is_ground = depth + surface_noise.eval_3d(x, y, z) > 0.0;
+            // the caves are stretched horizontally so that they are easier to walk
+            let is_cave = cave_noise.eval_3d(x, y * 2.0, z) < cave_threshold;
+            is_ground && !is_cave
+        };
+        let no_sea = bottom - 1;
+        for (chunk_index, chunk) in &mut self.chunks {
+            for cell_index in chunk.iter(*chunk_index) {
+                let tile = if !is_solid(cell_index) {
+                    TileType::Air
+                } else if is_solid(cell_index + CellIndex::new(0, 1, 0)) {
+                    TileType::WallRock
+                } else {
+                    TileType::WallDirt
+                };
+                set_tile(chunk.get_cell_mut(cell_index), cell_index, tile, no_sea);
+            }
+        }
+        self.land_ship_anywhere();
+    }
+
+    pub(super) fn regenerate_archipelago(&mut self, params: ArchipelagoParams, seed: i64) {
+        let noise = OpenSimplexNoise::new(Some(seed));
+        // the noise is roughly in [-1, 1], so this leaves `land_fraction` of it above the sea
+        let coast = 1.0 - 2.0 * params.land_fraction.clamp(0.0, 1.0);
+        let max_ground = self.max_cell.y - 2;
+        for (chunk_index, chunk) in &mut self.chunks {
+            for cell_index in chunk.iter(*chunk_index) {
+                let value = noise.eval_2d(
+                    cell_index.x as f64 * params.scale,
+                    cell_index.z as f64 * params.scale,
+                );
+                let ground = if value > coast {
+                    let height = (value - coast) / (1.0 - coast);
+                    (height * params.max_height as f64) as i32
+                } else {
+                    let depth = (coast - value) / (coast + 1.0);
+                    -1 - (depth * params.sea_depth as f64) as i32
+                };
+                let ground = ground.min(max_ground);
+                let tile = column_tile(cell_index.y, ground, TileType::WallDirt);
+                set_tile(chunk.get_cell_mut(cell_index), cell_index, tile, 0);
+            }
+        }
+        self.land_ship_anywhere();
+    }
+
+    pub(super) fn regenerate_mountains(&mut self, params: MountainsParams, seed: i64) {
+        let noise = OpenSimplexNoise::new(Some(seed));
+        let cliff_height = params.cliff_height.max(1);
+        let max_ground = self.max_cell.y - 2;
+        for (chunk_index, chunk) in &mut self.chunks {
+            for cell_index in chunk.iter(*chunk_index) {
+                let value = noise.eval_2d(
+                    cell_index.x as f64 * params.scale,
+                    cell_index.z as f64 * params.scale,
+                );
+                // the ridges are where the noise crosses 0
+                let ridge = 1.0 - value.abs();
+                let height = (ridge * ridge * params.peak_height as f64) as i32;
+                let terrace = height / cliff_height * cliff_height;
+                let ground = (params.valley_height + terrace).min(max_ground);
+                let surface = if ground > params.tree_line {
+                    TileType::WallRock
+                } else {
+                    TileType::WallDirt
+                };
+                let tile = column_tile(cell_index.y, ground, surface);
+                set_tile(chunk.get_cell_mut(cell_index), cell_index, tile, 0);
+            }
+        }
+        self.land_ship_anywhere();
+    }
+}
+
+/// Bigger values give smaller and more frequent deposits.
+const DEPOSIT_SCALE: f64 = 0.15;
+/// The dirt pockets are only this close to the surface.
+const DIRT_MAX_DEPTH: i32 = 6;
+const DIRT_THRESHOLD: f64 = 0.4;
+const AQUIFER_MIN_DEPTH: i32 = 4;
+const AQUIFER_THRESHOLD: f64 = 0.45;
+const ORE_MIN_DEPTH: i32 = 8;
+const ORE_THRESHOLD_AT_SURFACE: f64 = 0.6;
+/// The ore gets more common with depth, as its threshold goes down this much per cell.
+const ORE_THRESHOLD_PER_DEPTH: f64 = 0.01;
+
+impl Map {
+    /// Replaces some of the rock below the surface with deposits worth digging for: pockets of
+    /// dirt near the surface, wet rock that releases water, and metal ore deeper down. The depth
+    /// is counted from the highest solid cell of each column.
+    pub(super) fn place_deposits(&mut self, seed: i64) {
+        let dirt_noise = OpenSimplexNoise::new(Some(seed.wrapping_add(2)));
+        let aquifer_noise = OpenSimplexNoise::new(Some(seed.wrapping_add(3)));
+        let ore_noise = OpenSimplexNoise::new(Some(seed.wrapping_add(4)));
+        let (min, max) = (self.min_cell, self.max_cell);
+        for x in min.x..=max.x {
+            for z in min.z..=max.z {
+                let mut surface = None;
+                for y in (min.y..=max.y).rev() {
+                    let position = CellIndex::new(x, y, z);
+                    let cell = self.get_cell_mut(position);
+                    if cell.tile_type != TileType::Air && surface.is_none() {
+                        surface = Some(y);
+                    }
+                    if cell.tile_type != TileType::WallRock {
+                        continue;
+                    }
+                    let depth = surface.unwrap() - y;
+                    let sample = |noise: &OpenSimplexNoise| {
+                        noise.eval_3d(
+                            x as f64 * DEPOSIT_SCALE,
+                            y as f64 * DEPOSIT_SCALE,
+                            z as f64 * DEPOSIT_SCALE,
+                        )
+                    };
+                    let deposit = choose_deposit(
+                        depth,
+                        sample(&dirt_noise),
+                        sample(&aquifer_noise),
+                        sample(&ore_noise),
+                    );
+                    if let Some(deposit) = deposit {
+                        cell.tile_type = deposit;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The deposit, if any, in a cell of rock `depth` cells below the surface, given the noise of
+/// each kind of deposit there.
+fn choose_deposit(depth: i32, dirt: f64, aquifer: f64, ore: f64) -> Option<TileType> {
+    let ore_threshold = ORE_THRESHOLD_AT_SURFACE - ORE_THRESHOLD_PER_DEPTH * depth as f64;
+    if depth >= ORE_MIN_DEPTH && ore > ore_threshold {
+        Some(TileType::WallOre)
+    } else if depth >= AQUIFER_MIN_DEPTH && aquifer > AQUIFER_THRESHOLD {
+        Some(TileType::WallAquifer)
+    } else if (1..=DIRT_MAX_DEPTH).contains(&depth) && dirt > DIRT_THRESHOLD {
+        Some(TileType::WallDirt)
+    } else {
+        None
+    }
+}
+
+/// The tile at the height `y` of a column whose highest solid cell is at `ground`.
+fn column_tile(y: i32, ground: i32, surface: TileType) -> TileType {
+    if y < ground {
+        TileType::WallRock
+    } else if y == ground {
+        surface
+    } else {
+        TileType::Air
+    }
+}
+
+fn set_tile(cell: &mut Cell, cell_index: CellIndex, tile: TileType, sea_level: i32) {
+    cell.tile_type = tile;
+    define_pressure_up_to_sea_level(tile, cell_index, sea_level, cell);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::{CellCubeIterator, MapType, MAP_SIZE};
+    use crate::world::robots::DOWN;
+
+    fn assert_landed(map: &Map) {
+        let ship = map.get_ship_position().unwrap();
+        assert_eq!(map.get_cell(ship).tile_type, TileType::MachineShip);
+        assert_eq!(map.get_cell(ship).pressure, 0, "the ship should be dry");
+        assert_ne!(map.get_cell(ship + DOWN).tile_type, TileType::Air);
+    }
+
+    /// The heights of the highest solid cell of each column.
+    fn ground_heights(map: &Map) -> Vec<Vec<i32>> {
+        let min = map.min_cell();
+        let max = map.max_cell();
+        (min.x..=max.x)
+            .map(|x| {
+                (min.z..=max.z)
+                    .map(|z| {
+                        (min.y..=max.y)
+                            .rev()
+                            .find(|y| {
+                                let tile = map.get_cell(CellIndex::new(x, *y, z)).tile_type;
+                                tile != TileType::Air && tile != TileType::MachineShip
+                            })
+                            .unwrap_or(min.y)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_map() {
+        for map_type in [
+            MapType::Caves(CavesParams::default()),
+            MapType::Archipelago(ArchipelagoParams::default()),
+            MapType::Mountains(MountainsParams::default()),
+        ] {
+            let map = Map::new_generated_with_seed(map_type, Some(3));
+            let other = Map::new_generated_with_seed(map_type, Some(3));
+            let min = map.min_cell();
+            let max = map.max_cell();
+            assert_eq!(
+                map._get_pressures_and_types(min, max),
+                other._get_pressures_and_types(min, max)
+            );
+            assert_landed(&map);
+        }
+    }
+
+    #[test]
+    fn test_regenerating_keeps_no_trace_of_the_old_ship() {
+        let map_type = MapType::Archipelago(ArchipelagoParams::default());
+        let mut map = Map::new_generated_with_seed(map_type, Some(3));
+        map.generate(Some(4));
+        let other = Map::new_generated_with_seed(map_type, Some(4));
+        let min = map.min_cell();
+        let max = map.max_cell();
+        assert_eq!(
+            map._get_pressures_and_types(min, max),
+            other._get_pressures_and_types(min, max)
+        );
+        assert_eq!(map.get_ship_position(), other.get_ship_position());
+    }
+
+    #[test]
+    fn test_parse_map_type() {
+        assert_eq!(
+            MapType::parse("caves hollowness=0.5 scale=0.2"),
+            Ok(MapType::Caves(CavesParams {
+                hollowness: 0.5,
+                scale: 0.2,
+                ..CavesParams::default()
+            }))
+        );
+        assert_eq!(
+            MapType::parse("mountains"),
+            Ok(MapType::Mountains(MountainsParams::default()))
+        );
+        assert_eq!(
+            MapType::parse("archipelago depth=3"),
+            Err("unknown parameter 'depth' of the archipelago map".to_string())
+        );
+        assert_eq!(
+            MapType::parse("caves scale"),
+            Err("expected 'parameter=value' but got 'scale'".to_string())
+        );
+        assert_eq!(
+            MapType::parse("island scale=2"),
+            Err("the island map has no parameters".to_string())
+        );
+        assert_eq!(
+            MapType::parse("moon"),
+            Err("unknown map 'moon'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_caves_have_overhangs() {
+        let map = Map::new_generated_with_seed(MapType::Caves(CavesParams::default()), Some(5));
+        let min = map.min_cell();
+        let max = map.max_cell();
+        let overhangs = CellCubeIterator::new(min, max - CellIndex::new(0, 1, 0))
+            .filter(|cell_index| {
+                map.get_cell(*cell_index).tile_type == TileType::Air
+                    && map
+                        .get_cell(*cell_index + CellIndex::new(0, 1, 0))
+                        .tile_type
+                        != TileType::Air
+            })
+            .count();
+        assert!(overhangs > 0);
+    }
+
+    #[test]
+    fn test_archipelago_has_land_and_sea() {
+        let params = ArchipelagoParams::default();
+        let map = Map::new_generated_with_seed(MapType::Archipelago(params), Some(5));
+        let heights = ground_heights(&map);
+        let land = heights
+            .concat()
+            .iter()
+            .filter(|height| **height >= 0)
+            .count();
+        let sea = heights.concat().len() - land;
+        assert!(land > 0);
+        assert!(sea > land, "{} cells of land and {} of sea", land, sea);
+        let min = map.min_cell();
+        let (x, z) = (0..MAP_SIZE)
+            .flat_map(|x| (0..MAP_SIZE).map(move |z| (x, z)))
+            .find(|(x, z)| heights[*x as usize][*z as usize] < 0)
+            .unwrap();
+        let sea_surface = CellIndex::new(min.x + x, 0, min.z + z);
+        assert!(
+            map.get_cell(sea_surface).pressure > 0,
+            "the sea should be water"
+        );
+    }
+
+    #[test]
+    fn test_ship_lands_without_dry_ground() {
+        let params = ArchipelagoParams {
+            land_fraction: 0.0,
+            ..ArchipelagoParams::default()
+        };
+        let map = Map::new_generated_with_seed(MapType::Archipelago(params), Some(5));
+        assert_landed(&map);
+    }
+
+    #[test]
+    fn test_deposits_depend_on_depth() {
+        assert_eq!(choose_deposit(0, 1.0, 1.0, 1.0), None, "the surface stays");
+        assert_eq!(choose_deposit(2, 1.0, 1.0, 1.0), Some(TileType::WallDirt));
+        assert_eq!(choose_deposit(30, 1.0, 1.0, 1.0), Some(TileType::WallOre));
+        assert_eq!(
+            choose_deposit(30, 1.0, 1.0, 0.0),
+            Some(TileType::WallAquifer)
+        );
+        assert_eq!(choose_deposit(30, 1.0, 0.0, 0.0), None);
+
+        let map =
+            Map::new_generated_with_seed(MapType::Mountains(MountainsParams::default()), Some(5));
+        let count = |tile| {
+            CellCubeIterator::new(map.min_cell(), map.max_cell())
+                .filter(|position| map.get_cell(*position).tile_type == tile)
+                .count()
+        };
+        assert!(count(TileType::WallOre) > 0);
+        assert!(count(TileType::WallAquifer) > 0);
+        let island = Map::new_generated(MapType::Island);
+        let min = island.min_cell();
+        let max = island.max_cell();
+        assert!(CellCubeIterator::new(min, max).all(|position| {
+            let tile = island.get_cell(position).tile_type;
+            tile != TileType::WallOre && tile != TileType::WallAquifer
+        }));
+    }
+
+    #[test]
+    fn test_mountains_have_cliffs() {
+        let params = MountainsParams::default();
+        let map = Map::new_generated_with_seed(MapType::Mountains(params), Some(5));
+        let heights = ground_heights(&map);
+        let highest = heights.iter().flatten().max().unwrap();
+        assert!(*highest > params.tree_line);
+        let has_cliff = heights.windows(2).any(|pair| {
+            (0..pair[0].len()).any(|z| (pair[0][z] - pair[1][z]).abs() >= params.cliff_height)
+        });
+        assert!(has_cliff);
+    }
+}
@@ -72,8 +72,10 @@ pub struct MutMapIterator {
 }
 
 impl MutMapIterator {
+    /// The `skipped_chunks` are not visited, and are given back unchanged.
     pub fn new(
         chunks: Chunks,
+        skipped_chunks: Chunks,
         min_cell: CellIndex,
         max_cell: CellIndex,
         ship_position: Option<CellIndex>,
@@ -87,7 +89,7 @@ impl MutMapIterator {
         Self {
             chunk_iterator,
             cell_iterator,
-            collected_chunks: Chunks::new(),
+            collected_chunks: skipped_chunks,
             min_cell,
             max_cell,
             ship_position,
@@ -102,9 +104,11 @@ impl MutMapIterator {
             .unwrap_or_default();
         let previous_chunk_cell_iterator =
             std::mem::replace(&mut self.cell_iterator, next_chunk_cell_iterator);
-        previous_chunk_cell_iterator
-            .into_chunk()
-            .into_hash(&mut self.collected_chunks);
+        if !previous_chunk_cell_iterator.is_empty() {
+            previous_chunk_cell_iterator
+                .into_chunk()
+                .into_hash(&mut self.collected_chunks);
+        }
         self.cell_iterator.next()
     }
 }
@@ -123,7 +127,7 @@ impl<'a> RefMutIterator<'a, CellIterItem<'a>> for MutMapIterator {
 mod tests {
     use super::*;
     use crate::world::map::chunk;
-    use crate::world::map::Map;
+    use crate::world::map::{Map, TileType};
     // #[test]
     // fn test_basic_map_iterator() {
     //     let map = Map::new_for_cube(CellIndex::new(0, 0, 0), CellIndex::new(0, 0, 1));
@@ -171,4 +175,15 @@ mod tests {
         assert_eq!(sum_pressure, 10 * chunk::SIZE as i32);
         assert_eq!(updated_map.get_cell(CellIndex::new(0, 0, 0)).pressure, 10)
     }
+
+    #[test]
+    fn test_skipping_every_chunk() {
+        let mut map = Map::new_for_cube(CellIndex::new(0, 0, 0), CellIndex::new(0, 0, 1));
+        let position = CellIndex::new(0, 0, 0);
+        map.get_cell_mut(position).tile_type = TileType::WallRock;
+        let mut iter = map.iter_mut_near_fluid();
+        assert!(iter.next().is_none());
+        let updated_map = Map::new_from_iter(iter);
+        assert_eq!(updated_map.get_cell(position).tile_type, TileType::WallRock);
+    }
 }
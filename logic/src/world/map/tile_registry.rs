@@ -0,0 +1,322 @@
+//! The properties of each `TileType`, defined in `assets/tiles.txt` (see the comments there for
+//! the format). The file is embedded at build time, so a new tile only needs a variant in
+//! `TileType` and `TILE_TYPES`, and a section in that file.
+
+use crate::screen::assets::TILES_IN_TILESET;
+use crate::screen::gui::format_units::{Grams, Watts};
+use crate::world::map::cell::TILE_TYPES;
+use crate::world::map::{Pressure, TileType};
+use std::sync::OnceLock;
+
+pub const DEFAULT_TILES: &str = include_str!("../../../../assets/tiles.txt");
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkRole {
+    None,
+    Wire,
+    Machine,
+    Ship,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileProperties {
+    pub tile: TileType,
+    pub texture: Option<usize>,
+    /// red, green and blue
+    pub color: Option<[u8; 3]>,
+    pub name: String,
+    pub action: Option<String>,
+    pub tooltip: Vec<String>,
+    pub liquid: bool,
+    pub walkable_horizontal: bool,
+    pub walkable_vertical: bool,
+    pub floodable_horizontal: bool,
+    pub floodable_from_above: bool,
+    pub floodable_from_below: bool,
+    pub opaque_cube: bool,
+    pub covering: bool,
+    pub soil: bool,
+    pub tree: bool,
+    pub network: NetworkRole,
+    pub material: Option<Grams>,
+    pub storage: Option<Grams>,
+    pub power: Watts,
+    pub ages_into: Option<TileType>,
+    /// the pressure of the water released when the tile is dug out
+    pub water: Pressure,
+}
+
+impl TileProperties {
+    fn new(tile: TileType) -> Self {
+        Self {
+            tile,
+            texture: None,
+            color: None,
+            name: String::new(),
+            action: None,
+            tooltip: Vec::new(),
+            liquid: false,
+            walkable_horizontal: false,
+            walkable_vertical: false,
+            floodable_horizontal: false,
+            floodable_from_above: false,
+            floodable_from_below: false,
+            opaque_cube: false,
+            covering: false,
+            soil: false,
+            tree: false,
+            network: NetworkRole::None,
+            material: None,
+            storage: None,
+            power: 0.0,
+            ages_into: None,
+            water: 0,
+        }
+    }
+
+    /// returns false if the flag is unknown
+    fn set_flag(&mut self, flag: &str) -> bool {
+        let field = match flag {
+            "liquid" => &mut self.liquid,
+            "walkable_horizontal" => &mut self.walkable_horizontal,
+            "walkable_vertical" => &mut self.walkable_vertical,
+            "floodable_horizontal" => &mut self.floodable_horizontal,
+            "floodable_from_above" => &mut self.floodable_from_above,
+            "floodable_from_below" => &mut self.floodable_from_below,
+            "opaque_cube" => &mut self.opaque_cube,
+            "covering" => &mut self.covering,
+            "soil" => &mut self.soil,
+            "tree" => &mut self.tree,
+            _ => return false,
+        };
+        *field = true;
+        true
+    }
+
+    fn set(&mut self, property: &str, value: &str) -> Result<(), String> {
+        let value = if property == "tooltip" {
+            value
+        } else {
+            value.trim()
+        };
+        let invalid = || format!("'{}' has an invalid value '{}'", property, value);
+        match property {
+            "texture" => {
+                let texture = value.parse::<usize>().map_err(|_| invalid())?;
+                if texture >= TILES_IN_TILESET {
+                    return Err(format!(
+                        "texture {} is out of the tileset, which has {} tiles",
+                        texture, TILES_IN_TILESET
+                    ));
+                }
+                self.texture = Some(texture);
+            }
+            "color" => self.color = Some(parse_color(value).ok_or_else(invalid)?),
+            "name" => self.name = value.to_string(),
+            "action" => self.action = Some(value.to_string()),
+            "tooltip" => self.tooltip.push(value.to_string()),
+            "flags" => {
+                for flag in value.split(',').map(|flag| flag.trim()) {
+                    if !flag.is_empty() && !self.set_flag(flag) {
+                        return Err(format!("unknown flag '{}'", flag));
+                    }
+                }
+            }
+            "network" => {
+                self.network = match value {
+                    "none" => NetworkRole::None,
+                    "wire" => NetworkRole::Wire,
+                    "machine" => NetworkRole::Machine,
+                    "ship" => NetworkRole::Ship,
+                    _ => return Err(invalid()),
+                }
+            }
+            "material" => self.material = Some(value.parse().map_err(|_| invalid())?),
+            "storage" => self.storage = Some(value.parse().map_err(|_| invalid())?),
+            "power" => self.power = value.parse().map_err(|_| invalid())?,
+            "ages_into" => {
+                self.ages_into = Some(TileType::from_name(value).ok_or_else(invalid)?);
+            }
+            "water" => self.water = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("unknown property '{}'", property)),
+        }
+        Ok(())
+    }
+}
+
+pub struct TileRegistry {
+    /// indexed by `slot()`
+    tiles: Vec<TileProperties>,
+}
+
+impl TileRegistry {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut tiles: Vec<Option<TileProperties>> = vec![None; slot_count()];
+        let mut current: Option<TileType> = None;
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if let Some(section) = trimmed.strip_prefix('[') {
+                let name = section
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("line {}: expected '[TileName]'", line_number))?;
+                let tile = TileType::from_name(name.trim()).ok_or_else(|| {
+                    format!("line {}: unknown tile '{}'", line_number, name.trim())
+                })?;
+                let properties = &mut tiles[slot(tile)];
+                if properties.is_some() {
+                    return Err(format!(
+                        "line {}: tile '{}' is defined more than once",
+                        line_number,
+                        name.trim()
+                    ));
+                }
+                *properties = Some(TileProperties::new(tile));
+                current = Some(tile);
+                continue;
+            }
+            let (property, value) = trimmed
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'property = value'", line_number))?;
+            // only the space after the '=' is removed, to keep the indentation of the tooltips
+            let value = value.strip_prefix(' ').unwrap_or(value).trim_end();
+            let properties = match current {
+                Some(tile) => tiles[slot(tile)].as_mut().unwrap(),
+                None => {
+                    return Err(format!(
+                        "line {}: expected '[TileName]' before the properties",
+                        line_number
+                    ))
+                }
+            };
+            properties
+                .set(property.trim(), value)
+                .map_err(|e| format!("line {}: {}", line_number, e))?;
+        }
+        let mut complete = Vec::new();
+        for (i, properties) in tiles.into_iter().enumerate() {
+            match properties {
+                Some(properties) => complete.push(properties),
+                None => {
+                    let tile = TILE_TYPES.iter().find(|tile| slot(**tile) == i);
+                    if let Some(tile) = tile {
+                        return Err(format!("missing the tile '{:?}'", tile));
+                    }
+                    // not a TileType, its slot will not be used
+                    complete.push(TileProperties::new(TileType::Unset));
+                }
+            }
+        }
+        Ok(Self { tiles: complete })
+    }
+
+    pub fn get(&self, tile: TileType) -> &TileProperties {
+        &self.tiles[slot(tile)]
+    }
+}
+
+/// The tiles are defined in a file that is part of the build, so a malformed file is a bug
+/// (caught by the tests) and it panics.
+pub fn tile_registry() -> &'static TileRegistry {
+    static TILE_REGISTRY: OnceLock<TileRegistry> = OnceLock::new();
+    TILE_REGISTRY.get_or_init(|| {
+        TileRegistry::parse(DEFAULT_TILES)
+            .unwrap_or_else(|e| panic!("invalid assets/tiles.txt: {}", e))
+    })
+}
+
+pub fn tile_properties(tile: TileType) -> &'static TileProperties {
+    tile_registry().get(tile)
+}
+
+/// from `#rrggbb`
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// TileType values go from -1 to the last texture index, which makes a small dense array.
+fn slot(tile: TileType) -> usize {
+    (tile as i32 - TileType::Unset as i32) as usize
+}
+
+fn slot_count() -> usize {
+    TILE_TYPES.iter().map(|tile| slot(*tile)).max().unwrap() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_tiles_are_valid() {
+        let registry = TileRegistry::parse(DEFAULT_TILES).unwrap();
+        for tile in TILE_TYPES {
+            let properties = registry.get(tile);
+            assert_eq!(properties.tile, tile);
+            assert!(!properties.name.is_empty(), "{:?} has no name", tile);
+            if tile != TileType::Unset {
+                assert_eq!(properties.texture, Some(tile as usize));
+                assert!(properties.action.is_some(), "{:?} has no action", tile);
+            }
+        }
+    }
+
+    #[test]
+    fn test_properties() {
+        let registry = TileRegistry::parse(DEFAULT_TILES).unwrap();
+        let stairs = registry.get(TileType::Stairs);
+        assert!(stairs.walkable_horizontal && stairs.walkable_vertical && stairs.covering);
+        assert_eq!(
+            stairs.tooltip,
+            vec!["- Gives access to", "  underground levels"]
+        );
+        assert_eq!(stairs.material, None);
+        let solar_panel = registry.get(TileType::MachineSolarPanel);
+        assert_eq!(solar_panel.network, NetworkRole::Machine);
+        assert_eq!(solar_panel.power, 1000.0);
+        assert_eq!(solar_panel.tooltip[1], "");
+        assert_eq!(
+            registry.get(TileType::TreeSparse).ages_into,
+            Some(TileType::TreeDying)
+        );
+        assert!(registry.get(TileType::WallAquifer).water > 0);
+        assert_eq!(
+            registry.get(TileType::WallRock).color,
+            Some([0x5c, 0x5c, 0x5c])
+        );
+        assert_eq!(registry.get(TileType::WallRock).water, 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| TileRegistry::parse(text).err().unwrap();
+        assert_eq!(
+            error("name = Air"),
+            "line 1: expected '[TileName]' before the properties"
+        );
+        assert_eq!(error("[Lava]"), "line 1: unknown tile 'Lava'");
+        assert_eq!(
+            error("[Air]\n\nflags = shiny"),
+            "line 3: unknown flag 'shiny'"
+        );
+        assert_eq!(
+            error("[Air]\ncolour = blue"),
+            "line 2: unknown property 'colour'"
+        );
+        assert!(error("[Air]\ntexture = 99").starts_with("line 2: texture 99"));
+        assert_eq!(
+            error("[Air]\ncolor = #12345"),
+            "line 2: 'color' has an invalid value '#12345'"
+        );
+        assert_eq!(
+            error("[Air]\n[Air]"),
+            "line 2: tile 'Air' is defined more than once"
+        );
+        assert_eq!(error("[Air]"), "missing the tile 'Unset'");
+    }
+}
@@ -1,17 +1,17 @@
-use crate::world::map::cell::DEFAULT_HEALTH;
+use crate::world::balance::with_balance;
+use crate::world::map::cell::default_health;
+use crate::world::map::tile_registry::tile_properties;
 use crate::world::map::{cell::is_liquid, Cell, CellIndex, Map, TileType};
 use crate::world::networks::network::{Addition, Replacement};
 use crate::world::robots::{DOWN, UP};
 use std::collections::HashSet;
 
-const AIR_LEVELS_FOR_ALLOWING_SOLAR: i32 = 20;
-
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub struct Transformation {
     pub new_tile_type: TileType,
 }
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, PartialOrd, Ord)]
 pub enum TransformationFailure {
     NotEnoughMaterial,
     NotEnoughStorage,
@@ -22,6 +22,8 @@ pub enum TransformationFailure {
     OutOfShipReach,
     CanNotDeconstructShip,
     SplitNetwork,
+    /// the tile is not unlocked in this scenario
+    Locked,
 }
 
 pub fn allowed_transformations(cells: &HashSet<CellIndex>, map: &Map) -> Vec<Transformation> {
@@ -77,7 +79,7 @@ pub fn allowed_transformations_of_cell(
         Unset => {
             panic!("can not transform an UNSET cell!")
         }
-        WallRock => machines_plus(vec![TreeHealthy]),
+        WallRock | WallOre | WallAquifer => machines_plus(vec![TreeHealthy]),
         WallDirt => machines_plus(vec![TreeHealthy, WallRock]),
         FloorRock => machines_plus(vec![TreeHealthy]),
         FloorDirt => machines_plus(vec![FloorRock, TreeHealthy]),
@@ -120,7 +122,7 @@ pub fn allowed_transformations_of_cell(
 pub fn solar_allowed(cell_index: &CellIndex, map: &Map) -> bool {
     column_above_is(
         TileType::Air,
-        AIR_LEVELS_FOR_ALLOWING_SOLAR,
+        with_balance(|balance| balance.air_levels_for_allowing_solar),
         *cell_index,
         map,
     )
@@ -217,7 +219,12 @@ impl Transformation {
             cell.renderable_pressure = 0;
         }
         if self.new_tile_type == TileType::TreeHealthy {
-            cell.health = DEFAULT_HEALTH;
+            cell.health = default_health();
+        }
+        let water = tile_properties(cell.tile_type).water;
+        if water > 0 && self.new_tile_type == TileType::Air {
+            cell.pressure = water;
+            cell.renderable_pressure = water;
         }
         cell.tile_type = self.new_tile_type;
     }
@@ -263,7 +270,7 @@ mod tests {
             let max_cell = CellIndex::new(0, 25, 0);
             let mut map = Map::new_for_cube(min_cell, max_cell);
             map.get_cell_mut(CellIndex::new(0, 0, 0)).tile_type = cell.tile_type;
-            for i in 1..=AIR_LEVELS_FOR_ALLOWING_SOLAR {
+            for i in 1..=with_balance(|balance| balance.air_levels_for_allowing_solar) {
                 map.get_cell_mut(CellIndex::new(0, i, 0)).tile_type = TileType::Air;
             }
             CellTransformationFixture {
@@ -319,6 +326,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_digging_wet_rock_releases_water() {
+        let mut cell = Cell::new(TileType::WallAquifer);
+        Transformation::to(TileType::Air).apply(&mut cell);
+        assert!(cell.pressure > 0);
+
+        let mut cell = Cell::new(TileType::WallOre);
+        Transformation::to(TileType::Air).apply(&mut cell);
+        assert_eq!(cell.pressure, 0);
+    }
+
     #[test]
     fn test_no_identity_on_homogeneous_set() {
         let mut fx = CellTransformationFixture::new();
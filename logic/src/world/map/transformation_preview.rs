@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::screen::gui::format_units::{Grams, Watts};
+use crate::world::map::cell::is_networkable;
+use crate::world::map::transform_cells::{Transformation, TransformationFailure};
+use crate::world::map::transformation_rules::TransformationRules;
+use crate::world::map::{CellIndex, Map};
+use crate::world::networks::network::{material_composition, neighbours, power_balance};
+use crate::world::networks::Networks;
+
+/// What would happen if the transformation was queued now, to show it before the player commits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformationPreview {
+    pub transformation: Transformation,
+    /// cells that would be blocked, with the reason
+    pub forbidden: HashMap<CellIndex, TransformationFailure>,
+    /// material regained minus material spent by the cells that are not forbidden
+    pub material_change: Grams,
+    /// power generated minus power consumed by the cells that are not forbidden
+    pub power_change: Watts,
+}
+
+impl TransformationPreview {
+    pub fn new(
+        cells: &HashSet<CellIndex>,
+        transformation: Transformation,
+        map: &Map,
+        networks: &Networks,
+    ) -> Self {
+        let new_tile_type = transformation.new_tile_type;
+        let reachable = reachable_cells(cells, networks, is_networkable(new_tile_type));
+        let mut forbidden = HashMap::new();
+        let mut material_change = 0.0;
+        let mut power_change = 0.0;
+        for cell_index in cells {
+            let reason = if reachable.contains(cell_index) {
+                TransformationRules::new(*cell_index, new_tile_type, map).is_forbidden()
+            } else {
+                Some(TransformationFailure::OutOfShipReach)
+            };
+            if let Some(reason) = reason {
+                forbidden.insert(*cell_index, reason);
+            } else {
+                let old_tile_type = map.get_cell(*cell_index).tile_type;
+                material_change +=
+                    material_composition(old_tile_type) - material_composition(new_tile_type);
+                power_change += power_balance(new_tile_type) - power_balance(old_tile_type);
+            }
+        }
+        Self {
+            transformation,
+            forbidden,
+            material_change,
+            power_change,
+        }
+    }
+}
+
+/// The cells next to the ship network. If the new tiles join the network, the robots can
+/// continue building from them, so the cells next to those are reachable as well.
+fn reachable_cells(
+    cells: &HashSet<CellIndex>,
+    networks: &Networks,
+    extends_network: bool,
+) -> HashSet<CellIndex> {
+    let mut pending = cells
+        .iter()
+        .filter(|cell_index| networks.is_adjacent_to_ship_network(**cell_index))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut reachable = pending.iter().cloned().collect::<HashSet<_>>();
+    if extends_network {
+        while let Some(cell_index) = pending.pop() {
+            for neighbour in neighbours(cell_index) {
+                if cells.contains(&neighbour) && reachable.insert(neighbour) {
+                    pending.push(neighbour);
+                }
+            }
+        }
+    }
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::{Cell, TileType};
+
+    #[test]
+    fn test_preview_chain_of_wires() {
+        let tiles = (0..4)
+            .map(|x| (CellIndex::new(x, 0, 0), TileType::WallRock))
+            .chain((0..4).map(|x| (CellIndex::new(x, 1, 0), TileType::Air)))
+            .collect();
+        let map = Map::_new_from_tiles(Cell::new(TileType::Air), tiles);
+        let networks = Networks::new(CellIndex::new(0, 1, 0));
+        let cells = HashSet::from([CellIndex::new(1, 1, 0), CellIndex::new(2, 1, 0)]);
+
+        let preview =
+            TransformationPreview::new(&cells, Transformation::to(TileType::Wire), &map, &networks);
+        assert_eq!(preview.forbidden, HashMap::new());
+        assert_eq!(
+            preview.material_change,
+            -2.0 * material_composition(TileType::Wire)
+        );
+
+        let preview = TransformationPreview::new(
+            &cells,
+            Transformation::to(TileType::MachineAssembler),
+            &map,
+            &networks,
+        );
+        assert_eq!(
+            preview.power_change,
+            2.0 * power_balance(TileType::MachineAssembler)
+        );
+    }
+
+    #[test]
+    fn test_preview_out_of_reach() {
+        let tiles = (0..4)
+            .map(|x| (CellIndex::new(x, 0, 0), TileType::WallRock))
+            .chain((0..4).map(|x| (CellIndex::new(x, 1, 0), TileType::Air)))
+            .collect();
+        let map = Map::_new_from_tiles(Cell::new(TileType::Air), tiles);
+        let networks = Networks::new(CellIndex::new(0, 1, 0));
+        let cells = HashSet::from([CellIndex::new(1, 1, 0), CellIndex::new(3, 1, 0)]);
+
+        let preview = TransformationPreview::new(
+            &cells,
+            Transformation::to(TileType::WallRock),
+            &map,
+            &networks,
+        );
+        assert_eq!(
+            preview.forbidden,
+            HashMap::from([(
+                CellIndex::new(3, 1, 0),
+                TransformationFailure::OutOfShipReach
+            )])
+        );
+        assert_eq!(preview.power_change, 0.0);
+    }
+}
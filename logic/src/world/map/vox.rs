@@ -0,0 +1,387 @@
+//! Export and import of maps in the MagicaVoxel `.vox` format, to view and edit them in other
+//! tools. Each tile is a voxel with the `color` of `assets/tiles.txt`, and water has its own
+//! color. Dry air is empty. The z axis of the file is the y axis (up) of the map.
+//!
+//! When importing, each voxel becomes the tile with the closest color, so files painted with
+//! other palettes can be imported too, and files without a palette use the default one of
+//! MagicaVoxel. Only the first model of the file is read.
+
+use crate::world::fluids::VERTICAL_PRESSURE_DIFFERENCE;
+use crate::world::map::cell::{ages, default_health, TILE_TYPES};
+use crate::world::map::tile_registry::tile_properties;
+use crate::world::map::{Cell, CellCubeIterator, CellIndex, Map, TileType};
+
+const MAGIC: &[u8; 4] = b"VOX ";
+const VERSION: i32 = 150;
+/// The coordinates of each voxel are stored in a byte.
+const MAX_SIZE: i32 = 256;
+const WATER_COLOR: [u8; 3] = [0x30, 0x90, 0xe0];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Voxel {
+    Tile(TileType),
+    Water,
+}
+
+/// The voxel and color of each color index of the exported files, starting at index 1.
+fn palette() -> Vec<(Voxel, [u8; 3])> {
+    let mut palette = TILE_TYPES
+        .iter()
+        .filter_map(|tile| {
+            tile_properties(*tile)
+                .color
+                .map(|color| (Voxel::Tile(*tile), color))
+        })
+        .collect::<Vec<_>>();
+    palette.push((Voxel::Water, WATER_COLOR));
+    palette
+}
+
+/// The colors of MagicaVoxel for the files without an RGBA chunk, laid out like that chunk, so
+/// the color index `i` is at `i - 1`. It's a cube of 6 levels per channel without black,
+/// followed by ramps of red, green, blue and grey.
+fn default_colors() -> Vec<u8> {
+    const CUBE_LEVELS: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP_LEVELS: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut colors = Vec::with_capacity(256 * 4);
+    for r in CUBE_LEVELS {
+        for g in CUBE_LEVELS {
+            for b in CUBE_LEVELS {
+                if [r, g, b] != [0, 0, 0] {
+                    colors.extend([r, g, b, 255]);
+                }
+            }
+        }
+    }
+    for channel in 0..3 {
+        for level in RAMP_LEVELS {
+            let mut color = [0, 0, 0, 255];
+            color[channel] = level;
+            colors.extend(color);
+        }
+    }
+    for level in RAMP_LEVELS {
+        colors.extend([level, level, level, 255]);
+    }
+    // the index 256 doesn't exist, as voxels use a byte and 0 means empty
+    colors.extend([0, 0, 0, 0]);
+    colors
+}
+
+impl Map {
+    pub fn to_vox(&self) -> Result<Vec<u8>, String> {
+        let size = self.max_cell - self.min_cell + CellIndex::new(1, 1, 1);
+        if size.max_element() > MAX_SIZE {
+            return Err(format!(
+                "the map is too big for the .vox format, which allows up to {} cells per side",
+                MAX_SIZE
+            ));
+        }
+        let palette = palette();
+        let mut voxels = Vec::new();
+        for position in CellCubeIterator::new(self.min_cell, self.max_cell) {
+            let cell = self.get_cell(position);
+            let voxel = match cell.tile_type {
+                TileType::Air if cell.pressure > 0 => Voxel::Water,
+                tile => Voxel::Tile(tile),
+            };
+            // dry air and unset cells are not in the palette, and stay empty
+            if let Some(index) = palette.iter().position(|(v, _)| *v == voxel) {
+                let offset = position - self.min_cell;
+                voxels.extend([
+                    offset.x as u8,
+                    offset.z as u8,
+                    offset.y as u8,
+                    index as u8 + 1,
+                ]);
+            }
+        }
+
+        let mut size_content = Vec::new();
+        for side in [size.x, size.z, size.y] {
+            size_content.extend(side.to_le_bytes());
+        }
+        let mut voxels_content = ((voxels.len() / 4) as i32).to_le_bytes().to_vec();
+        voxels_content.extend(voxels);
+        let mut palette_content = Vec::new();
+        for i in 0..256 {
+            let [r, g, b] = palette.get(i).map(|(_, color)| *color).unwrap_or([0, 0, 0]);
+            palette_content.extend([r, g, b, 255]);
+        }
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size_content, &[]);
+        write_chunk(&mut children, b"XYZI", &voxels_content, &[]);
+        write_chunk(&mut children, b"RGBA", &palette_content, &[]);
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        write_chunk(&mut bytes, b"MAIN", &[], &children);
+        Ok(bytes)
+    }
+
+    /// The map is centered horizontally, like the generated maps. If there is no spaceship
+    /// (exactly of its color), it lands wherever it can.
+    pub fn from_vox(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.get(0..4) != Some(MAGIC.as_slice()) {
+            return Err("not a .vox file".to_string());
+        }
+        let mut reader = Reader { bytes, offset: 8 };
+        let main = reader.chunk()?;
+        if main.id != *b"MAIN" {
+            return Err("the file doesn't start with a MAIN chunk".to_string());
+        }
+        let mut reader = Reader {
+            bytes: main.children,
+            offset: 0,
+        };
+        let mut size = None;
+        let mut voxels = None;
+        let mut colors = None;
+        while reader.offset < reader.bytes.len() {
+            let chunk = reader.chunk()?;
+            match &chunk.id {
+                b"SIZE" if size.is_none() => size = Some(chunk.content),
+                b"XYZI" if voxels.is_none() => voxels = Some(chunk.content),
+                b"RGBA" => colors = Some(chunk.content),
+                _ => {}
+            }
+        }
+        let size = size.ok_or_else(|| "the file has no SIZE chunk".to_string())?;
+        let voxels = voxels.ok_or_else(|| "the file has no XYZI chunk".to_string())?;
+        let colors = colors.map_or_else(default_colors, |colors| colors.to_vec());
+        if colors.len() < 256 * 4 {
+            return Err("the palette (RGBA chunk) is truncated".to_string());
+        }
+
+        let mut size_reader = Reader {
+            bytes: size,
+            offset: 0,
+        };
+        let (size_x, size_y, size_z) = (size_reader.i32()?, size_reader.i32()?, size_reader.i32()?);
+        // the y of the file is the z of the map
+        let size = CellIndex::new(size_x, size_z, size_y);
+        if size.min_element() < 1 || size.max_element() > MAX_SIZE {
+            return Err(format!("invalid model size {}", size));
+        }
+        let min_cell = CellIndex::new(-size.x / 2, -size.y / 2, -size.z / 2);
+        let max_cell = min_cell + size - CellIndex::new(1, 1, 1);
+        let mut map = Map::new_for_cube(min_cell, max_cell);
+        for position in CellCubeIterator::new(min_cell, max_cell) {
+            *map.get_cell_mut(position) = Cell::new(TileType::Air);
+        }
+
+        let mut voxel_reader = Reader {
+            bytes: voxels,
+            offset: 0,
+        };
+        let voxel_count = voxel_reader.i32()?;
+        let palette = palette();
+        for _ in 0..voxel_count {
+            let [x, y, z, color_index] = voxel_reader.bytes::<4>()?;
+            let position = min_cell + CellIndex::new(x as i32, z as i32, y as i32);
+            if position.cmpgt(max_cell).any() {
+                return Err(format!("voxel ({} {} {}) is out of the model", x, y, z));
+            }
+            let i_color = (color_index as usize).max(1) - 1;
+            let color = [
+                colors[i_color * 4],
+                colors[i_color * 4 + 1],
+                colors[i_color * 4 + 2],
+            ];
+            let cell = map.get_cell_mut(position);
+            match closest_voxel(&palette, color) {
+                Voxel::Water => {
+                    cell.pressure = VERTICAL_PRESSURE_DIFFERENCE;
+                    cell.renderable_pressure = cell.pressure;
+                }
+                Voxel::Tile(tile) => {
+                    *cell = Cell::new(tile);
+                    if ages(tile) {
+                        cell.health = default_health();
+                    }
+                    if tile == TileType::MachineShip {
+                        if map.ship_position.is_some() {
+                            return Err("the file has more than one spaceship".to_string());
+                        }
+                        map.ship_position = Some(position);
+                    }
+                }
+            }
+        }
+        if map.ship_position.is_none() {
+            map.land_ship_anywhere();
+        }
+        map.compress();
+        Ok(map)
+    }
+}
+
+/// The ship only matches its exact color, as it's unique and other grey voxels should not
+/// become ships.
+fn closest_voxel(palette: &[(Voxel, [u8; 3])], color: [u8; 3]) -> Voxel {
+    let distance = |other: &[u8; 3]| {
+        (0..3)
+            .map(|i| (color[i] as i32 - other[i] as i32).pow(2))
+            .sum::<i32>()
+    };
+    palette
+        .iter()
+        .filter(|(voxel, other)| {
+            *voxel != Voxel::Tile(TileType::MachineShip) || distance(other) == 0
+        })
+        .min_by_key(|(_, other)| distance(other))
+        .map(|(voxel, _)| *voxel)
+        .unwrap()
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend(id);
+    bytes.extend((content.len() as i32).to_le_bytes());
+    bytes.extend((children.len() as i32).to_le_bytes());
+    bytes.extend(content);
+    bytes.extend(children);
+}
+
+struct Chunk<'a> {
+    id: [u8; 4],
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let slice = self
+            .bytes
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| "the file is truncated".to_string())?;
+        self.offset += len;
+        Ok(slice)
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.slice(N)?.try_into().unwrap())
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.bytes::<4>()?))
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        usize::try_from(self.i32()?).map_err(|_| "the file has a negative length".to_string())
+    }
+
+    fn chunk(&mut self) -> Result<Chunk<'a>, String> {
+        let id = self.bytes::<4>()?;
+        let content_len = self.length()?;
+        let children_len = self.length()?;
+        Ok(Chunk {
+            id,
+            content: self.slice(content_len)?,
+            children: self.slice(children_len)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::{MapType, MountainsParams};
+
+    #[test]
+    fn test_roundtrip() {
+        let map =
+            Map::new_generated_with_seed(MapType::Mountains(MountainsParams::default()), Some(4));
+        let loaded = Map::from_vox(&map.to_vox().unwrap()).unwrap();
+        assert_eq!(loaded.min_cell(), map.min_cell());
+        assert_eq!(loaded.max_cell(), map.max_cell());
+        assert_eq!(loaded.get_ship_position(), map.get_ship_position());
+        for position in CellCubeIterator::new(map.min_cell(), map.max_cell()) {
+            let cell = map.get_cell(position);
+            let loaded_cell = loaded.get_cell(position);
+            assert_eq!(loaded_cell.tile_type, cell.tile_type, "at {}", position);
+            assert_eq!(
+                loaded_cell.pressure > 0,
+                cell.pressure > 0,
+                "at {}",
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn test_tile_colors_are_different() {
+        let palette = palette();
+        for (voxel, color) in &palette {
+            assert_eq!(closest_voxel(&palette, *color), *voxel);
+        }
+    }
+
+    #[test]
+    fn test_other_palettes() {
+        let mut map = Map::new_for_cube(CellIndex::new(0, 0, 0), CellIndex::new(2, 2, 2));
+        for position in CellCubeIterator::new(map.min_cell(), map.max_cell()) {
+            *map.get_cell_mut(position) = Cell::new(TileType::Air);
+        }
+        map.get_cell_mut(CellIndex::new(0, 0, 0)).tile_type = TileType::WallRock;
+        let mut bytes = map.to_vox().unwrap();
+        // changes the first color of the palette, which is the rock, to a slightly lighter grey
+        let rock_color = bytes.len() - 256 * 4;
+        bytes[rock_color..rock_color + 3].copy_from_slice(&[0x60, 0x60, 0x60]);
+
+        let loaded = Map::from_vox(&bytes).unwrap();
+        let rock = loaded.min_cell();
+        assert_eq!(loaded.get_cell(rock).tile_type, TileType::WallRock);
+        assert_eq!(
+            loaded.get_ship_position(),
+            Some(rock + CellIndex::new(0, 1, 0))
+        );
+    }
+
+    #[test]
+    fn test_default_palette() {
+        let colors = default_colors();
+        assert_eq!(colors.len(), 256 * 4);
+        assert_eq!(colors[0..4], [0xff, 0xff, 0xff, 255]);
+        assert_eq!(colors[214 * 4..215 * 4], [0, 0, 0x33, 255]);
+        assert_eq!(colors[215 * 4..216 * 4], [0xee, 0, 0, 255]);
+        assert_eq!(colors[254 * 4..255 * 4], [0x11, 0x11, 0x11, 255]);
+
+        // a column of 2 cells, with a voxel of the color index 252, grey 0x555555, at the bottom
+        let mut size = Vec::new();
+        for side in [1, 1, 2] {
+            size.extend(i32::to_le_bytes(side));
+        }
+        let mut voxels = 1i32.to_le_bytes().to_vec();
+        voxels.extend([0, 0, 0, 252]);
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size, &[]);
+        write_chunk(&mut children, b"XYZI", &voxels, &[]);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        write_chunk(&mut bytes, b"MAIN", &[], &children);
+
+        let loaded = Map::from_vox(&bytes).unwrap();
+        let rock = loaded.min_cell();
+        assert_eq!(loaded.get_cell(rock).tile_type, TileType::WallRock);
+        assert_eq!(
+            loaded.get_ship_position(),
+            Some(rock + CellIndex::new(0, 1, 0))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Map::from_vox(b"PNG").err().unwrap(), "not a .vox file");
+        let map = Map::new_for_cube(CellIndex::new(0, 0, 0), CellIndex::new(0, 0, 0));
+        let bytes = map.to_vox().unwrap();
+        assert_eq!(
+            Map::from_vox(&bytes[..bytes.len() - 1]).err().unwrap(),
+            "the file is truncated"
+        );
+    }
+}
@@ -1,11 +1,17 @@
 pub mod network;
 
+use crate::common::snapshot::{
+    cell_index_str, parse_cell_index, parse_tile, parse_value, Snapshot, SnapshotReader,
+    SnapshotWriter,
+};
 use crate::screen::gui::format_units::{format_liters, Grams};
+use crate::world::balance::with_balance;
+use crate::world::event_log::representative_cell;
 use crate::world::map::cell::is_networkable;
 use crate::world::map::transform_cells::TransformationFailure;
 use crate::world::map::{CellIndex, TileType};
 use crate::world::networks::network::{
-    Addition, Network, Node, Replacement, MATERIAL_NEEDED_FOR_A_MACHINE, SPACESHIP_INITIAL_STORAGE,
+    material_composition, neighbours, Addition, Network, Node, Nodes, Replacement,
 };
 
 pub struct Networks {
@@ -13,11 +19,22 @@ pub struct Networks {
     ship_network: Network,
     unconnected_networks: Vec<Network>,
     air_cleaned: f64,
+    events: Vec<NetworkEvent>,
+}
+
+/// Things that happened to the networks since the last `take_events()`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NetworkEvent {
+    /// how many networks were joined into one by adding the machine at the position
+    Merged(CellIndex, usize),
+    /// the position is any machine of the network
+    LostPower(CellIndex),
 }
 
 impl Networks {
     pub fn new(ship_position: CellIndex) -> Self {
-        let mut network = Network::new_with_storage(SPACESHIP_INITIAL_STORAGE);
+        let initial_storage = with_balance(|balance| balance.spaceship_initial_storage);
+        let mut network = Network::new_with_storage(initial_storage);
         network.add_or_panic(
             Node {
                 position: ship_position,
@@ -30,6 +47,7 @@ impl Networks {
             ship_network: network,
             unconnected_networks: Vec::new(),
             air_cleaned: 0.0,
+            events: Vec::new(),
         }
     }
 
@@ -93,6 +111,12 @@ impl Networks {
             match addition {
                 Addition::Ok => {
                     let adjacent_networks = self.get_adjacent_networks(cell_index);
+                    if adjacent_networks.len() > 0 {
+                        self.events.push(NetworkEvent::Merged(
+                            cell_index,
+                            adjacent_networks.len() + 1,
+                        ));
+                    }
                     for i_network in adjacent_networks.iter().rev() {
                         let joining_network = self.unconnected_networks.remove(*i_network);
                         self.re_add_network(joining_network);
@@ -170,8 +194,10 @@ impl Networks {
     fn re_add_network(&mut self, network_to_split: Network) {
         let storage_to_redistribute = network_to_split.stored_resources;
         let mut storage_per_node = storage_to_redistribute;
+        // re-adding node by node can look like merges, but the caller already knows what happened
+        let events_before = self.events.len();
         for node in network_to_split.nodes() {
-            storage_per_node += MATERIAL_NEEDED_FOR_A_MACHINE; // assumes the network already paid for this
+            storage_per_node += material_composition(node.tile); // assumes the network already paid for this
             if !self.add_with_storage(
                 node.position,
                 node.tile,
@@ -184,6 +210,7 @@ impl Networks {
         if storage_per_node > 0.0 {
             println!("Bug: not enough capacity");
         }
+        self.events.truncate(events_before);
     }
 
     fn get_adjacent_networks(&self, cell_index: CellIndex) -> Vec<usize> {
@@ -210,6 +237,10 @@ impl Networks {
         storage: &mut Grams,
     ) {
         assert!(to_be_merged.len() > 0);
+        if to_be_merged.len() > 1 {
+            self.events
+                .push(NetworkEvent::Merged(node.position, to_be_merged.len()));
+        }
         let to_be_removed = &to_be_merged[1..];
         let kept = to_be_merged[0];
         let mut networks_to_be_merged = Vec::new();
@@ -245,10 +276,22 @@ impl Networks {
 
     pub fn update(&mut self) {
         let mut air_cleaned = 0.0;
+        let mut lost_power = Vec::new();
         for network in self.iter_mut() {
-            air_cleaned += network.update().air_cleaned;
+            let update = network.update();
+            air_cleaned += update.air_cleaned;
+            if update.lost_power {
+                if let Some(position) = representative_cell(network.nodes.keys()) {
+                    lost_power.push(NetworkEvent::LostPower(position));
+                }
+            }
         }
         self.air_cleaned += air_cleaned;
+        self.events.append(&mut lost_power);
+    }
+
+    pub fn take_events(&mut self) -> Vec<NetworkEvent> {
+        std::mem::take(&mut self.events)
     }
 
     pub fn len(&self) -> usize {
@@ -305,6 +348,50 @@ impl Networks {
         *self = Self::new(self.ship_position)
     }
 
+    /// Sets the tiles without checking material, storage or splits, and regroups the machines into
+    /// connected networks. Meant for undo and redo, where the changes were already valid once.
+    /// The `resources_delta` goes to the ship network, as that's the one robots build from. The
+    /// storage of each network is clamped to its capacity, like in `Network::try_add_resources`.
+    pub fn force_replace(&mut self, tiles: &[(CellIndex, TileType)], resources_delta: Grams) {
+        let mut nodes = Nodes::new();
+        for network in self.iter() {
+            nodes.extend(network.nodes.iter());
+        }
+        for (position, tile) in tiles {
+            if is_networkable(*tile) {
+                nodes.insert(*position, *tile);
+            } else {
+                nodes.remove(position);
+            }
+        }
+        let ship_storage = self.ship_network.stored_resources + resources_delta;
+        let old_networks = std::mem::take(&mut self.unconnected_networks);
+        self.ship_network.nodes = take_connected(&mut nodes, self.ship_position);
+        self.ship_network.stored_resources = 0.0;
+        self.ship_network.try_add_resources(ship_storage.max(0.0));
+        while let Some(start) = nodes.keys().next().cloned() {
+            let mut network = Network::new();
+            network.nodes = take_connected(&mut nodes, start);
+            self.unconnected_networks.push(network);
+        }
+        for old_network in old_networks {
+            let kept = old_network
+                .nodes
+                .keys()
+                .find_map(|position| self.get_network_index(*position));
+            let overflow = match kept {
+                Some(i_network) => {
+                    let network = self.iter_mut().nth(i_network).unwrap();
+                    network.inherit_power_state(&old_network);
+                    network.try_add_resources(old_network.stored_resources)
+                }
+                None => old_network.stored_resources,
+            };
+            // what doesn't fit anywhere is lost, like when removing a storage machine
+            self.ship_network.try_add_resources(overflow);
+        }
+    }
+
     pub fn get(&self, position: CellIndex) -> Option<Node> {
         let node_opt = (&self.ship_network).get_node(position);
         if node_opt.is_some() {
@@ -318,18 +405,105 @@ impl Networks {
         }
         None
     }
+    /// returns the index of the network containing the position, in the order of `iter()`,
+    /// so the ship network is always 0
+    pub fn get_network_index(&self, position: CellIndex) -> Option<usize> {
+        self.iter()
+            .position(|network| network.get_node(position).is_some())
+    }
     pub fn is_in_ship_network(&self, position: CellIndex) -> bool {
         let node_opt = self.ship_network.get_node(position);
         return node_opt.is_some();
     }
 }
 
+impl Snapshot for Networks {
+    fn save(&self, snapshot: &mut SnapshotWriter) {
+        snapshot.line("networks.ship_position", cell_index_str(self.ship_position));
+        snapshot.line("networks.air_cleaned", self.air_cleaned);
+        // the ship network is always the first one
+        for (i_network, network) in self.iter().enumerate() {
+            snapshot.line(
+                "networks.network",
+                format!(
+                    "{} {} {}",
+                    i_network,
+                    network.stored_resources,
+                    network.was_powered()
+                ),
+            );
+            for node in network.nodes() {
+                snapshot.line(
+                    "networks.node",
+                    format!(
+                        "{} {} {:?}",
+                        i_network,
+                        cell_index_str(node.position),
+                        node.tile
+                    ),
+                );
+            }
+        }
+    }
+
+    fn load(snapshot: &SnapshotReader) -> Result<Self, String> {
+        let ship_position = snapshot.get_cell_index("networks.ship_position")?;
+        let mut networks = Vec::new();
+        let key = "networks.network";
+        for values in snapshot.get_all(key) {
+            let i_network: usize = parse_value(values, 0, key)?;
+            if i_network != networks.len() {
+                return Err(format!("'{}' {} is out of order", key, i_network));
+            }
+            networks.push(Network::new_with_nodes(
+                Nodes::new(),
+                parse_value(values, 1, key)?,
+                parse_value(values, 2, key)?,
+            ));
+        }
+        let key = "networks.node";
+        for values in snapshot.get_all(key) {
+            let i_network: usize = parse_value(values, 0, key)?;
+            let network = networks
+                .get_mut(i_network)
+                .ok_or_else(|| format!("'{}' refers to a missing network {}", key, i_network))?;
+            network.nodes.insert(
+                parse_cell_index(values, 1, key)?,
+                parse_tile(values, 4, key)?,
+            );
+        }
+        if networks.is_empty() {
+            return Err("missing the ship network in 'networks.network'".to_string());
+        }
+        let ship_network = networks.remove(0);
+        Ok(Networks {
+            ship_position,
+            ship_network,
+            unconnected_networks: networks,
+            air_cleaned: snapshot.get("networks.air_cleaned")?,
+            events: Vec::new(),
+        })
+    }
+}
+
+/// Removes from `nodes` all the ones connected to `start`, and returns them.
+fn take_connected(nodes: &mut Nodes, start: CellIndex) -> Nodes {
+    let mut connected = Nodes::new();
+    let mut pending = vec![start];
+    while let Some(position) = pending.pop() {
+        if let Some(tile) = nodes.remove(&position) {
+            connected.insert(position, tile);
+            pending.extend(neighbours(position));
+        }
+    }
+    connected
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::world::map::TileType::{MachineStorage, WallRock, Wire};
     use crate::world::map::{CellIndex, TileType};
-    use crate::world::networks::network::WALL_WEIGHT;
     use TileType::{Air, MachineAirCleaner, MachineAssembler};
 
     #[test]
@@ -339,7 +513,7 @@ mod tests {
             networks.add(CellIndex::new(0, 0, 1), MachineAssembler, Air),
             true
         );
-        let mut material_for_separate_network = MATERIAL_NEEDED_FOR_A_MACHINE;
+        let mut material_for_separate_network = material_composition(MachineAssembler);
         assert_eq!(
             networks.add_with_storage(
                 CellIndex::new(0, 0, 3),
@@ -350,9 +524,78 @@ mod tests {
             true
         );
         assert_eq!(networks.len(), 2);
+        assert_eq!(networks.get_network_index(CellIndex::new(0, 0, 1)), Some(0));
+        assert_eq!(networks.get_network_index(CellIndex::new(0, 0, 3)), Some(1));
+        assert_eq!(networks.get_network_index(CellIndex::new(0, 0, 2)), None);
         networks.add(CellIndex::new(0, 0, 2), MachineAssembler, Air);
         assert_eq!(networks.len(), 1);
+        assert_eq!(networks.get_network_index(CellIndex::new(0, 0, 3)), Some(0));
         assert_eq!(networks.get_non_ship_machine_count(), 3);
+        assert_eq!(
+            networks.take_events(),
+            vec![NetworkEvent::Merged(CellIndex::new(0, 0, 2), 2)]
+        );
+        assert_eq!(networks.take_events(), vec![]);
+    }
+
+    #[test]
+    fn test_force_replace_splits_and_restores() {
+        let mut networks = Networks::new_default();
+        networks.add(CellIndex::new(0, 0, 1), MachineAssembler, Air);
+        networks.add(CellIndex::new(0, 0, 2), MachineAssembler, Air);
+        let stored = networks.get_stored_resources();
+
+        networks.force_replace(&[(CellIndex::new(0, 0, 1), Air)], -1.0);
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks.get_network_index(CellIndex::new(0, 0, 2)), Some(1));
+        assert_eq!(networks.get_stored_resources(), stored - 1.0);
+
+        networks.force_replace(&[(CellIndex::new(0, 0, 1), MachineAssembler)], 1.0);
+        assert_eq!(networks.len(), 1);
+        assert_eq!(networks.get_non_ship_machine_count(), 2);
+        assert_eq!(networks.get_stored_resources(), stored);
+        assert_eq!(networks.take_events(), vec![]);
+    }
+
+    #[test]
+    fn test_force_replace_clamps_to_capacity() {
+        let mut networks = Networks::new_default();
+        let capacity = networks.get_storage_capacity();
+        networks.force_replace(&[], capacity);
+        assert_eq!(networks.get_stored_resources(), capacity);
+
+        networks.add(CellIndex::new(0, 0, 1), MachineStorage, Air);
+        let capacity_with_storage = networks.get_storage_capacity();
+        networks.force_replace(&[], capacity_with_storage);
+        networks.force_replace(&[(CellIndex::new(0, 0, 1), Air)], 0.0);
+        assert_eq!(networks.get_stored_resources(), capacity);
+    }
+
+    #[test]
+    fn test_lost_power_is_reported_once() {
+        let mut networks = Networks::new_default();
+        networks.update();
+        assert_eq!(networks.take_events(), vec![]);
+        networks.add(CellIndex::new(0, 0, 1), MachineAssembler, Air);
+        networks.update();
+        networks.update();
+        assert_eq!(
+            networks.take_events(),
+            vec![NetworkEvent::LostPower(CellIndex::new(0, 0, 0))]
+        );
+    }
+
+    #[test]
+    fn test_new_networks_dont_lose_power() {
+        let mut networks = Networks::new_default();
+        networks.add(CellIndex::new(0, 0, 5), MachineAssembler, Air);
+        networks.update();
+        assert_eq!(networks.take_events(), vec![]);
+
+        // rebuilding the networks keeps knowing that it had no power
+        networks.force_replace(&[(CellIndex::new(0, 0, 6), MachineAssembler)], 0.0);
+        networks.update();
+        assert_eq!(networks.take_events(), vec![]);
     }
 
     #[test]
@@ -384,7 +627,7 @@ mod tests {
 
         assert_eq!(
             resources_after_constructing - resources_before_constructing,
-            WALL_WEIGHT - 3.0 * MATERIAL_NEEDED_FOR_A_MACHINE
+            material_composition(WallRock) - 3.0 * material_composition(Wire)
         )
     }
 
@@ -507,9 +750,7 @@ mod tests {
 mod storage_tests {
     use super::*;
     use crate::world::map::TileType::WallRock;
-    use crate::world::networks::network::{
-        MAX_STORAGE_PER_SPACESHIP, SPACESHIP_INITIAL_STORAGE, WALL_WEIGHT,
-    };
+    use crate::world::networks::network::storage_capacity;
     use TileType::{Air, MachineStorage, Wire};
 
     #[test]
@@ -517,9 +758,13 @@ mod storage_tests {
         let networks = Networks::new_default();
         assert_eq!(
             networks.get_stored_resources(),
-            SPACESHIP_INITIAL_STORAGE - MATERIAL_NEEDED_FOR_A_MACHINE
+            with_balance(|balance| balance.spaceship_initial_storage)
+                - material_composition(TileType::MachineShip)
+        );
+        assert_eq!(
+            networks.get_storage_capacity(),
+            storage_capacity(TileType::MachineShip)
         );
-        assert_eq!(networks.get_storage_capacity(), MAX_STORAGE_PER_SPACESHIP);
     }
     #[test]
     fn test_can_not_add_machine_without_resources() {
@@ -585,7 +830,8 @@ mod storage_tests {
         );
         assert_eq!(
             networks.get_stored_resources(),
-            material_before_constructing + WALL_WEIGHT - MATERIAL_NEEDED_FOR_A_MACHINE
+            material_before_constructing + material_composition(WallRock)
+                - material_composition(MachineStorage)
         );
 
         let material_before_constructing = networks.get_stored_resources();
@@ -595,7 +841,7 @@ mod storage_tests {
         );
         assert_eq!(
             networks.get_stored_resources(),
-            material_before_constructing - MATERIAL_NEEDED_FOR_A_MACHINE
+            material_before_constructing - material_composition(MachineStorage)
         );
     }
 
@@ -611,7 +857,7 @@ mod storage_tests {
         assert_eq!(networks.add(CellIndex::new(1, 0, 0), WallRock, Air), true);
         assert_eq!(
             networks.get_stored_resources(),
-            material_before_building - WALL_WEIGHT
+            material_before_building - material_composition(WallRock)
         )
     }
 }
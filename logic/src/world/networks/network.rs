@@ -3,22 +3,14 @@ use std::collections::{HashMap, HashSet, VecDeque};
 use crate::screen::gui::format_units::{
     format_grams, format_unit, format_watts, Grams, Liters, Watts,
 };
+use crate::world::balance::with_balance;
 use crate::world::map::cell::is_networkable;
+use crate::world::map::tile_registry::tile_properties;
 use crate::world::map::{CellIndex, TileType};
 use crate::world::robots::CellIndexDiff;
 
-pub const POWER_PER_SOLAR_PANEL: Watts = 1000.0;
-pub const POWER_CONSUMED_PER_MACHINE: Watts = POWER_PER_SOLAR_PANEL;
-
-const AIR_CLEANED_PER_CLEANER_PER_UPDATE: Liters = 1.0;
-
-pub const MATERIAL_NEEDED_FOR_A_MACHINE: Grams = 1_000_000.0;
-pub const MAX_STORAGE_PER_SPACESHIP: Grams = 5.0 * MATERIAL_NEEDED_FOR_A_MACHINE;
-pub const SPACESHIP_INITIAL_STORAGE: Grams = 5.0 * MATERIAL_NEEDED_FOR_A_MACHINE;
-// pub const MAX_STORAGE_PER_MACHINE: Grams = 1_000_000.0;
-pub const MAX_STORAGE_PER_MACHINE: Grams = 0.0;
-pub const MAX_STORAGE_PER_STORAGE_MACHINE: Grams = 10_000_000.0;
-pub const WALL_WEIGHT: Grams = 10_000_000.0;
+// the material, storage and power of each tile are in `tile_registry`, and they can be
+// overridden in the balance file
 
 // pub type Nodes = Vec<Node>;
 pub type Nodes = HashMap<CellIndex, TileType>;
@@ -27,6 +19,8 @@ pub type Nodes = HashMap<CellIndex, TileType>;
 pub struct Network {
     pub nodes: Nodes,
     pub stored_resources: Grams,
+    /// whether the power was satisfied in the last update
+    powered: bool,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -54,6 +48,7 @@ pub enum Addition {
 
 pub struct NetworkUpdate {
     pub air_cleaned: Liters,
+    pub lost_power: bool,
 }
 
 // pub struct NetworkEffect {
@@ -66,17 +61,39 @@ impl Network {
         Network {
             nodes: Nodes::new(),
             stored_resources: 0.0,
+            powered: false,
         }
     }
     pub fn new_with_storage(initial_storage: Grams) -> Self {
         Network {
             nodes: Nodes::new(),
             stored_resources: initial_storage,
+            powered: false,
         }
     }
+    pub fn new_with_nodes(nodes: Nodes, stored_resources: Grams, powered: bool) -> Self {
+        Network {
+            nodes,
+            stored_resources,
+            powered,
+        }
+    }
+    /// whether the power was satisfied in the last update
+    pub fn was_powered(&self) -> bool {
+        self.powered
+    }
+    /// For networks rebuilt from a previous one, so that losing the power it had is noticed,
+    /// while new networks don't report losing a power they never had.
+    pub fn inherit_power_state(&mut self, previous: &Network) {
+        self.powered |= previous.powered;
+    }
     pub fn update(&mut self) -> NetworkUpdate {
+        let powered = self.is_power_satisfied();
+        let lost_power = self.powered && !powered;
+        self.powered = powered;
         NetworkUpdate {
-            air_cleaned: if self.is_power_satisfied() {
+            lost_power,
+            air_cleaned: if powered {
                 let mut air_cleaners = 0;
                 for node in self.nodes() {
                     match node.tile {
@@ -85,7 +102,7 @@ impl Network {
                         _ => {}
                     }
                 }
-                air_cleaners as f64 * AIR_CLEANED_PER_CLEANER_PER_UPDATE
+                air_cleaners as f64 * with_balance(|balance| balance.air_cleaned_per_cleaner)
             } else {
                 0.0
             },
@@ -105,9 +122,10 @@ impl Network {
     }
 
     fn get_power_generated(&self) -> f64 {
-        let solar_panels_count = self.count_tiles_of_type_in(&[TileType::MachineSolarPanel]);
-        let power = solar_panels_count as f64 * POWER_PER_SOLAR_PANEL;
-        power
+        self.nodes
+            .values()
+            .map(|tile| power_balance(*tile).max(0.0))
+            .sum()
     }
 
     pub fn get_power_required_str(&self) -> String {
@@ -116,14 +134,10 @@ impl Network {
     }
 
     fn get_power_required(&self) -> f64 {
-        let machines_count = self.count_tiles_of_type_in(&[
-            TileType::MachineDrill,
-            TileType::MachineAssembler,
-            TileType::MachineAirCleaner,
-            TileType::MachineStorage,
-        ]);
-        let power = machines_count as f64 * POWER_CONSUMED_PER_MACHINE;
-        power
+        self.nodes
+            .values()
+            .map(|tile| -power_balance(*tile).min(0.0))
+            .sum()
     }
 
     pub fn is_power_satisfied(&self) -> bool {
@@ -143,7 +157,8 @@ impl Network {
     fn get_air_cleaned_speed(&self) -> f64 {
         if self.is_power_satisfied() {
             let air_cleaners_count = self.count_tiles_of_type_in(&[TileType::MachineAirCleaner]);
-            let liters = air_cleaners_count as f64 * f64::from(AIR_CLEANED_PER_CLEANER_PER_UPDATE);
+            let liters =
+                air_cleaners_count as f64 * with_balance(|balance| balance.air_cleaned_per_cleaner);
             liters
         } else {
             0.0
@@ -164,11 +179,10 @@ impl Network {
     }
 
     pub fn get_storage_capacity(&self) -> Grams {
-        let storage_count = self.count_tiles_of_type_in(&[TileType::MachineStorage]);
-        let ships = self.count_tiles_of_type_in(&[TileType::MachineShip]);
-        storage_count as f64 * MAX_STORAGE_PER_STORAGE_MACHINE
-            + ships as f64 * MAX_STORAGE_PER_SPACESHIP
-            + (self.len() as i32 - storage_count - ships) as f64 * MAX_STORAGE_PER_MACHINE
+        self.nodes
+            .values()
+            .map(|tile| storage_capacity(*tile))
+            .sum()
     }
     pub fn get_storage_capacity_str(&self) -> String {
         format_grams(self.get_storage_capacity())
@@ -355,54 +369,30 @@ impl Network {
 }
 
 pub fn material_composition(tile: TileType) -> Grams {
-    match tile {
-        TileType::Unset => {
-            panic!("should not be asking the amount of material of an Unset tile")
-        }
-        TileType::WallRock | TileType::WallDirt => WALL_WEIGHT,
-        TileType::FloorRock | TileType::FloorDirt => {
-            panic!("floor is deprecated, should not be asking amount of material")
-        }
-        TileType::Stairs => {
-            panic!("stairs are deprecated, should not be asking amount of material")
-        }
-        TileType::Air => 0.0,
-        TileType::Wire
-        | TileType::MachineAssembler
-        | TileType::MachineAirCleaner
-        | TileType::MachineDrill
-        | TileType::MachineSolarPanel
-        | TileType::MachineShip
-        | TileType::MachineStorage => MATERIAL_NEEDED_FOR_A_MACHINE,
-        TileType::TreeHealthy | TileType::TreeSparse | TileType::TreeDying | TileType::TreeDead => {
-            0.0
-        }
-    }
+    let material = with_balance(|balance| balance.tile_material(tile));
+    material
+        .or(tile_properties(tile).material)
+        .unwrap_or_else(|| {
+            panic!(
+                "should not be asking the amount of material of a {:?} tile",
+                tile
+            )
+        })
+}
+/// power generated minus power consumed by a single tile
+pub fn power_balance(tile: TileType) -> Watts {
+    with_balance(|balance| balance.tile_power(tile)).unwrap_or(tile_properties(tile).power)
 }
 pub fn storage_capacity(tile: TileType) -> Grams {
-    match tile {
-        TileType::Unset => {
-            panic!("should not be asking the amount of capacity of an Unset tile")
-        }
-        TileType::WallRock | TileType::WallDirt => 0.0,
-        TileType::FloorRock | TileType::FloorDirt => {
-            panic!("floor is deprecated, should not be asking amount of capacity")
-        }
-        TileType::Stairs => {
-            panic!("stairs are deprecated, should not be asking amount of capacity")
-        }
-        TileType::Air => 0.0,
-        TileType::Wire
-        | TileType::MachineAssembler
-        | TileType::MachineAirCleaner
-        | TileType::MachineDrill
-        | TileType::MachineSolarPanel => MAX_STORAGE_PER_MACHINE,
-        TileType::MachineShip => MAX_STORAGE_PER_SPACESHIP,
-        TileType::MachineStorage => MAX_STORAGE_PER_STORAGE_MACHINE,
-        TileType::TreeHealthy | TileType::TreeSparse | TileType::TreeDying | TileType::TreeDead => {
-            0.0
-        }
-    }
+    let storage = with_balance(|balance| balance.tile_storage(tile));
+    storage
+        .or(tile_properties(tile).storage)
+        .unwrap_or_else(|| {
+            panic!(
+                "should not be asking the amount of capacity of a {:?} tile",
+                tile
+            )
+        })
 }
 
 impl Default for Network {
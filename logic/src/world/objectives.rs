@@ -0,0 +1,514 @@
+//! The objectives of a campaign, defined in `assets/objectives.txt` or in another file with the
+//! same format given with `--objectives` (see the comments there for the format). An objective is
+//! active once the objectives it comes after are completed, and it's completed when all its
+//! conditions are met at the same time. The game is won when all the objectives are completed.
+
+use crate::world::balance::with_balance;
+use crate::world::event_log::EventKind;
+use crate::world::map::tile_registry::{tile_properties, NetworkRole};
+use crate::world::map::TileType;
+use crate::world::{AgeInMinutes, GameGoalState, World};
+
+pub const DEFAULT_OBJECTIVES: &str = include_str!("../../../assets/objectives.txt");
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Quantity {
+    AirCleaned,
+    StoredResources,
+    Life,
+    /// apart from the ship
+    Machines,
+    Minutes,
+    /// only of the tiles that are part of a network or alive, as the rest would need to go
+    /// through the whole map
+    Tiles(TileType),
+}
+
+impl Quantity {
+    fn parse(text: &str) -> Result<Self, String> {
+        let quantity = match text {
+            "air_cleaned" => Quantity::AirCleaned,
+            "stored_resources" => Quantity::StoredResources,
+            "life" => Quantity::Life,
+            "machines" => Quantity::Machines,
+            "minutes" => Quantity::Minutes,
+            _ => {
+                let name = text
+                    .strip_prefix("tiles:")
+                    .ok_or_else(|| format!("unknown quantity '{}'", text))?;
+                let tile =
+                    TileType::from_name(name).ok_or_else(|| format!("unknown tile '{}'", name))?;
+                let properties = tile_properties(tile);
+                if properties.network == NetworkRole::None && !properties.tree {
+                    return Err(format!(
+                        "only the tiles of networks and trees can be counted, not '{}'",
+                        name
+                    ));
+                }
+                Quantity::Tiles(tile)
+            }
+        };
+        Ok(quantity)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    AtLeast,
+    AtMost,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Number(f64),
+    /// the name of a goal in the balance file, see `Balance::goal()`
+    Balance(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub quantity: Quantity,
+    pub comparison: Comparison,
+    pub target: Target,
+}
+
+impl Condition {
+    /// Parses `QUANTITY >= TARGET` or `QUANTITY <= TARGET`.
+    fn parse(text: &str) -> Result<Self, String> {
+        let (quantity, comparison, target) = if let Some((quantity, target)) = text.split_once(">=")
+        {
+            (quantity, Comparison::AtLeast, target)
+        } else if let Some((quantity, target)) = text.split_once("<=") {
+            (quantity, Comparison::AtMost, target)
+        } else {
+            return Err(format!(
+                "expected 'QUANTITY >= TARGET' or 'QUANTITY <= TARGET' but got '{}'",
+                text
+            ));
+        };
+        let target = target.trim();
+        let target = match target.parse::<f64>() {
+            Ok(number) => Target::Number(number),
+            Err(_) => {
+                if with_balance(|balance| balance.goal(target)).is_none() {
+                    return Err(format!("unknown target '{}'", target));
+                }
+                Target::Balance(target.to_string())
+            }
+        };
+        Ok(Self {
+            quantity: Quantity::parse(quantity.trim())?,
+            comparison,
+            target,
+        })
+    }
+
+    pub fn target_value(&self) -> f64 {
+        match &self.target {
+            Target::Number(number) => *number,
+            Target::Balance(name) => with_balance(|balance| balance.goal(name)).unwrap_or(0.0),
+        }
+    }
+
+    pub fn is_met(&self, value: f64) -> bool {
+        match self.comparison {
+            Comparison::AtLeast => value >= self.target_value(),
+            Comparison::AtMost => value <= self.target_value(),
+        }
+    }
+
+    /// From 0 to 1, where 1 means that the condition is met.
+    pub fn progress(&self, value: f64) -> f64 {
+        if self.is_met(value) {
+            return 1.0;
+        }
+        let target = self.target_value();
+        let ratio = match self.comparison {
+            Comparison::AtLeast => value / target,
+            // +1 so that reaching 0 is possible
+            Comparison::AtMost => (target.max(0.0) + 1.0) / (value + 1.0),
+        };
+        ratio.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Objective {
+    pub name: String,
+    pub title: String,
+    pub dialog: Vec<String>,
+    /// indexes of the objectives that have to be completed before this one
+    pub after: Vec<usize>,
+    pub conditions: Vec<Condition>,
+    /// the trees stop aging once this objective is completed
+    pub stops_aging: bool,
+}
+
+impl Objective {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            title: name.to_string(),
+            dialog: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+            stops_aging: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Campaign {
+    /// the file it was loaded from, or None for the default campaign. The snapshots save it to
+    /// know which campaign the completed objectives belong to.
+    pub path: Option<String>,
+    pub objectives: Vec<Objective>,
+}
+
+impl Campaign {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        let campaign = Self::parse(&text).map_err(|e| format!("in {}: {}", path, e))?;
+        Ok(Self {
+            path: Some(path.to_string()),
+            ..campaign
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut objectives = Vec::<Objective>::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if let Some(section) = trimmed.strip_prefix('[') {
+                let name = section
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("line {}: expected '[objective_name]'", line_number))?
+                    .trim();
+                if objectives.iter().any(|objective| objective.name == name) {
+                    return Err(format!(
+                        "line {}: objective '{}' is defined more than once",
+                        line_number, name
+                    ));
+                }
+                objectives.push(Objective::new(name));
+                continue;
+            }
+            let (property, value) = trimmed
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'property = value'", line_number))?;
+            let (defined, objective) = match objectives.split_last_mut() {
+                Some((objective, defined)) => (defined, objective),
+                None => {
+                    return Err(format!(
+                        "line {}: expected '[objective_name]' before the properties",
+                        line_number
+                    ))
+                }
+            };
+            set_property(objective, defined, property.trim(), value.trim())
+                .map_err(|e| format!("line {}: {}", line_number, e))?;
+        }
+        if objectives.is_empty() {
+            return Err("there are no objectives".to_string());
+        }
+        for objective in &objectives {
+            if objective.conditions.is_empty() {
+                return Err(format!("objective '{}' has no conditions", objective.name));
+            }
+        }
+        Ok(Self {
+            path: None,
+            objectives,
+        })
+    }
+
+    /// The campaign is defined in a file that is part of the build, so a malformed file is a bug
+    /// (caught by the tests) and it panics.
+    pub fn default_campaign() -> Self {
+        Self::parse(DEFAULT_OBJECTIVES)
+            .unwrap_or_else(|e| panic!("invalid assets/objectives.txt: {}", e))
+    }
+}
+
+/// `defined` are the objectives before this one, which are the only ones it can come after.
+fn set_property(
+    objective: &mut Objective,
+    defined: &[Objective],
+    property: &str,
+    value: &str,
+) -> Result<(), String> {
+    match property {
+        "title" => objective.title = value.to_string(),
+        "dialog" => objective.dialog.push(value.to_string()),
+        "after" => {
+            for name in value.split(',').map(|name| name.trim()) {
+                let index = defined
+                    .iter()
+                    .position(|defined| defined.name == name)
+                    .ok_or_else(|| format!("'{}' is not an objective defined above", name))?;
+                objective.after.push(index);
+            }
+        }
+        "condition" => objective.conditions.push(Condition::parse(value)?),
+        "effect" => match value {
+            "stop_aging" => objective.stops_aging = true,
+            _ => return Err(format!("unknown effect '{}'", value)),
+        },
+        _ => return Err(format!("unknown property '{}'", property)),
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ObjectiveStatus {
+    /// waiting for other objectives to be completed
+    Locked,
+    Active,
+    Completed(AgeInMinutes),
+}
+
+pub struct Objectives {
+    pub campaign: Campaign,
+    /// when each objective of the campaign was completed, in the same order
+    completed: Vec<Option<AgeInMinutes>>,
+}
+
+impl Objectives {
+    pub fn new(campaign: Campaign) -> Self {
+        let completed = vec![None; campaign.objectives.len()];
+        Self {
+            campaign,
+            completed,
+        }
+    }
+
+    pub fn status(&self, index: usize) -> ObjectiveStatus {
+        if let Some(age) = self.completed[index] {
+            ObjectiveStatus::Completed(age)
+        } else if self.campaign.objectives[index]
+            .after
+            .iter()
+            .all(|after| self.completed[*after].is_some())
+        {
+            ObjectiveStatus::Active
+        } else {
+            ObjectiveStatus::Locked
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Objective, ObjectiveStatus)> {
+        self.campaign
+            .objectives
+            .iter()
+            .enumerate()
+            .map(|(i, objective)| (objective, self.status(i)))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Objective> {
+        self.campaign
+            .objectives
+            .iter()
+            .find(|objective| objective.name == name)
+    }
+
+    pub fn completed_count(&self) -> usize {
+        self.completed.iter().flatten().count()
+    }
+
+    pub fn all_completed(&self) -> bool {
+        self.completed_count() == self.completed.len()
+    }
+
+    pub fn stops_aging(&self) -> bool {
+        self.iter().any(|(objective, status)| {
+            objective.stops_aging && matches!(status, ObjectiveStatus::Completed(_))
+        })
+    }
+
+    pub fn complete(&mut self, name: &str, age: AgeInMinutes) -> Result<(), String> {
+        let index = self
+            .campaign
+            .objectives
+            .iter()
+            .position(|objective| objective.name == name)
+            .ok_or_else(|| format!("unknown objective '{}'", name))?;
+        self.completed[index] = Some(age);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.completed.fill(None);
+    }
+}
+
+impl World {
+    pub fn measure(&self, quantity: Quantity) -> f64 {
+        match quantity {
+            Quantity::AirCleaned => self.networks.get_total_air_cleaned(),
+            Quantity::StoredResources => self.networks.get_stored_resources(),
+            Quantity::Life => self.life.len() as f64,
+            Quantity::Machines => self.networks.get_non_ship_machine_count() as f64,
+            Quantity::Minutes => self.age_in_minutes as f64,
+            Quantity::Tiles(tile) => {
+                let in_networks = self
+                    .networks
+                    .iter()
+                    .flat_map(|network| network.nodes())
+                    .filter(|node| node.tile == tile)
+                    .count();
+                let alive = self
+                    .life
+                    .iter()
+                    .filter(|position| self.map.get_cell(**position).tile_type == tile)
+                    .count();
+                (in_networks + alive) as f64
+            }
+        }
+    }
+
+    pub fn is_objective_met(&self, objective: &Objective) -> bool {
+        objective
+            .conditions
+            .iter()
+            .all(|condition| condition.is_met(self.measure(condition.quantity)))
+    }
+
+    /// Completes the active objectives whose conditions are met, and finishes the game when all
+    /// of them are completed.
+    pub(super) fn update_objectives(&mut self) {
+        if self.goal_state != GameGoalState::Started {
+            return;
+        }
+        let newly_completed = self
+            .objectives
+            .iter()
+            .filter(|(objective, status)| {
+                *status == ObjectiveStatus::Active && self.is_objective_met(objective)
+            })
+            .map(|(objective, _)| objective.name.clone())
+            .collect::<Vec<_>>();
+        for name in newly_completed {
+            self.objectives
+                .complete(&name, self.age_in_minutes)
+                .unwrap();
+            self.events.push(
+                self.age_in_minutes,
+                EventKind::ObjectiveCompleted(name),
+                None,
+            );
+        }
+        if self.objectives.all_completed() {
+            self.goal_state = GameGoalState::Finished(self.age_in_minutes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::CellIndex;
+
+    #[test]
+    fn test_default_campaign() {
+        let campaign = Campaign::default_campaign();
+        let names = campaign
+            .objectives
+            .iter()
+            .map(|objective| objective.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["clean_air", "restore_nature"]);
+        assert_eq!(campaign.path, None);
+        assert!(campaign.objectives[0].stops_aging);
+        assert_eq!(campaign.objectives[1].after, vec![0]);
+        assert_eq!(
+            campaign.objectives[1].conditions[0],
+            Condition {
+                quantity: Quantity::Machines,
+                comparison: Comparison::AtMost,
+                target: Target::Number(0.0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| Campaign::parse(text).unwrap_err();
+        assert_eq!(
+            error("title = x"),
+            "line 1: expected '[objective_name]' before the properties"
+        );
+        assert_eq!(
+            error("[a]\ncondition = trees >= 3"),
+            "line 2: unknown quantity 'trees'"
+        );
+        assert_eq!(
+            error("[a]\ncondition = tiles:WallRock >= 3"),
+            "line 2: only the tiles of networks and trees can be counted, not 'WallRock'"
+        );
+        assert_eq!(
+            error("[a]\ncondition = life >= many"),
+            "line 2: unknown target 'many'"
+        );
+        assert_eq!(
+            error("[a]\nafter = b\n[b]"),
+            "line 2: 'b' is not an objective defined above"
+        );
+        assert_eq!(error("[a]\ndialog = hi"), "objective 'a' has no conditions");
+        assert_eq!(error("# nothing"), "there are no objectives");
+    }
+
+    #[test]
+    fn test_progress() {
+        let condition = Condition::parse("machines <= 0").unwrap();
+        assert_eq!(condition.progress(9.0), 0.1);
+        assert_eq!(condition.progress(0.0), 1.0);
+        let condition = Condition::parse("life >= 50").unwrap();
+        assert_eq!(condition.progress(25.0), 0.5);
+        assert!(!condition.is_met(49.0));
+        assert!(condition.is_met(50.0));
+    }
+
+    #[test]
+    fn test_count_tiles() {
+        let world = World::new();
+        assert_eq!(world.measure(Quantity::Tiles(TileType::MachineShip)), 1.0);
+        assert_eq!(world.measure(Quantity::Tiles(TileType::TreeHealthy)), 0.0);
+    }
+
+    #[test]
+    fn test_campaign_is_won_in_order() {
+        let mut world = World::new();
+        world.goal_state = GameGoalState::Started;
+        world.age_in_minutes = 30;
+        let trees_required = with_balance(|balance| balance.life_count_required_for_winning);
+        for i in 0..trees_required {
+            world.life.insert(CellIndex::new(i as i32, 0, 0));
+        }
+        world.update_objectives();
+        assert_eq!(world.objectives.status(1), ObjectiveStatus::Locked);
+        assert_eq!(world.goal_state, GameGoalState::Started);
+
+        world
+            .networks
+            .set_production(with_balance(|balance| balance.goal_air_cleaned));
+        world.update_objectives();
+        assert_eq!(world.objectives.status(0), ObjectiveStatus::Completed(30));
+        assert!(world.objectives.stops_aging());
+        assert_eq!(
+            world.events.iter_newest().next().unwrap().kind,
+            EventKind::ObjectiveCompleted("clean_air".to_string())
+        );
+        assert_eq!(world.objectives.status(1), ObjectiveStatus::Active);
+        assert_eq!(world.goal_state, GameGoalState::Started);
+
+        world.age_in_minutes = 40;
+        world.update_objectives();
+        assert_eq!(world.objectives.status(1), ObjectiveStatus::Completed(40));
+        assert_eq!(world.goal_state, GameGoalState::Finished(40));
+    }
+}
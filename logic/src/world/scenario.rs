@@ -0,0 +1,341 @@
+//! A scenario sets the starting map and rules of a game, so that tests and tutorials can start
+//! from hand-crafted situations. It's a text file with lines like `ship = 0 1 0`, loaded with
+//! `--scenario`. See `assets/scenarios/garden.txt` for an example with all the options.
+
+use crate::screen::gui::format_units::{format_grams, Grams};
+use crate::world::map::cell::{ages, default_health};
+use crate::world::map::transform_cells::Transformation;
+use crate::world::map::{Cell, CellCubeIterator, CellIndex, Map, MapType, TileType};
+use crate::world::robots::Robot;
+use crate::world::World;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScenarioMap {
+    Generated(MapType),
+    /// only air, apart from the tiles of the scenario
+    Tiles,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    pub map: ScenarioMap,
+    /// for the generated maps. Without it, each game is different
+    pub seed: Option<i64>,
+    /// inclusive boxes of cells, applied in order after generating the map. They don't
+    /// overwrite the ship
+    pub fills: Vec<(CellIndex, CellIndex, TileType)>,
+    /// needed in `ScenarioMap::Tiles`, as the generated maps choose their own
+    pub ship: Option<CellIndex>,
+    /// grams of material in the ship, instead of the value of the balance file
+    pub resources: Option<Grams>,
+    /// if empty, a robot starts at the ship
+    pub robots: Vec<CellIndex>,
+    /// the tiles that can be built, or None if all of them can
+    pub unlocked_tiles: Option<Vec<TileType>>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("in {}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut map = None;
+        let mut scenario = Scenario {
+            map: ScenarioMap::Tiles,
+            seed: None,
+            fills: Vec::new(),
+            ship: None,
+            resources: None,
+            robots: Vec::new(),
+            unlocked_tiles: None,
+        };
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = line_index + 1;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'name = value'", line_number))?;
+            let key = key.trim();
+            let words = value.split_whitespace().collect::<Vec<_>>();
+            let result = match key {
+                "map" => parse_map(&words).map(|parsed| map = Some(parsed)),
+                "seed" => parse_number(key, &words, 0).map(|seed| scenario.seed = Some(seed)),
+                "fill" => expect_words(key, &words, 7).and_then(|_| {
+                    let tile = TileType::parse_placeable(words[6])?;
+                    let corner = parse_position(key, &words, 0)?;
+                    let other_corner = parse_position(key, &words, 3)?;
+                    scenario.fills.push((corner, other_corner, tile));
+                    Ok(())
+                }),
+                "tile" => expect_words(key, &words, 4).and_then(|_| {
+                    let position = parse_position(key, &words, 0)?;
+                    scenario
+                        .fills
+                        .push((position, position, TileType::parse_placeable(words[3])?));
+                    Ok(())
+                }),
+                "ship" => expect_words(key, &words, 3)
+                    .and_then(|_| parse_position(key, &words, 0))
+                    .map(|ship| scenario.ship = Some(ship)),
+                "resources" => parse_number(key, &words, 0)
+                    .map(|resources| scenario.resources = Some(resources)),
+                "robot" => expect_words(key, &words, 3)
+                    .and_then(|_| parse_position(key, &words, 0))
+                    .map(|robot| scenario.robots.push(robot)),
+                "unlock" => parse_unlocked(value).map(|unlocked| {
+                    scenario
+                        .unlocked_tiles
+                        .get_or_insert_with(Vec::new)
+                        .extend(unlocked)
+                }),
+                _ => Err(format!("unknown name '{}'", key)),
+            };
+            result.map_err(|e| format!("line {}: {}", line_number, e))?;
+        }
+        scenario.map = map.ok_or_else(|| "missing 'map'".to_string())?;
+        if scenario.map == ScenarioMap::Tiles && scenario.ship.is_none() {
+            return Err("a 'tiles' map needs a 'ship'".to_string());
+        }
+        Ok(scenario)
+    }
+}
+
+impl World {
+    pub fn new_with_scenario(
+        profile: bool,
+        fluids: bool,
+        scenario: &Scenario,
+    ) -> Result<Self, String> {
+        let mut map = match scenario.map {
+            ScenarioMap::Generated(map_type) => {
+                Map::new_generated_with_seed(map_type, scenario.seed)
+            }
+            ScenarioMap::Tiles => {
+                let mut map = Map::new();
+                for position in CellCubeIterator::new(map.min_cell(), map.max_cell()) {
+                    *map.get_cell_mut(position) = Cell::new(TileType::Air);
+                }
+                map
+            }
+        };
+        if let Some(ship) = scenario.ship {
+            map.place_ship(in_map(&map, ship)?);
+        }
+        let ship = map.get_ship_position();
+        let mut cells = Vec::new();
+        for (corner, other_corner, tile) in &scenario.fills {
+            in_map(&map, *corner)?;
+            in_map(&map, *other_corner)?;
+            for position in CellCubeIterator::new_from_mixed(*corner, *other_corner) {
+                if Some(position) == ship {
+                    continue;
+                }
+                let mut cell = *map.get_cell(position);
+                Transformation::to(*tile).apply(&mut cell);
+                if ages(*tile) {
+                    cell.health = default_health();
+                }
+                cells.push((position, cell));
+            }
+        }
+
+        let mut world = World::new_with_map(profile, fluids, map);
+        // the fills go through the World to add the machines to the networks and the trees to life
+        world.force_cells(cells, 0.0);
+        if !scenario.robots.is_empty() {
+            world.robots = Vec::new();
+            for position in &scenario.robots {
+                world.robots.push(Robot {
+                    position: in_map(&world.map, *position)?,
+                });
+            }
+        }
+        if let Some(resources) = scenario.resources {
+            let ship_network = world.networks.iter_mut().next().unwrap();
+            let overflow =
+                ship_network.try_add_resources(resources - ship_network.get_stored_resources());
+            if overflow > 0.0 {
+                return Err(format!(
+                    "the resources don't fit in the spaceship, which can store {}",
+                    format_grams(ship_network.get_storage_capacity())
+                ));
+            }
+        }
+        world.unlocked_tiles = scenario.unlocked_tiles.clone();
+        Ok(world)
+    }
+}
+
+fn in_map(map: &Map, position: CellIndex) -> Result<CellIndex, String> {
+    if map.in_range(position) {
+        Ok(position)
+    } else {
+        Err(format!("{} is out of the map", position))
+    }
+}
+
+/// The generated maps can have parameters, like `caves hollowness=0.5`. See `MapType::parse()`.
+fn parse_map(words: &[&str]) -> Result<ScenarioMap, String> {
+    if words == ["tiles"] {
+        return Ok(ScenarioMap::Tiles);
+    } else if words.is_empty() {
+        expect_words("map", words, 1)?;
+    }
+    MapType::parse(&words.join(" ")).map(ScenarioMap::Generated)
+}
+
+fn parse_unlocked(value: &str) -> Result<Vec<TileType>, String> {
+    value
+        .split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(TileType::parse_placeable)
+        .collect()
+}
+
+fn expect_words(key: &str, words: &[&str], count: usize) -> Result<(), String> {
+    if words.len() == count {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{}' needs {} values but got {}",
+            key,
+            count,
+            words.len()
+        ))
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(
+    key: &str,
+    words: &[&str],
+    index: usize,
+) -> Result<T, String> {
+    expect_words(key, words, index + 1)?;
+    words[index]
+        .parse::<T>()
+        .map_err(|_| format!("'{}' has an invalid value '{}'", key, words[index]))
+}
+
+/// reads the 3 coordinates starting at `index`
+fn parse_position(key: &str, words: &[&str], index: usize) -> Result<CellIndex, String> {
+    let coordinate = |i: usize| {
+        words[i]
+            .parse::<i32>()
+            .map_err(|_| format!("'{}' has an invalid coordinate '{}'", key, words[i]))
+    };
+    Ok(CellIndex::new(
+        coordinate(index)?,
+        coordinate(index + 1)?,
+        coordinate(index + 2)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::transform_cells::TransformationFailure;
+    use crate::world::{Task, TransformationTask};
+    use std::collections::HashSet;
+
+    const GARDEN: &str = include_str!("../../../assets/scenarios/garden.txt");
+
+    #[test]
+    fn test_example_scenario() {
+        let scenario = Scenario::parse(GARDEN).unwrap();
+        let world = World::new_with_scenario(false, false, &scenario).unwrap();
+        assert_eq!(world.validate(), Vec::new());
+        let ship = world.map.get_ship_position().unwrap();
+        assert_eq!(Some(ship), scenario.ship);
+        assert_eq!(world.map.get_cell(ship).tile_type, TileType::MachineShip);
+        assert_eq!(world.robots.len(), scenario.robots.len());
+        assert_eq!(
+            world.networks.get_stored_resources(),
+            scenario.resources.unwrap()
+        );
+        assert!(world.life.len() > 0);
+        assert!(world.networks.get_non_ship_machine_count() > 0);
+    }
+
+    #[test]
+    fn test_generated_map() {
+        let scenario = Scenario::parse(
+            "map = island\n\
+             tile = 0 0 1 WallDirt\n\
+             unlock = TreeHealthy, Air\n",
+        )
+        .unwrap();
+        let world = World::new_with_scenario(false, false, &scenario).unwrap();
+        assert_eq!(
+            world.map.get_cell(CellIndex::new(0, 0, 1)).tile_type,
+            TileType::WallDirt
+        );
+        assert_eq!(world.robots.len(), 1);
+        assert!(world.is_unlocked(TileType::Air));
+        assert!(!world.is_unlocked(TileType::Wire));
+    }
+
+    #[test]
+    fn test_locked_tiles_are_not_built() {
+        let scenario = Scenario::parse("map = island\nunlock = TreeHealthy\n").unwrap();
+        let mut world = World::new_with_scenario(false, false, &scenario).unwrap();
+        let cell = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1);
+        let cells = HashSet::from([cell]);
+        let allowed = world
+            .allowed_transformations(&cells)
+            .iter()
+            .map(|transformation| transformation.new_tile_type)
+            .collect::<Vec<_>>();
+        assert_eq!(allowed, vec![TileType::TreeHealthy]);
+
+        let task = TransformationTask::new(cells, Transformation::to(TileType::Wire));
+        match world.try_build(task) {
+            Some(Task::Transform(remaining)) => assert_eq!(
+                remaining.blocked_because,
+                Some(HashSet::from([TransformationFailure::Locked]))
+            ),
+            _ => panic!("the wire should not be built"),
+        }
+        assert_eq!(world.map.get_cell(cell).tile_type, TileType::Air);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| Scenario::parse(text).unwrap_err();
+        assert_eq!(error("ship = 0 1 0"), "missing 'map'");
+        assert_eq!(error("map = tiles"), "a 'tiles' map needs a 'ship'");
+        assert_eq!(error("map = moon"), "line 1: unknown map 'moon'");
+        assert_eq!(
+            error("map = island\nfill = 0 0 0 1 1 Wire"),
+            "line 2: 'fill' needs 7 values but got 6"
+        );
+        assert_eq!(
+            error("map = island\nunlock = Wire, Tree"),
+            "line 2: unknown tile 'Tree'"
+        );
+        assert_eq!(
+            error("map = island\ntile = 0 0 0 Unset"),
+            "line 2: tile 'Unset' can not be placed"
+        );
+        assert_eq!(
+            error("map = island\nfill = 0 0 0 1 1 1 MachineShip"),
+            "line 2: tile 'MachineShip' can not be placed"
+        );
+        assert_eq!(
+            error("map = island\nrobot = 0 x 0"),
+            "line 2: 'robot' has an invalid coordinate 'x'"
+        );
+
+        let scenario = Scenario::parse("map = tiles\nship = 0 1000 0").unwrap();
+        assert_eq!(
+            World::new_with_scenario(false, false, &scenario).err(),
+            Some("[0, 1000, 0] is out of the map".to_string())
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Saves the World in the format of `crate::common::snapshot`. The event log, the undo history
+//! and the console log are not saved, so they start empty after loading.
+
+use crate::common::snapshot::{
+    cell_index_str, parse_cell_index, parse_tile, parse_value, Snapshot, SnapshotReader,
+    SnapshotWriter,
+};
+use crate::world::console::ConsoleLog;
+use crate::world::event_log::EventLog;
+use crate::world::fluids::{FluidMode, Fluids};
+use crate::world::game_state::GameState;
+use crate::world::history::History;
+use crate::world::map::transform_cells::Transformation;
+use crate::world::map::{CellIndex, Map, TileType};
+use crate::world::networks::Networks;
+use crate::world::objectives::{Campaign, ObjectiveStatus, Objectives};
+use crate::world::robots::Robot;
+use crate::world::{GameGoalState, Task, TransformationTask, World};
+use std::collections::{HashSet, VecDeque};
+
+impl Snapshot for World {
+    fn save(&self, snapshot: &mut SnapshotWriter) {
+        snapshot.line("world.age_in_minutes", self.age_in_minutes);
+        snapshot.line("world.goal_state", goal_state_str(self.goal_state));
+        if let Some(path) = &self.objectives.campaign.path {
+            snapshot.line("world.campaign", path);
+        }
+        for (objective, status) in self.objectives.iter() {
+            if let ObjectiveStatus::Completed(age) = status {
+                snapshot.line("world.objective", format!("{} {}", objective.name, age));
+            }
+        }
+        if let Some(unlocked_tiles) = &self.unlocked_tiles {
+            let names = unlocked_tiles.iter().map(|tile| format!("{:?}", tile));
+            snapshot.line("world.unlocked_tiles", names.collect::<Vec<_>>().join(" "));
+        }
+        for robot in &self.robots {
+            snapshot.line("world.robot", cell_index_str(robot.position));
+        }
+        for position in &self.aging_tiles {
+            snapshot.line("world.aging_tile", cell_index_str(*position));
+        }
+        for position in &self.life {
+            snapshot.line("world.life", cell_index_str(*position));
+        }
+        for (i_task, task) in self.task_queue.iter().enumerate() {
+            match task {
+                Task::Transform(transformation_task) => {
+                    snapshot.line(
+                        "world.task.transform",
+                        format!(
+                            "{} {:?}",
+                            i_task, transformation_task.transformation.new_tile_type
+                        ),
+                    );
+                    for position in &transformation_task.to_transform {
+                        snapshot.line(
+                            "world.task.cell",
+                            format!("{} {}", i_task, cell_index_str(*position)),
+                        );
+                    }
+                }
+                Task::Movement(position) => {
+                    snapshot.line(
+                        "world.task.movement",
+                        format!("{} {}", i_task, cell_index_str(*position)),
+                    );
+                }
+            }
+        }
+        self.game_state.save(snapshot);
+        self.map.save(snapshot);
+        self.networks.save(snapshot);
+    }
+
+    fn load(snapshot: &SnapshotReader) -> Result<Self, String> {
+        let game_state = GameState::load(snapshot)?;
+        let mut fluids = Fluids::new(FluidMode::InStages);
+        fluids.set_profile(game_state.profile);
+        Ok(World {
+            map: Map::load(snapshot)?,
+            fluids,
+            robots: parse_positions(snapshot, "world.robot")?
+                .into_iter()
+                .map(|position| Robot { position })
+                .collect(),
+            task_queue: parse_tasks(snapshot)?,
+            networks: Networks::load(snapshot)?,
+            aging_tiles: parse_positions(snapshot, "world.aging_tile")?
+                .into_iter()
+                .collect(),
+            life: parse_positions(snapshot, "world.life")?
+                .into_iter()
+                .collect(),
+            game_state,
+            goal_state: parse_goal_state(snapshot.get_single("world.goal_state")?)?,
+            objectives: parse_objectives(snapshot)?,
+            unlocked_tiles: parse_unlocked_tiles(snapshot)?,
+            age_in_minutes: snapshot.get("world.age_in_minutes")?,
+            events: EventLog::new(),
+            history: History::new(),
+            console: ConsoleLog::new(),
+        })
+    }
+}
+
+fn goal_state_str(goal_state: GameGoalState) -> String {
+    match goal_state {
+        GameGoalState::Finished(age) => format!("Finished {}", age),
+        _ => format!("{:?}", goal_state),
+    }
+}
+
+fn parse_goal_state(values: &[String]) -> Result<GameGoalState, String> {
+    let key = "world.goal_state";
+    let goal_state = match parse_value::<String>(values, 0, key)?.as_str() {
+        "InitialDialog" => GameGoalState::InitialDialog,
+        "Started" => GameGoalState::Started,
+        // saved before the objectives existed. They are completed again in the next tick
+        "ReachedProduction" => GameGoalState::Started,
+        "Finished" => GameGoalState::Finished(parse_value(values, 1, key)?),
+        "PostFinished" => GameGoalState::PostFinished,
+        other => return Err(format!("'{}' has an unknown value '{}'", key, other)),
+    };
+    Ok(goal_state)
+}
+
+/// The campaign is loaded again from its file, as only the completed objectives are saved.
+fn parse_objectives(snapshot: &SnapshotReader) -> Result<Objectives, String> {
+    let campaign = match snapshot.get_all("world.campaign").first() {
+        None => Campaign::default_campaign(),
+        Some(words) => {
+            let path = words.join(" ");
+            Campaign::load(&path).map_err(|e| format!("unknown campaign '{}': {}", path, e))?
+        }
+    };
+    let mut objectives = Objectives::new(campaign);
+    let key = "world.objective";
+    for values in snapshot.get_all(key) {
+        let name = parse_value::<String>(values, 0, key)?;
+        objectives.complete(&name, parse_value(values, 1, key)?)?;
+    }
+    Ok(objectives)
+}
+
+/// Missing if all the tiles are unlocked.
+fn parse_unlocked_tiles(snapshot: &SnapshotReader) -> Result<Option<Vec<TileType>>, String> {
+    let key = "world.unlocked_tiles";
+    match snapshot.get_all(key).first() {
+        Some(values) => (0..values.len())
+            .map(|i| parse_tile(values, i, key))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+fn parse_positions(snapshot: &SnapshotReader, key: &str) -> Result<Vec<CellIndex>, String> {
+    snapshot
+        .get_all(key)
+        .iter()
+        .map(|values| parse_cell_index(values, 0, key))
+        .collect()
+}
+
+/// The tasks are saved with their index in the queue. The blocked reasons are not saved, as
+/// they are computed again the next time the robots try the task.
+fn parse_tasks(snapshot: &SnapshotReader) -> Result<VecDeque<Task>, String> {
+    let mut tasks = Vec::<(usize, Task)>::new();
+    let key = "world.task.transform";
+    for values in snapshot.get_all(key) {
+        let transformation = Transformation::to(parse_tile(values, 1, key)?);
+        let task = TransformationTask::new(HashSet::new(), transformation);
+        tasks.push((parse_value(values, 0, key)?, Task::Transform(task)));
+    }
+    let key = "world.task.movement";
+    for values in snapshot.get_all(key) {
+        let position = parse_cell_index(values, 1, key)?;
+        tasks.push((parse_value(values, 0, key)?, Task::Movement(position)));
+    }
+    let key = "world.task.cell";
+    for values in snapshot.get_all(key) {
+        let i_task: usize = parse_value(values, 0, key)?;
+        let task = tasks.iter_mut().find(|(i, _)| *i == i_task);
+        match task {
+            Some((_, Task::Transform(task))) => {
+                task.to_transform.insert(parse_cell_index(values, 1, key)?);
+            }
+            _ => {
+                return Err(format!(
+                    "'{}' refers to a missing transformation task {}",
+                    key, i_task
+                ))
+            }
+        }
+    }
+    tasks.sort_by_key(|(i_task, _)| *i_task);
+    Ok(tasks.into_iter().map(|(_, task)| task).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_roundtrip() {
+        let mut world = World::new();
+        let ship = world.map.get_ship_position().unwrap();
+        world.age_in_minutes = 12;
+        world.goal_state = GameGoalState::Finished(10);
+        world.objectives.complete("clean_air", 5).unwrap();
+        world.unlocked_tiles = Some(vec![TileType::Wire, TileType::TreeHealthy]);
+        world
+            .task_queue
+            .push_back(Task::Transform(TransformationTask::new(
+                HashSet::from([
+                    ship + CellIndex::new(3, 0, 0),
+                    ship + CellIndex::new(4, 0, 0),
+                ]),
+                Transformation::to(TileType::Wire),
+            )));
+        world.task_queue.push_back(Task::Movement(ship));
+        world.networks.add(
+            ship + CellIndex::new(1, 0, 0),
+            TileType::MachineAirCleaner,
+            TileType::Air,
+        );
+
+        let mut writer = SnapshotWriter::new();
+        world.save(&mut writer);
+        let text = writer.finish();
+        let loaded = World::load(&SnapshotReader::parse(&text).unwrap()).unwrap();
+
+        let mut writer = SnapshotWriter::new();
+        loaded.save(&mut writer);
+        let mut lines = text.lines().collect::<Vec<_>>();
+        lines.sort();
+        let reloaded_text = writer.finish();
+        let mut reloaded_lines = reloaded_text.lines().collect::<Vec<_>>();
+        reloaded_lines.sort();
+        assert_eq!(lines, reloaded_lines);
+        assert_eq!(loaded.goal_state, GameGoalState::Finished(10));
+        assert_eq!(loaded.objectives.status(0), ObjectiveStatus::Completed(5));
+        assert_eq!(
+            loaded.unlocked_tiles,
+            Some(vec![TileType::Wire, TileType::TreeHealthy])
+        );
+        assert_eq!(loaded.task_queue.len(), 2);
+        assert_eq!(
+            loaded
+                .networks
+                .get(ship + CellIndex::new(1, 0, 0))
+                .map(|n| n.tile),
+            Some(TileType::MachineAirCleaner)
+        );
+    }
+
+    #[test]
+    fn test_missing_field_is_reported() {
+        let mut world = World::new();
+        world.age_in_minutes = 12;
+        let mut writer = SnapshotWriter::new();
+        world.save(&mut writer);
+        let text = writer
+            .finish()
+            .lines()
+            .filter(|line| !line.starts_with("world.age_in_minutes"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let error = World::load(&SnapshotReader::parse(&text).unwrap()).err();
+        assert_eq!(error, Some("missing 'world.age_in_minutes'".to_string()));
+    }
+
+    #[test]
+    fn test_campaign_is_kept() {
+        // relative to the crate, where the tests run
+        let path = "../assets/objectives.txt";
+        let mut world = World::new();
+        world.objectives = Objectives::new(Campaign::load(path).unwrap());
+        world.objectives.complete("clean_air", 5).unwrap();
+        let mut writer = SnapshotWriter::new();
+        world.save(&mut writer);
+        let text = writer.finish();
+        let loaded = World::load(&SnapshotReader::parse(&text).unwrap()).unwrap();
+        assert_eq!(loaded.objectives.campaign.path.as_deref(), Some(path));
+        assert_eq!(loaded.objectives.status(0), ObjectiveStatus::Completed(5));
+
+        let text = text.replace(path, "missing_campaign.txt");
+        let error = World::load(&SnapshotReader::parse(&text).unwrap())
+            .err()
+            .unwrap();
+        assert!(
+            error.starts_with("unknown campaign 'missing_campaign.txt'"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Checks that the parts of the World that duplicate information agree with each other. Meant
+//! for tests and for debugging (see `--validate`), as it goes through the whole map.
+
+use crate::screen::gui::format_units::Grams;
+use crate::world::map::cell::ages;
+use crate::world::map::{CellCubeIterator, CellIndex, TileType};
+use crate::world::World;
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// the network has a node in this position, but the map has a different tile there
+    NodeTileMismatch {
+        position: CellIndex,
+        node_tile: TileType,
+        map_tile: TileType,
+    },
+    /// the network index is in the order of `Networks::iter()`
+    DisconnectedNetwork { network_index: usize },
+    StorageOverCapacity {
+        network_index: usize,
+        stored: Grams,
+        capacity: Grams,
+    },
+    /// the map has a living tree in this position, but it's not counted in `World::life`
+    TreeMissingFromLife { position: CellIndex },
+    /// `World::life` has this position, but the map doesn't have a living tree there
+    LifeWithoutTree { position: CellIndex },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::NodeTileMismatch {
+                position,
+                node_tile,
+                map_tile,
+            } => write!(
+                f,
+                "the network node at {} is {:?} but the map has {:?}",
+                position, node_tile, map_tile
+            ),
+            Violation::DisconnectedNetwork { network_index } => {
+                write!(f, "network {} is not connected", network_index)
+            }
+            Violation::StorageOverCapacity {
+                network_index,
+                stored,
+                capacity,
+            } => write!(
+                f,
+                "network {} stores {} grams but its capacity is {} grams",
+                network_index, stored, capacity
+            ),
+            Violation::TreeMissingFromLife { position } => {
+                write!(f, "the tree at {} is not counted as life", position)
+            }
+            Violation::LifeWithoutTree { position } => {
+                write!(f, "{} is counted as life but has no living tree", position)
+            }
+        }
+    }
+}
+
+impl World {
+    /// Returns every broken invariant, or an empty Vec if the World is consistent.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        self.validate_networks(&mut violations);
+        self.validate_life(&mut violations);
+        violations
+    }
+
+    fn validate_networks(&self, violations: &mut Vec<Violation>) {
+        for (network_index, network) in self.networks.iter().enumerate() {
+            for node in network.nodes() {
+                let map_tile = self.map.get_cell(node.position).tile_type;
+                if map_tile != node.tile {
+                    violations.push(Violation::NodeTileMismatch {
+                        position: node.position,
+                        node_tile: node.tile,
+                        map_tile,
+                    });
+                }
+            }
+            if !network.is_connected() {
+                violations.push(Violation::DisconnectedNetwork { network_index });
+            }
+            let stored = network.get_stored_resources();
+            let capacity = network.get_storage_capacity();
+            if stored > capacity {
+                violations.push(Violation::StorageOverCapacity {
+                    network_index,
+                    stored,
+                    capacity,
+                });
+            }
+        }
+    }
+
+    fn validate_life(&self, violations: &mut Vec<Violation>) {
+        for position in CellCubeIterator::new(self.map.min_cell(), self.map.max_cell()) {
+            if let Some(cell) = self.map.get_cell_optional(position) {
+                if ages(cell.tile_type) && !self.life.contains(&position) {
+                    violations.push(Violation::TreeMissingFromLife { position });
+                }
+            }
+        }
+        for position in &self.life {
+            let is_alive = self
+                .map
+                .get_cell_optional(*position)
+                .map(|cell| ages(cell.tile_type))
+                .unwrap_or(false);
+            if !is_alive {
+                violations.push(Violation::LifeWithoutTree {
+                    position: *position,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::networks::network::Node;
+
+    fn next_to_ship(world: &World) -> CellIndex {
+        world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 1)
+    }
+
+    #[test]
+    fn test_new_world_is_valid() {
+        assert_eq!(World::new().validate(), Vec::new());
+    }
+
+    #[test]
+    fn test_node_tile_mismatch() {
+        let mut world = World::new();
+        let position = next_to_ship(&world);
+        world
+            .networks
+            .add(position, TileType::MachineAirCleaner, TileType::Air);
+        assert_eq!(
+            world.validate(),
+            vec![Violation::NodeTileMismatch {
+                position,
+                node_tile: TileType::MachineAirCleaner,
+                map_tile: TileType::Air,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_disconnected_network() {
+        let mut world = World::new();
+        let position = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, 2);
+        world.map.get_cell_mut(position).tile_type = TileType::MachineAirCleaner;
+        world
+            .networks
+            .iter_mut()
+            .next()
+            .unwrap()
+            .only_add(Node::new(position, TileType::MachineAirCleaner));
+        assert_eq!(
+            world.validate(),
+            vec![Violation::DisconnectedNetwork { network_index: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_storage_over_capacity() {
+        let mut world = World::new();
+        let ship_network = world.networks.iter_mut().next().unwrap();
+        let capacity = ship_network.get_storage_capacity();
+        ship_network.stored_resources = capacity + 1.0;
+        assert_eq!(
+            world.validate(),
+            vec![Violation::StorageOverCapacity {
+                network_index: 0,
+                stored: capacity + 1.0,
+                capacity,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_life_mismatches() {
+        let mut world = World::new();
+        let tree = next_to_ship(&world);
+        world.map.get_cell_mut(tree).tile_type = TileType::TreeHealthy;
+        let missing_tree = world.map.get_ship_position().unwrap() + CellIndex::new(0, 0, -1);
+        world.life.insert(missing_tree);
+        assert_eq!(
+            world.validate(),
+            vec![
+                Violation::TreeMissingFromLife { position: tree },
+                Violation::LifeWithoutTree {
+                    position: missing_tree
+                },
+            ]
+        );
+    }
+}